@@ -1,21 +1,49 @@
 //! Groups all the bot commands together. These then delegate to the model as needed
 
-use poise::serenity_prelude::Mentionable;
+use anyhow::Context as _;
+use poise::{
+    ChoiceParameter as _, CreateReply, FrameworkContext,
+    serenity_prelude::{
+        self as serenity, CreateAllowedMentions, FullEvent, GuildId, Interaction, Mentionable,
+    },
+};
+use std::sync::Arc;
 use tracing::{error, info, instrument, warn};
 
 use crate::{
     AuthorPreferredDisplay as _, Context, Data,
     commands::{
-        cohort_cmd::unranked,
+        audit::{audit, do_audit},
+        cohort_cmd::{cohort, unranked},
+        config_cmd::config,
         general::{help, ping, uptime},
+        guild::setup,
+        permissions::permissions,
         schedule::schedule,
     },
+    errors::bail_user,
+    model::{
+        audit::AuditAction,
+        cohort::{Cohort, program::ProgramKey},
+        guild::permissions::Capability,
+    },
 };
-pub use cohort_cmd::do_start_event;
+use cohort_cmd::do_dry_run;
+pub use cohort_cmd::{Effects, do_objective};
+pub use error_handler::on_error;
+mod audit;
 mod cohort_cmd;
+mod config_cmd;
+mod confirm;
+mod error_handler;
 mod general;
+mod guild;
+mod permissions;
 mod schedule;
 
+/// Stays below Discord's limit of 2000 characters per message
+const MAX_MESSAGE_LEN: usize = 1900;
+
 /// Common info added to tracing for functions
 async fn tracing_handler_start(ctx: &Context<'_>) {
     info!("Author name: {}", ctx.author().name);
@@ -35,6 +63,156 @@ async fn call_to_parent_command(ctx: Context<'_>) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Replies so that only the author can see the response (prefix commands get a normal reply)
+async fn reply_ephemeral(ctx: &Context<'_>, msg: impl Into<String>) -> anyhow::Result<()> {
+    let builder = CreateReply::default().content(msg).ephemeral(true);
+    ctx.send(builder).await?;
+    Ok(())
+}
+
+/// Shortens the text to at most `max_len` bytes (cut on a character boundary) marking where it was cut
+fn truncate_text(text: &str, max_len: usize) -> String {
+    const MARKER: &str = "...";
+    if text.len() <= max_len {
+        return text.to_string();
+    }
+    let mut end = max_len.saturating_sub(MARKER.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{MARKER}", &text[..end])
+}
+
+/// Keeps as many whole lines from the start of the text as fit in `max_len` bytes
+///
+/// Returns the lines kept and how many there are so the caller can say how many were left out
+fn take_lines(text: &str, max_len: usize) -> (String, usize) {
+    let mut result = String::new();
+    let mut count = 0;
+    for line in text.lines() {
+        if result.len() + line.len() + 1 > max_len {
+            break;
+        }
+        result.push_str(line);
+        result.push('\n');
+        count += 1;
+    }
+    (result, count)
+}
+
+/// Splits the text between lines into parts that each fit in a message
+///
+/// Lines too long for a message on their own are shortened
+fn split_into_messages(text: &str) -> Vec<String> {
+    let mut chunks = vec![String::new()];
+    for line in text.lines() {
+        let line = truncate_text(line, MAX_MESSAGE_LEN);
+        let current = chunks.last_mut().expect("always has at least one chunk");
+        if !current.is_empty() && current.len() + line.len() + 1 > MAX_MESSAGE_LEN {
+            chunks.push(line);
+        } else {
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(&line);
+        }
+    }
+    chunks
+}
+
+/// Replies with the text in as many messages as needed without pinging the members it mentions
+async fn reply_in_chunks(ctx: Context<'_>, text: &str) -> anyhow::Result<()> {
+    for chunk in split_into_messages(text) {
+        ctx.send(
+            CreateReply::default()
+                .content(chunk)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// The server a command is for, commands sent by DM are for the home server
+fn command_guild_id(ctx: &Context<'_>) -> GuildId {
+    ctx.guild_id().unwrap_or(ctx.data().inner.home_guild_id)
+}
+
+/// Finds the cohort program a command is for
+///
+/// Uses the program named if there is one, otherwise the program that uses the channel (or the
+/// parent channel for threads) the command was sent in and falls back to the server's default program
+#[instrument(skip(ctx))]
+async fn resolve_program(
+    ctx: &Context<'_>,
+    program: Option<String>,
+) -> anyhow::Result<Arc<Cohort>> {
+    let data = ctx.data();
+    let guild_id = command_guild_id(ctx);
+    if let Some(name) = program {
+        return match data.programs_get(guild_id, &name)? {
+            Some(cohort) => Ok(cohort),
+            None => {
+                let names: Vec<String> = data
+                    .programs_names(guild_id)?
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect();
+                bail_user!(
+                    "No program named {name:?}. Valid programs are: {}",
+                    names.join(", ")
+                )
+            }
+        };
+    }
+    if let Some(cohort) = data.programs_find_by_channel(ctx.channel_id())? {
+        return Ok(cohort);
+    }
+    if let Some(parent_id) = ctx
+        .guild_channel()
+        .await
+        .and_then(|channel| channel.parent_id)
+        && let Some(cohort) = data.programs_find_by_channel(parent_id)?
+    {
+        return Ok(cohort);
+    }
+    data.programs_default(guild_id)
+}
+
+/// Finds the program a button press is for from the part of the button ID created by [`ProgramKey::to_custom_id_part`]
+///
+/// Buttons created before programs existed do not include a program and belong to the home server's default program
+fn resolve_program_for_interaction(
+    data: &Data,
+    program: Option<&str>,
+) -> anyhow::Result<Arc<Cohort>> {
+    let home_guild_id = data.inner.home_guild_id;
+    match program {
+        Some(part) => {
+            let (guild_id, name) = ProgramKey::parse_custom_id_part(part)?;
+            data.programs_get(guild_id.unwrap_or(home_guild_id), name)?
+                .with_context(|| format!("program {name:?} no longer exists"))
+        }
+        None => data.programs_default(home_guild_id),
+    }
+}
+
+/// Suggests the names of the programs that start with what has been typed so far
+async fn autocomplete_program(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let names = match ctx.data().programs_names(command_guild_id(&ctx)) {
+        Ok(names) => names,
+        Err(e) => {
+            error!("failed to get program names for autocomplete: {e:?}");
+            return Vec::new();
+        }
+    };
+    names
+        .iter()
+        .map(|x| x.to_string())
+        .filter(|x| x.to_lowercase().starts_with(&partial.to_lowercase()))
+        .collect()
+}
+
 pub fn commands_list() -> Vec<poise::Command<Data, anyhow::Error>> {
     vec![
         ping(),
@@ -42,23 +220,102 @@ pub fn commands_list() -> Vec<poise::Command<Data, anyhow::Error>> {
         general::version(),
         uptime(),
         unranked(),
+        cohort(),
         schedule(),
+        setup(),
+        config(),
+        permissions(),
+        audit(),
     ]
 }
 
+/// Handles events that are not commands (for example button presses on messages sent by the bot)
+pub async fn event_handler(
+    ctx: &serenity::Context,
+    event: &FullEvent,
+    _framework: FrameworkContext<'_, Data, anyhow::Error>,
+    data: &Data,
+) -> anyhow::Result<()> {
+    if let FullEvent::InteractionCreate {
+        interaction: Interaction::Component(interaction),
+    } = event
+    {
+        let custom_id = interaction.data.custom_id.as_str();
+        if custom_id.starts_with(cohort_cmd::CHECK_IN_PREFIX) {
+            cohort_cmd::handle_check_in_interaction(ctx, interaction, data).await?;
+        } else if custom_id.starts_with(cohort_cmd::REMATCH_PREFIX) {
+            cohort_cmd::handle_rematch_interaction(ctx, interaction, data).await?;
+        } else if custom_id.starts_with(cohort_cmd::FEEDBACK_PREFIX) {
+            cohort_cmd::handle_feedback_interaction(ctx, interaction, data).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks if the author is allowed to run commands that need the capability
+///
+/// Members with one of the admin roles can run every command, others need to have been given the
+/// capability (directly or through one of their roles) with `/permissions`
+async fn has_capability(ctx: Context<'_>, capability: Capability) -> anyhow::Result<bool> {
+    check_access(ctx, Some(capability)).await
+}
+
+/// Checks if the author has one of the admin roles, capabilities given with `/permissions` do not count
+///
+/// Used for the commands that change who can run commands so a capability can't be used to gain others
+async fn is_admin(ctx: Context<'_>) -> anyhow::Result<bool> {
+    check_access(ctx, None).await
+}
+
+/// Without a capability only the admin roles are allowed
 #[instrument(skip(ctx))]
-async fn is_auth(ctx: Context<'_>) -> anyhow::Result<bool> {
+async fn check_access(ctx: Context<'_>, capability: Option<Capability>) -> anyhow::Result<bool> {
     info!("START");
     let result;
-    let role_id = ctx.data().inner.shared_config.auth_role_id;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let Some(config) = data.guild_config(guild_id)? else {
+        warn!(
+            "User: {:?} ({}) attempted to execute {:?} in a server that has not been set up.",
+            ctx.author().name,
+            ctx.author().id,
+            ctx.command().qualified_name,
+        );
+        ctx.say("This server has not been set up yet, see `/setup`")
+            .await?;
+        return Ok(false);
+    };
+    let role_ids = config.auth_role_ids;
     if let Some(member) = ctx.author_member().await {
-        result = member.roles.contains(&role_id);
+        result = role_ids.iter().any(|x| member.roles.contains(x))
+            || match capability {
+                Some(capability) => data.guild_permissions(guild_id)?.allows(
+                    capability,
+                    member.user.id,
+                    &member.roles,
+                ),
+                None => false,
+            };
         if !result {
+            let mentions: Vec<String> = role_ids.iter().map(|x| x.mention().to_string()).collect();
+            let needs = match capability {
+                Some(capability) => format!("**{}**", capability.name()),
+                None => "an admin role".to_string(),
+            };
             ctx.reply(format!(
-                "You don't have permission to run this command. Please see someone from {} for assistance",
-                role_id.mention()
+                "You don't have permission to run this command (needs {needs}). Please see someone from {} for assistance",
+                mentions.join(" or ")
             ))
             .await?;
+            do_audit(
+                &ctx,
+                None,
+                AuditAction::PermissionDenied {
+                    command: ctx.command().qualified_name.clone(),
+                    capability,
+                },
+            )
+            .await?;
         }
     } else {
         result = false;
@@ -71,7 +328,7 @@ async fn is_auth(ctx: Context<'_>) -> anyhow::Result<bool> {
     };
     if !result {
         warn!(
-            "User: {:?} ({}) attempted to execute {:?} but they did not have role# {role_id}.",
+            "User: {:?} ({}) attempted to execute {:?} but they did not have {capability:?} or any of role# {role_ids:?}.",
             ctx.author().name,
             ctx.author().id,
             ctx.command().qualified_name,
@@ -80,3 +337,66 @@ async fn is_auth(ctx: Context<'_>) -> anyhow::Result<bool> {
     info!("END");
     Ok(result)
 }
+
+async fn can_manage_schedule(ctx: Context<'_>) -> anyhow::Result<bool> {
+    has_capability(ctx, Capability::ManageSchedule).await
+}
+
+async fn can_manage_cohort(ctx: Context<'_>) -> anyhow::Result<bool> {
+    has_capability(ctx, Capability::ManageCohort).await
+}
+
+async fn can_moderate_members(ctx: Context<'_>) -> anyhow::Result<bool> {
+    has_capability(ctx, Capability::ModerateMembers).await
+}
+
+async fn can_configure_bot(ctx: Context<'_>) -> anyhow::Result<bool> {
+    has_capability(ctx, Capability::ConfigureBot).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_text_cuts_on_char_boundary() {
+        assert_eq!(truncate_text("short", 10), "short");
+        assert_eq!(truncate_text("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_text("a bit too long", 10), "a bit t...");
+        // Each é is two bytes so the cut has to move back to a boundary
+        assert_eq!(truncate_text("éééééé", 8), "éé...");
+        assert!(truncate_text(&"x".repeat(3000), 2000).len() <= 2000);
+    }
+
+    #[test]
+    fn take_lines_keeps_whole_lines() {
+        assert_eq!(take_lines("", 10), (String::new(), 0));
+        assert_eq!(take_lines("a\nb\nc", 100), ("a\nb\nc\n".to_string(), 3));
+        assert_eq!(
+            take_lines("aaa\nbbb\nccc", 8),
+            ("aaa\nbbb\n".to_string(), 2)
+        );
+        assert_eq!(take_lines("too long\nb", 4), (String::new(), 0));
+    }
+
+    #[test]
+    fn split_into_messages_fits_each_message() {
+        assert_eq!(split_into_messages("a\nb"), vec!["a\nb"]);
+        let line = "y".repeat(100);
+        let text = vec![line.as_str(); 50].join("\n");
+        let chunks = split_into_messages(&text);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|x| x.len() <= MAX_MESSAGE_LEN));
+        assert_eq!(chunks.join("\n"), text);
+        let chunks = split_into_messages(&"z".repeat(5000));
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].len() <= MAX_MESSAGE_LEN);
+        let chunks = split_into_messages(&format!("{}\nnext", "w".repeat(MAX_MESSAGE_LEN)));
+        assert_eq!(
+            chunks.len(),
+            2,
+            "no empty message before a full length line"
+        );
+        assert_eq!(chunks[1], "next");
+    }
+}
@@ -0,0 +1,90 @@
+//! Records privileged actions in the server's audit log and lets admins search it
+
+use poise::serenity_prelude::{CreateAllowedMentions, CreateMessage, User};
+use tracing::{instrument, warn};
+
+use crate::{
+    Context,
+    commands::{
+        MAX_MESSAGE_LEN, autocomplete_program, can_moderate_members, command_guild_id,
+        reply_in_chunks, tracing_handler_start, truncate_text,
+    },
+    model::{
+        audit::{AuditAction, AuditActionKind, AuditEntry},
+        cohort::Cohort,
+        user_serde::UserRecordSupport as _,
+    },
+};
+
+/// Limit on how many entries are shown to keep the reply to a few messages
+const MAX_LISTED: usize = 20;
+
+/// Entries can include text written by admins (e.g. a ban reason) so each is shortened to this
+const MAX_ENTRY_LEN: usize = 300;
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members"
+)]
+#[instrument(name = "audit", skip(ctx))]
+/// Search the most recent privileged actions taken in this server
+pub async fn audit(
+    ctx: Context<'_>,
+    #[description = "Only show actions taken by or on this member"] user: Option<User>,
+    #[description = "Only show this kind of action"] action: Option<AuditActionKind>,
+    #[description = "Only show actions taken in this program"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    tracing_handler_start(&ctx).await;
+    let entries = ctx.data().audit_logs_search(
+        command_guild_id(&ctx),
+        user.map(|x| x.id.into()),
+        action,
+        program.as_deref(),
+        MAX_LISTED,
+    )?;
+    let mut msg = String::from("**Audit Log**\n");
+    if entries.is_empty() {
+        writeln!(msg, "No matching actions recorded")?;
+    }
+    for entry in entries {
+        writeln!(
+            msg,
+            "- {}",
+            truncate_text(&entry.to_string(), MAX_ENTRY_LEN)
+        )?;
+    }
+    reply_in_chunks(ctx, &msg).await?;
+    Ok(())
+}
+
+/// Records an action taken by the author in the server's audit log (noting the program if it is
+/// for one) then posts it to the audit channel if one is set
+#[instrument(skip(ctx, cohort))]
+pub async fn do_audit(
+    ctx: &Context<'_>,
+    cohort: Option<&Cohort>,
+    action: AuditAction,
+) -> anyhow::Result<()> {
+    let data = ctx.data();
+    let actor = ctx.author_id_number();
+    let guild_id = cohort.map_or_else(|| command_guild_id(ctx), |x| x.guild_id());
+    let entry = AuditEntry::new(actor, cohort.map(|x| x.program().clone()), action)?;
+    data.audit_logs_add(guild_id, entry.clone())?;
+    let Some(channel_id) = data.guild_config(guild_id)?.and_then(|x| x.channel_audit) else {
+        return Ok(());
+    };
+    // The entry mentions members, they should not be pinged each time an admin acts
+    let builder = CreateMessage::new()
+        .content(truncate_text(&entry.to_string(), MAX_MESSAGE_LEN))
+        .allowed_mentions(CreateAllowedMentions::new());
+    if let Err(e) = channel_id.send_message(ctx, builder).await {
+        warn!("failed to post audit entry to {channel_id} with error: {e:?}");
+    }
+    Ok(())
+}
@@ -1,15 +1,91 @@
 //! Groups the commands related to the unranked challenge
 
-use poise::serenity_prelude::{CacheHttp, ChannelId};
-use tracing::instrument;
+use poise::serenity_prelude::{ChannelId, Mentionable as _};
+use tracing::{info, instrument};
 
-use self::interested_list::register;
+use self::{
+    archive::{history, show},
+    check_in::{do_check_in, progress},
+    constraints::{exclusion, preference},
+    effects::Post,
+    eligibility::eligibility,
+    feedback::feedback,
+    grouping::{group_size, pair},
+    inactive::{inactive_after, rematch},
+    interested_list::{describe_scores_reset, do_scores_reset, register},
+    moderation::admin,
+    preview::pairs,
+    programs::program,
+    stats::{participation, stats},
+    waitlist::{max_participants, status},
+};
+pub use self::{
+    check_in::{CHECK_IN_PREFIX, handle_check_in_interaction},
+    dry_run::do_dry_run,
+    effects::Effects,
+    feedback::{FEEDBACK_PREFIX, handle_feedback_interaction},
+    inactive::{REMATCH_PREFIX, handle_rematch_interaction},
+};
 use crate::{
-    Context, Data,
-    commands::{call_to_parent_command, is_auth, tracing_handler_start},
+    Context,
+    commands::{
+        audit::do_audit, autocomplete_program, call_to_parent_command, can_manage_cohort,
+        confirm::confirm, reply_in_chunks, resolve_program, tracing_handler_start,
+    },
+    model::{audit::AuditAction, cohort::Cohort, guild::GuildConfig, schedule::Objective},
 };
 
+mod archive;
+mod check_in;
+mod constraints;
+mod dry_run;
+mod effects;
+mod eligibility;
+mod feedback;
+mod grouping;
+mod inactive;
 mod interested_list;
+mod membership;
+mod moderation;
+mod notifications;
+mod preview;
+mod programs;
+mod roles;
+mod stats;
+mod threads;
+mod waitlist;
+
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    subcommand_required,
+    subcommands(
+        "group_size",
+        "pair",
+        "pairs",
+        "exclusion",
+        "preference",
+        "progress",
+        "inactive_after",
+        "rematch",
+        "feedback",
+        "history",
+        "show",
+        "stats",
+        "participation",
+        "status",
+        "max_participants",
+        "eligibility",
+        "admin",
+        "program"
+    )
+)]
+#[instrument(name = "cohort", skip(ctx))]
+/// Commands related to running the accountability cohort
+pub async fn cohort(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
 
 #[poise::command(
     prefix_command,
@@ -25,21 +101,99 @@ pub async fn unranked(ctx: Context<'_>) -> anyhow::Result<()> {
     call_to_parent_command(ctx).await
 }
 
-#[poise::command(hide_in_help, prefix_command, guild_only = true, check = "is_auth")]
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
 #[instrument(name = "unranked-start_event", skip(ctx))]
-/// Resets ideas and scores for the start of the new event and sets the message with the leading idea
-pub async fn start_event(ctx: Context<'_>) -> anyhow::Result<()> {
+/// Archives the current cohort, resets registrations and announces that sign ups are open
+pub async fn start_event(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+    #[description = "Report what would be done without doing any of it"] dry_run: Option<bool>,
+) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    if dry_run.unwrap_or_default() {
+        let report = do_dry_run(
+            ctx.data(),
+            &cohort,
+            Objective::UnrankedStartEvent,
+            ctx.channel_id(),
+        )
+        .await?;
+        reply_in_chunks(ctx, &report).await?;
+        return Ok(());
+    }
+    let changes = format!(
+        "{}- Sign ups for the next cohort are announced in {}",
+        describe_scores_reset(ctx.data(), &cohort)?,
+        ctx.channel_id().mention()
+    );
+    if !confirm(
+        ctx,
+        &format!("Start the next {} cohort", cohort.program()),
+        &changes,
+    )
+    .await?
+    {
+        return Ok(());
+    }
     ctx.reply("Request started").await?;
-    do_start_event(ctx, ctx.channel_id(), ctx.data()).await?;
+    do_start_event(
+        &Effects::Live(&ctx),
+        ctx.channel_id(),
+        ctx.data().guild_config(cohort.guild_id())?.as_ref(),
+        &cohort,
+    )
+    .await?;
+    do_audit(&ctx, Some(&cohort), AuditAction::EventStarted).await?;
     Ok(())
 }
 
-#[instrument(skip(_cache_http, _data))]
+/// Archives the previous cohort, resets the registrations and lets members know sign ups are open
+#[instrument(skip(effects, config, cohort), fields(program = %cohort.program()))]
 pub async fn do_start_event(
-    _cache_http: impl CacheHttp,
+    effects: &Effects<'_>,
     channel_id: ChannelId,
-    _data: &Data,
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
 ) -> anyhow::Result<()> {
-    todo!()
+    info!("START");
+    do_scores_reset(effects, channel_id, config, cohort).await?;
+    effects
+        .send(channel_id, Post::text(sign_ups_open_message(cohort)))
+        .await?;
+    info!("END");
+    Ok(())
+}
+
+/// Runs the objective as the scheduler does, `channel_id` is where the start event posts its announcements
+pub async fn do_objective(
+    effects: &Effects<'_>,
+    objective: Objective,
+    channel_id: ChannelId,
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
+) -> anyhow::Result<()> {
+    match objective {
+        Objective::UnrankedStartEvent => do_start_event(effects, channel_id, config, cohort).await,
+        Objective::CohortCheckIn => do_check_in(effects, config, cohort).await,
+    }
+}
+
+fn sign_ups_open_message(cohort: &Cohort) -> String {
+    if cohort.program().is_default() {
+        "**Sign ups for the next cohort are open!** Use `/unranked register set` to join"
+            .to_string()
+    } else {
+        format!(
+            "**Sign ups for the next {0} cohort are open!** Use `/unranked register set program:{0}` to join",
+            cohort.program()
+        )
+    }
 }
@@ -0,0 +1,171 @@
+//! Groups the commands for looking back at past cohorts
+
+use std::num::NonZeroUsize;
+
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::{
+        autocomplete_program, reply_in_chunks, resolve_program, tracing_handler_start,
+        truncate_text,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            archive::{ArchivedCohort, ArchivedMember, CohortNumber},
+            feedback::FeedbackSummary,
+            record::CohortRecord,
+        },
+        schedule::UnixTimestamp,
+        user_serde::UserRecordSupport as _,
+    },
+};
+
+/// Limit on how many cohorts are listed to keep the reply short
+const MAX_LISTED: usize = 20;
+
+/// Goals are written by members so each is shortened to this when showing a whole cohort
+const MAX_GOAL_LEN: usize = 200;
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-history", skip(ctx))]
+/// List past cohorts and who you were grouped with in each
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    tracing_handler_start(&ctx).await;
+    let archive = resolve_program(&ctx, program).await?.archive()?;
+    if archive.cohorts().is_empty() {
+        ctx.reply("No cohorts have been archived yet").await?;
+        return Ok(());
+    }
+    let user_id_number = ctx.author_id_number();
+    let mut msg = String::from("**Past Cohorts**\n");
+    let skipped = archive.cohorts().len().saturating_sub(MAX_LISTED);
+    for (i, cohort) in archive.cohorts().iter().enumerate().skip(skipped) {
+        let partners: Vec<String> = cohort
+            .group_of(user_id_number)
+            .map(|group| {
+                group
+                    .members
+                    .iter()
+                    .filter(|x| x.id_number != user_id_number)
+                    .map(|x| x.name.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        writeln!(
+            msg,
+            "{}. Ended {} - {} members in {} groups - {}",
+            CohortNumber::from_index(i),
+            cohort.archived,
+            cohort.members.len(),
+            cohort.groups.len(),
+            if cohort.group_of(user_id_number).is_none() {
+                "you did not take part".to_string()
+            } else if partners.is_empty() {
+                "you were in a group on your own".to_string()
+            } else {
+                format!("you were grouped with {}", partners.join(", "))
+            }
+        )?;
+    }
+    if skipped > 0 {
+        writeln!(msg, "({skipped} older cohorts not shown)")?;
+    }
+    write!(msg, "Use `/cohort show` with a number to see more details")?;
+    reply_in_chunks(ctx, &msg).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-show", skip(ctx))]
+/// Show the groups, goals and results of a past cohort
+pub async fn show(
+    ctx: Context<'_>,
+    #[description = "Cohort number as listed by history"] number: NonZeroUsize,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let number: CohortNumber = number.into();
+    let archive = resolve_program(&ctx, program).await?.archive()?;
+    let msg = match archive.get(number) {
+        Some(cohort) => display_archived(number, cohort)?,
+        None => format!(
+            "**Cohort {number} not found** there are {} archived cohorts",
+            archive.cohorts().len()
+        ),
+    };
+    // Every member is listed so a real cohort takes several messages
+    reply_in_chunks(ctx, &msg).await?;
+    Ok(())
+}
+
+fn display_archived(number: CohortNumber, cohort: &ArchivedCohort) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+    let mut result = format!("**Cohort {number}**\nEnded {}\n\n", cohort.archived);
+    for (i, group) in cohort.groups.iter().enumerate() {
+        writeln!(result, "**Group {}**", i + 1)?;
+        for member in group.members.iter() {
+            let responses = cohort
+                .check_ins
+                .iter()
+                .filter(|x| x.response(member.id_number).is_some())
+                .count();
+            writeln!(
+                result,
+                "- {} - Goal: {} - Check-ins: {responses} of {}",
+                member.name,
+                truncate_text(
+                    cohort.goal_of(member.id_number).unwrap_or("Not set"),
+                    MAX_GOAL_LEN
+                ),
+                cohort.check_ins.len()
+            )?;
+        }
+    }
+    write!(
+        result,
+        "\n**Feedback**\n{}",
+        FeedbackSummary::new(&cohort.feedback, cohort.members.len())
+    )?;
+    Ok(result)
+}
+
+/// Moves the cohort in progress (if any) into the archive and returns the number it was given
+#[instrument(skip(cohort), fields(program = %cohort.program()))]
+pub fn do_archive_cohort(cohort: &Cohort) -> anyhow::Result<Option<CohortNumber>> {
+    info!("START");
+    if !cohort.record()?.is_in_progress() {
+        info!("END no cohort in progress");
+        return Ok(None);
+    }
+    let record = cohort.record_take()?;
+    let result = cohort.archive_add(to_archived_cohort(cohort, record)?)?;
+    info!("END archived as cohort {result}");
+    Ok(Some(result))
+}
+
+/// Builds the archived form of the record keeping each member's goal
+pub fn to_archived_cohort(cohort: &Cohort, record: CohortRecord) -> anyhow::Result<ArchivedCohort> {
+    let mut members = vec![];
+    for published in record.groups() {
+        for user in published.group.members.iter() {
+            let goal = cohort
+                .scores_record(user.id_number)?
+                .and_then(|x| x.goal().map(ToString::to_string));
+            members.push(ArchivedMember {
+                user: user.clone(),
+                goal,
+            });
+        }
+    }
+    Ok(ArchivedCohort::new(record, members, UnixTimestamp::now()?))
+}
@@ -0,0 +1,255 @@
+//! Groups the functionality for the weekly check-ins on members' progress
+
+use anyhow::Context as _;
+use poise::{
+    CreateReply, Modal as _,
+    serenity_prelude::{
+        self as serenity, ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton,
+        CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, Mentionable as _,
+        ModalInteractionCollector, User,
+    },
+};
+use std::time::Duration;
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Context, Data,
+    commands::{
+        autocomplete_program,
+        cohort_cmd::{
+            effects::{Effects, Post},
+            inactive::do_detect_inactive,
+        },
+        resolve_program, resolve_program_for_interaction, tracing_handler_start,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            check_in::{CheckInId, CheckInStatus},
+            program::ProgramKey,
+        },
+        guild::GuildConfig,
+        schedule::UnixTimestamp,
+        user_serde::UserIdNumber,
+    },
+    sanitize_markdown,
+};
+
+/// Prefix of the custom ID of all check-in buttons, used to route interactions here
+pub const CHECK_IN_PREFIX: &str = "checkin:";
+const NOTE_ACTION: &str = "note";
+
+#[derive(Debug, poise::Modal)]
+#[name = "Check-in note"]
+struct CheckInNoteModal {
+    #[name = "How is your goal going?"]
+    #[paragraph]
+    #[max_length = 1000]
+    note: String,
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-progress", skip(ctx))]
+/// Show the check-in progress for you (or another member) and the rest of the group
+pub async fn progress(
+    ctx: Context<'_>,
+    #[description = "Member to show progress for (defaults to you)"] user: Option<User>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let user_id_number: UserIdNumber = user.as_ref().unwrap_or(ctx.author()).id.into();
+    let record = resolve_program(&ctx, program).await?.record()?;
+    let Some(published) = record.group_of(user_id_number) else {
+        ctx.reply("**Not a member of a group in the current cohort**")
+            .await?;
+        return Ok(());
+    };
+    let mut embed = CreateEmbed::new().title("Check-in Progress");
+    for member in published.group.members.iter() {
+        let lines: Vec<String> = record
+            .check_ins()
+            .iter()
+            .enumerate()
+            .map(|(i, round)| {
+                let response = round
+                    .response(member.id_number)
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| "No response".to_string());
+                format!("Week {}: {response}", i + 1)
+            })
+            .collect();
+        embed = embed.field(
+            member.name.to_string(),
+            if lines.is_empty() {
+                "No check-ins yet".to_string()
+            } else {
+                lines.join("\n")
+            },
+            false,
+        );
+    }
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Starts a new round of check-ins and prompts every member in their group thread (or by DM if there is no thread)
+#[instrument(skip(effects, config, cohort), fields(program = %cohort.program()))]
+pub async fn do_check_in(
+    effects: &Effects<'_>,
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
+) -> anyhow::Result<()> {
+    info!("START");
+    // Check previous rounds before starting a new one so members get the full week to respond
+    do_detect_inactive(effects, config, cohort).await?;
+    let id = cohort.record_check_in_start(UnixTimestamp::now()?)?;
+    let record = cohort.record()?;
+    for published in record.groups() {
+        let post = check_in_post(cohort.key(), id, &published.group.to_string());
+        match published.thread_id {
+            Some(thread_id) => {
+                if let Err(e) = effects.send(thread_id, post).await {
+                    warn!("failed to post check-in to thread {thread_id} with error: {e:?}");
+                }
+            }
+            None => {
+                for member in published.group.members.iter() {
+                    let mention = member.id_number.to_user_id().mention().to_string();
+                    let is_opted_out = cohort
+                        .scores_record(member.id_number)?
+                        .is_some_and(|x| x.dm_opt_out());
+                    if is_opted_out {
+                        effects.not_sent(format!(
+                            "No DM to {mention} because they opted out of DMs"
+                        ))?;
+                        continue;
+                    }
+                    let post = check_in_post(cohort.key(), id, &mention);
+                    if let Err(e) = effects.direct_message(member.id_number, post).await {
+                        warn!("failed to DM check-in to {member:?} with error: {e:?}");
+                    }
+                }
+            }
+        }
+    }
+    info!("END");
+    Ok(())
+}
+
+fn check_in_text(id: CheckInId, mentions: &str) -> String {
+    format!("**Week {id} check-in** {mentions}\nHow is your goal going? Let your group know below.")
+}
+
+fn check_in_post(key: &ProgramKey, id: CheckInId, mentions: &str) -> Post {
+    let program = key.to_custom_id_part();
+    let mut buttons: Vec<CreateButton> = CheckInStatus::ALL
+        .iter()
+        .map(|status| {
+            CreateButton::new(format!("{CHECK_IN_PREFIX}{program}:{id}:{status}"))
+                .label(status.label())
+                .style(match status {
+                    CheckInStatus::OnTrack => ButtonStyle::Primary,
+                    CheckInStatus::Behind => ButtonStyle::Secondary,
+                    CheckInStatus::Done => ButtonStyle::Success,
+                })
+        })
+        .collect();
+    buttons.push(
+        CreateButton::new(format!("{CHECK_IN_PREFIX}{program}:{id}:{NOTE_ACTION}"))
+            .label("Add a note")
+            .style(ButtonStyle::Secondary),
+    );
+    Post::text(check_in_text(id, mentions)).with_components(
+        vec![CreateActionRow::Buttons(buttons)],
+        "the check-in buttons",
+    )
+}
+
+/// Handles presses of the check-in buttons which can happen long after the check-in was posted
+#[instrument(skip(ctx, interaction, data))]
+pub async fn handle_check_in_interaction(
+    ctx: &serenity::Context,
+    interaction: &ComponentInteraction,
+    data: &Data,
+) -> anyhow::Result<()> {
+    info!("START");
+    let custom_id = interaction.data.custom_id.as_str();
+    let parts: Vec<&str> = custom_id
+        .strip_prefix(CHECK_IN_PREFIX)
+        .with_context(|| format!("invalid check-in custom id: {custom_id:?}"))?
+        .split(':')
+        .collect();
+    // Buttons posted before programs existed do not include the program
+    let (program, id, action) = match parts[..] {
+        [program, id, action] => (Some(program), id, action),
+        [id, action] => (None, id, action),
+        _ => anyhow::bail!("invalid check-in custom id: {custom_id:?}"),
+    };
+    let id: CheckInId = id
+        .parse::<std::num::NonZeroUsize>()
+        .with_context(|| format!("invalid check-in id in {custom_id:?}"))?
+        .into();
+    let user_id_number: UserIdNumber = interaction.user.id.into();
+    let cohort = resolve_program_for_interaction(data, program)?;
+    if action == NOTE_ACTION {
+        const NOTE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+        // Based on `poise::execute_modal_on_component_interaction` which needs a poise context
+        let modal_custom_id = interaction.id.to_string();
+        interaction
+            .create_response(ctx, CheckInNoteModal::create(None, modal_custom_id.clone()))
+            .await?;
+        let Some(submission) = ModalInteractionCollector::new(&ctx.shard)
+            .filter(move |x| x.data.custom_id == modal_custom_id)
+            .timeout(NOTE_TIMEOUT)
+            .await
+        else {
+            info!("note modal timed out");
+            return Ok(());
+        };
+        let modal = CheckInNoteModal::parse(submission.data.clone()).map_err(anyhow::Error::msg)?;
+        let msg = match cohort.record_check_in_note_set(
+            id,
+            user_id_number,
+            sanitize_markdown(modal.note),
+            UnixTimestamp::now()?,
+        ) {
+            Ok(()) => format!("Note recorded for week {id}"),
+            Err(e) => e.to_string(),
+        };
+        submission
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(msg)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+    } else {
+        let status: CheckInStatus = action.parse()?;
+        let msg = match cohort.record_check_in_status_set(
+            id,
+            user_id_number,
+            status,
+            UnixTimestamp::now()?,
+        ) {
+            Ok(()) => format!("Recorded **{}** for week {id}", status.label()),
+            Err(e) => e.to_string(),
+        };
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(msg)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+    }
+    info!("END");
+    Ok(())
+}
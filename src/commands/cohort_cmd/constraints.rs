@@ -0,0 +1,307 @@
+//! Groups the commands related to who members should or should not be grouped with
+
+use poise::serenity_prelude::{Mentionable as _, User};
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::{
+        autocomplete_program, call_to_parent_command, can_manage_cohort, reply_ephemeral,
+        resolve_program, tracing_handler_start,
+    },
+    errors::bail_user,
+    model::{
+        cohort::constraints::{Exclusion, ExclusionSource},
+        user_serde::{UserIdNumber, UserRecordSupport as _},
+    },
+    sanitize_markdown,
+};
+
+#[poise::command(
+    prefix_command,
+    slash_command,
+    subcommand_required,
+    subcommands("add", "remove", "list", "admin_add", "admin_remove", "admin_list")
+)]
+#[instrument(name = "cohort-exclusion", skip(ctx))]
+/// Commands related to people you should never be grouped with
+pub async fn exclusion(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    prefix_command,
+    slash_command,
+    subcommand_required,
+    subcommands("new_members", "partner_add", "partner_remove")
+)]
+#[instrument(name = "cohort-preference", skip(ctx))]
+/// Commands related to who you would like to be grouped with (not guaranteed)
+pub async fn preference(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-exclusion-add", skip(ctx))]
+/// Never be grouped with this person (they are not notified)
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Person to never be grouped with"] user: User,
+    #[description = "Optional note for admins"] reason: Option<String>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    if user.id == ctx.author().id {
+        bail_user!("You can not exclude yourself");
+    }
+    let cohort = resolve_program(&ctx, program).await?;
+    let exclusion = Exclusion {
+        other: user.id.into(),
+        source: ExclusionSource::SelfSet,
+        reason: reason.map(sanitize_markdown),
+    };
+    let did_add = cohort.constraints_exclusion_add(ctx.author_id_number(), exclusion)?;
+    reply_ephemeral(
+        &ctx,
+        if did_add {
+            format!("You will not be grouped with {}", user.mention())
+        } else {
+            format!("{} was already excluded", user.mention())
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-exclusion-remove", skip(ctx))]
+/// Remove an exclusion you set (including those from saying you would not pair again in feedback)
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Person to stop excluding"] user: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let (user_id, other_id): (UserIdNumber, UserIdNumber) =
+        (ctx.author_id_number(), user.id.into());
+    // Both kinds were set by the member themselves so both are theirs to remove
+    let did_remove =
+        cohort.constraints_exclusion_remove(user_id, other_id, ExclusionSource::SelfSet)?
+            | cohort.constraints_exclusion_remove(user_id, other_id, ExclusionSource::Feedback)?;
+    reply_ephemeral(
+        &ctx,
+        if did_remove {
+            format!("Exclusion of {} removed", user.mention())
+        } else {
+            format!("**No exclusion of {} found**", user.mention())
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, aliases("disp"))]
+#[instrument(name = "cohort-exclusion-list", skip(ctx))]
+/// Show your exclusions and preferences [aliases("disp")]
+pub async fn list(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let msg = cohort.constraints_user_as_string(ctx.author_id_number(), false)?;
+    reply_ephemeral(&ctx, msg).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-exclusion-admin_add", skip(ctx))]
+/// Prevent two people from ever being grouped together
+pub async fn admin_add(
+    ctx: Context<'_>,
+    #[description = "First person"] user: User,
+    #[description = "Person they must not be grouped with"] other: User,
+    #[description = "Reason (only shown to admins)"] reason: Option<String>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    if user.id == other.id {
+        bail_user!("A member can not be excluded from themselves");
+    }
+    let cohort = resolve_program(&ctx, program).await?;
+    let exclusion = Exclusion {
+        other: other.id.into(),
+        source: ExclusionSource::Admin(ctx.author_id_number()),
+        reason: reason.map(sanitize_markdown),
+    };
+    let did_add = cohort.constraints_exclusion_add(user.id.into(), exclusion)?;
+    info!(did_add);
+    reply_ephemeral(
+        &ctx,
+        if did_add {
+            format!(
+                "{} and {} will not be grouped together",
+                user.mention(),
+                other.mention()
+            )
+        } else {
+            "Exclusion already exists".to_string()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-exclusion-admin_remove", skip(ctx))]
+/// Remove an admin set exclusion between two people
+pub async fn admin_remove(
+    ctx: Context<'_>,
+    #[description = "First person"] user: User,
+    #[description = "Person they are excluded from"] other: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let admin_source = ExclusionSource::Admin(ctx.author_id_number());
+    let (user_id, other_id): (UserIdNumber, UserIdNumber) = (user.id.into(), other.id.into());
+    // Admin exclusions may have been added from either side
+    let did_remove = cohort.constraints_exclusion_remove(user_id, other_id, admin_source)?
+        | cohort.constraints_exclusion_remove(other_id, user_id, admin_source)?;
+    reply_ephemeral(
+        &ctx,
+        if did_remove {
+            "Exclusion removed"
+        } else {
+            "**No admin set exclusion found**"
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-exclusion-admin_list", skip(ctx))]
+/// Show all exclusions and preferences for a person
+pub async fn admin_list(
+    ctx: Context<'_>,
+    user: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let msg = cohort.constraints_user_as_string(user.id.into(), true)?;
+    reply_ephemeral(&ctx, format!("{}\n{msg}", user.mention())).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-preference-new_members", skip(ctx))]
+/// Prefer to be grouped with someone new to the server
+pub async fn new_members(
+    ctx: Context<'_>,
+    enabled: bool,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    cohort.constraints_prefer_new_members_set(ctx.author_id_number(), enabled)?;
+    reply_ephemeral(
+        &ctx,
+        if enabled {
+            "You will preferably be grouped with someone new to the server"
+        } else {
+            "Preference for new members removed"
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-preference-partner_add", skip(ctx))]
+/// Prefer to be grouped with this person
+pub async fn partner_add(
+    ctx: Context<'_>,
+    user: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    if user.id == ctx.author().id {
+        bail_user!("You can not prefer to be grouped with yourself");
+    }
+    let cohort = resolve_program(&ctx, program).await?;
+    let did_add =
+        cohort.constraints_preferred_partner_add(ctx.author_id_number(), user.id.into())?;
+    reply_ephemeral(
+        &ctx,
+        if did_add {
+            format!("You will preferably be grouped with {}", user.mention())
+        } else {
+            format!("{} was already a preferred partner", user.mention())
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-preference-partner_remove", skip(ctx))]
+/// Remove a preferred partner
+pub async fn partner_remove(
+    ctx: Context<'_>,
+    user: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let did_remove =
+        cohort.constraints_preferred_partner_remove(ctx.author_id_number(), user.id.into())?;
+    reply_ephemeral(
+        &ctx,
+        if did_remove {
+            format!("{} removed from preferred partners", user.mention())
+        } else {
+            format!("**{} was not a preferred partner**", user.mention())
+        },
+    )
+    .await?;
+    Ok(())
+}
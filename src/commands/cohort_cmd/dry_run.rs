@@ -0,0 +1,381 @@
+//! Reports what the scheduled objectives would do without doing any of it
+//!
+//! The objective is run through the same code as a real run but on a sandbox copy of the program
+//! and with its messages and role changes only recorded
+
+use std::fmt::Write as _;
+
+use poise::serenity_prelude::ChannelId;
+use tracing::{info, instrument};
+
+use crate::{
+    Data,
+    commands::cohort_cmd::{do_objective, effects::Effects, inactive::mention_list},
+    model::{
+        cohort::{Cohort, archive::CohortNumber, check_in::CheckInId},
+        guild::GuildConfig,
+        schedule::Objective,
+        user_serde::UserIdNumber,
+    },
+};
+
+/// Everything a run of an objective would do, grouped by the kind of change
+#[derive(Debug, Default)]
+struct DryRunReport {
+    state: Vec<String>,
+    messages: Vec<String>,
+    roles: Vec<String>,
+    schedule: Vec<String>,
+}
+
+/// Describes what running the objective for the program would do right now
+///
+/// `channel_id` is where the start event posts its announcements (the program's channel when run
+/// by the scheduler)
+#[instrument(skip(data, cohort), fields(program = %cohort.program()))]
+pub async fn do_dry_run(
+    data: &Data,
+    cohort: &Cohort,
+    objective: Objective,
+    channel_id: ChannelId,
+) -> anyhow::Result<String> {
+    use poise::ChoiceParameter as _;
+    info!("START");
+    let config = data.guild_config(cohort.guild_id())?;
+    let report = dry_run_report(config.as_ref(), cohort, objective, channel_id).await?;
+    let mut result = format!(
+        "**Dry run: {} ({})**\nNothing below has been done\n",
+        objective.name(),
+        cohort.program()
+    );
+    for (title, lines) in [
+        ("State changes", &report.state),
+        ("Messages", &report.messages),
+        ("Role changes", &report.roles),
+        ("Schedule", &report.schedule),
+    ] {
+        writeln!(result, "\n**{title}**")?;
+        if lines.is_empty() {
+            writeln!(result, "None")?;
+        }
+        for line in lines {
+            writeln!(result, "- {line}")?;
+        }
+    }
+    info!("END");
+    Ok(result)
+}
+
+/// Runs the objective on a sandbox copy of the program with the side effects only recorded
+async fn dry_run_report(
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
+    objective: Objective,
+    channel_id: ChannelId,
+) -> anyhow::Result<DryRunReport> {
+    let sandbox = cohort.sandbox()?;
+    let effects = Effects::report();
+    let outcome = do_objective(&effects, objective, channel_id, config, &sandbox).await;
+    let effects = effects.into_report()?;
+    let mut state = describe_state_changes(cohort, &sandbox)?;
+    if let Err(e) = outcome {
+        state.push(format!("The run stops here because it fails with: {e:#}"));
+    }
+    let schedule = vec![match objective.repeat_interval() {
+        Some(_) => "When run by the scheduler the next run is scheduled one week later".to_string(),
+        None => "When run by the scheduler the task is removed from the schedule".to_string(),
+    }];
+    Ok(DryRunReport {
+        state,
+        roles: effects.role_lines(),
+        messages: effects.messages,
+        schedule,
+    })
+}
+
+/// Lists what is different in the program after the run compared to before it
+fn describe_state_changes(before: &Cohort, after: &Cohort) -> anyhow::Result<Vec<String>> {
+    let mut result = vec![];
+    let archived_before = before.archive()?.cohorts().len();
+    for (index, archived) in after
+        .archive()?
+        .cohorts()
+        .iter()
+        .enumerate()
+        .skip(archived_before)
+    {
+        result.push(format!(
+            "The cohort in progress ({} groups) is archived as cohort {}",
+            archived.groups.len(),
+            CohortNumber::from_index(index)
+        ));
+    }
+    let registrations = |cohort: &Cohort| -> anyhow::Result<usize> {
+        Ok(cohort.scores_registered_users()?.len() + cohort.scores_waitlist()?.len())
+    };
+    let (registered_before, registered_after) = (registrations(before)?, registrations(after)?);
+    if registered_before > 0 && registered_after == 0 {
+        result.push(format!(
+            "All {registered_before} registrations (including the waitlist) are cleared"
+        ));
+    } else if registered_before != registered_after {
+        result.push(format!(
+            "The registrations (including the waitlist) go from {registered_before} to {registered_after}"
+        ));
+    }
+    if !before.scores()?.message.is_empty() && after.scores()?.message.is_empty() {
+        result.push("The scores message is cleared".to_string());
+    }
+    let (record_before, record_after) = (before.record()?, after.record()?);
+    let newly_inactive: Vec<UserIdNumber> = record_after
+        .groups()
+        .iter()
+        .flat_map(|x| x.group.member_ids())
+        .filter(|&x| record_after.is_inactive(x) && !record_before.is_inactive(x))
+        .collect();
+    if !newly_inactive.is_empty() {
+        result.push(format!(
+            "{} are flagged as inactive",
+            mention_list(&newly_inactive)
+        ));
+    }
+    if record_after.is_in_progress()
+        && record_after.check_ins().len() > record_before.check_ins().len()
+    {
+        result.push(format!(
+            "Week {} check-in is started for {} groups",
+            CheckInId::from_index(record_after.check_ins().len() - 1),
+            record_after.groups().len()
+        ));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use poise::serenity_prelude::{GuildId, RoleId, UserId};
+
+    use super::*;
+    use crate::{
+        commands::cohort_cmd::interested_list::archived_message,
+        config::SharedConfig,
+        model::{
+            cohort::{
+                check_in::CheckInStatus,
+                grouping::Group,
+                program::{ProgramKey, ProgramName},
+                settings::CohortSettings,
+            },
+            schedule::UnixTimestamp,
+            user_serde::UserRecord,
+        },
+    };
+
+    fn shared_config() -> &'static SharedConfig {
+        Box::leak(Box::new(SharedConfig {
+            start_instant: Instant::now(),
+            auth_role_id: RoleId::new(1),
+            channel_unranked: ChannelId::new(2),
+            channel_admin: Some(ChannelId::new(3)),
+            channel_startup: None,
+            role_cohort: Some(RoleId::new(4)),
+            role_veteran: Some(RoleId::new(5)),
+            veteran_cohorts: 1,
+        }))
+    }
+
+    fn user_id_number(i: u64) -> UserIdNumber {
+        UserId::new(i).into()
+    }
+
+    /// A program with 4 registered members placed in 2 groups without threads
+    async fn cohort_in_progress(shared_config: &'static SharedConfig) -> Cohort {
+        let key = ProgramKey {
+            guild_id: GuildId::new(1),
+            name: ProgramName::default(),
+            is_home_guild: true,
+        };
+        let cohort = Cohort::new(
+            shared_config,
+            key,
+            shared_config.channel_unranked,
+            &CohortSettings::default(),
+        )
+        .await;
+        let members: Vec<UserRecord> = (1..=4)
+            .map(|i| UserRecord {
+                id_number: user_id_number(i),
+                name: format!("member {i}").into(),
+            })
+            .collect();
+        let now = UnixTimestamp::now().unwrap();
+        for member in members.iter() {
+            cohort.score_set(member.clone(), 1, None, now).unwrap();
+        }
+        cohort
+            .record_groups_set(&[
+                Group {
+                    members: members[..2].to_vec(),
+                },
+                Group {
+                    members: members[2..].to_vec(),
+                },
+            ])
+            .unwrap();
+        cohort
+    }
+
+    /// Checks that the dry run changes nothing and reports exactly what running the objective does
+    ///
+    /// The real run records its side effects instead of sending them as there is no server to send them to
+    async fn dry_run_then_real_run(
+        config: &GuildConfig,
+        cohort: &Cohort,
+        objective: Objective,
+    ) -> DryRunReport {
+        let channel_id = cohort.channel_id();
+        let before = cohort.sandbox().unwrap();
+        let report = dry_run_report(Some(config), cohort, objective, channel_id)
+            .await
+            .unwrap();
+        assert!(describe_state_changes(&before, cohort).unwrap().is_empty());
+
+        let effects = Effects::report();
+        do_objective(&effects, objective, channel_id, Some(config), cohort)
+            .await
+            .unwrap();
+        let done = effects.into_report().unwrap();
+        assert_eq!(report.messages, done.messages);
+        assert_eq!(report.roles, done.role_lines());
+        assert_eq!(
+            report.state,
+            describe_state_changes(&before, cohort).unwrap()
+        );
+        report
+    }
+
+    #[tokio::test]
+    async fn start_event_report_matches_real_run() {
+        let shared_config = shared_config();
+        let config = GuildConfig::from_shared_config(GuildId::new(1), shared_config);
+        let cohort = cohort_in_progress(shared_config).await;
+
+        let report = dry_run_then_real_run(&config, &cohort, Objective::UnrankedStartEvent).await;
+
+        assert_eq!(
+            report.state,
+            [
+                "The cohort in progress (2 groups) is archived as cohort 1",
+                "All 4 registrations (including the waitlist) are cleared"
+            ]
+        );
+        assert_eq!(report.messages.len(), 4);
+        assert!(report.messages[0].contains(&archived_message(CohortNumber::from_index(0))));
+        assert_eq!(report.roles.len(), 2);
+        assert_eq!(cohort.archive().unwrap().cohorts().len(), 1);
+        assert!(cohort.scores_registered_users().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_in_report_matches_real_run() {
+        let shared_config = shared_config();
+        let config = GuildConfig::from_shared_config(GuildId::new(1), shared_config);
+        let cohort = cohort_in_progress(shared_config).await;
+        let now = UnixTimestamp::now().unwrap();
+        cohort
+            .settings_missed_check_ins_limit_set(user_id_number(1), 2)
+            .unwrap();
+        for _ in 0..2 {
+            let id = cohort.record_check_in_start(now).unwrap();
+            cohort
+                .record_check_in_status_set(id, user_id_number(1), CheckInStatus::OnTrack, now)
+                .unwrap();
+        }
+        cohort
+            .scores_dm_opt_out_set(user_id_number(2), true)
+            .unwrap();
+
+        let report = dry_run_then_real_run(&config, &cohort, Objective::CohortCheckIn).await;
+
+        assert_eq!(
+            report.state,
+            [
+                "<@2>, <@3>, <@4> are flagged as inactive",
+                "Week 3 check-in is started for 2 groups"
+            ]
+        );
+        let recipients: Vec<&str> = report
+            .messages
+            .iter()
+            .map(|x| x.split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            recipients,
+            [
+                "DM to <@1>",
+                "In <#3>",
+                "DM to <@1>",
+                "No DM to <@2> because they opted out of DMs",
+                "DM to <@3>",
+                "DM to <@4>"
+            ]
+        );
+        assert!(report.roles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stranded_partner_that_opted_out_is_reported_to_admins() {
+        let shared_config = shared_config();
+        let config = GuildConfig::from_shared_config(GuildId::new(1), shared_config);
+        let cohort = cohort_in_progress(shared_config).await;
+        let now = UnixTimestamp::now().unwrap();
+        cohort
+            .settings_missed_check_ins_limit_set(user_id_number(1), 1)
+            .unwrap();
+        let id = cohort.record_check_in_start(now).unwrap();
+        for i in [1, 3, 4] {
+            cohort
+                .record_check_in_status_set(id, user_id_number(i), CheckInStatus::OnTrack, now)
+                .unwrap();
+        }
+        cohort
+            .scores_dm_opt_out_set(user_id_number(1), true)
+            .unwrap();
+
+        let report = dry_run_then_real_run(&config, &cohort, Objective::CohortCheckIn).await;
+
+        assert!(report.messages[0].starts_with("In <#3>: \"These members have missed"));
+        assert!(
+            report.messages[1].starts_with(
+                "In <#3>: \"Unable to offer these stranded members a new partner by DM"
+            )
+        );
+        assert!(report.messages[1].contains("<@1> (member 1)"));
+        assert!(!report.messages.iter().any(|x| x.starts_with("DM to <@1>")));
+    }
+
+    #[tokio::test]
+    async fn failure_is_reported() {
+        let shared_config = shared_config();
+        let config = GuildConfig::from_shared_config(GuildId::new(1), shared_config);
+        let cohort = cohort_in_progress(shared_config).await;
+        cohort.record_take().unwrap();
+
+        let report = dry_run_report(
+            Some(&config),
+            &cohort,
+            Objective::CohortCheckIn,
+            cohort.channel_id(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            report.state,
+            ["The run stops here because it fails with: No cohort in progress to check in on"]
+        );
+        assert!(report.messages.is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+//! Carries out the side effects of the objectives, or only records them so a dry run can report
+//! them
+//!
+//! The objectives take an [`Effects`] instead of the context so a dry run goes through exactly the
+//! same code as a real run
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use poise::serenity_prelude::{
+    Cache, CacheHttp, ChannelId, CreateActionRow, CreateEmbed, CreateMessage, GuildId, Http,
+    Mentionable as _, RoleId,
+};
+use tracing::warn;
+
+use crate::{
+    commands::cohort_cmd::inactive::mention_list,
+    model::{guild::GuildConfig, user_serde::UserIdNumber},
+};
+
+/// Where the side effects of an objective go
+pub enum Effects<'a> {
+    /// Sends the messages and changes the roles
+    Live(&'a (dyn CacheHttp + 'a)),
+    /// Only records what would have been done
+    Report(Mutex<EffectsReport>),
+}
+
+/// The side effects recorded by [`Effects::Report`]
+#[derive(Debug, Default)]
+pub struct EffectsReport {
+    pub messages: Vec<String>,
+    pub roles: Vec<RoleChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleChange {
+    pub role_id: RoleId,
+    pub user_id_number: UserIdNumber,
+    pub is_added: bool,
+    pub reason: String,
+}
+
+/// A message as it is sent and as it is described in a dry run
+#[derive(Clone)]
+pub struct Post {
+    builder: CreateMessage,
+    description: String,
+}
+
+/// Lets the trait object be passed to serenity which expects `impl CacheHttp`
+struct Live<'a>(&'a (dyn CacheHttp + 'a));
+
+impl CacheHttp for Live<'_> {
+    fn http(&self) -> &Http {
+        self.0.http()
+    }
+
+    fn cache(&self) -> Option<&Arc<Cache>> {
+        self.0.cache()
+    }
+}
+
+impl Effects<'_> {
+    pub fn report() -> Self {
+        Self::Report(Mutex::default())
+    }
+
+    /// Returns what was recorded, which is nothing for [`Effects::Live`]
+    pub fn into_report(self) -> anyhow::Result<EffectsReport> {
+        match self {
+            Self::Live(_) => Ok(EffectsReport::default()),
+            Self::Report(report) => match report.into_inner() {
+                Ok(report) => Ok(report),
+                Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+            },
+        }
+    }
+
+    fn guard_report(
+        report: &Mutex<EffectsReport>,
+    ) -> anyhow::Result<MutexGuard<'_, EffectsReport>> {
+        match report.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    pub async fn send(&self, channel_id: ChannelId, post: Post) -> anyhow::Result<()> {
+        match self {
+            Self::Live(cache_http) => {
+                channel_id
+                    .send_message(Live(*cache_http), post.builder)
+                    .await?;
+            }
+            Self::Report(report) => Self::guard_report(report)?.messages.push(format!(
+                "In {}: {}",
+                channel_id.mention(),
+                post.description
+            )),
+        }
+        Ok(())
+    }
+
+    pub async fn direct_message(
+        &self,
+        user_id_number: UserIdNumber,
+        post: Post,
+    ) -> anyhow::Result<()> {
+        let user_id = user_id_number.to_user_id();
+        match self {
+            Self::Live(cache_http) => {
+                user_id
+                    .direct_message(Live(*cache_http), post.builder)
+                    .await?;
+            }
+            Self::Report(report) => Self::guard_report(report)?.messages.push(format!(
+                "DM to {}: {}",
+                user_id.mention(),
+                post.description
+            )),
+        }
+        Ok(())
+    }
+
+    /// Records why a message is not sent, the caller is expected to log it for live runs
+    pub fn not_sent(&self, reason: String) -> anyhow::Result<()> {
+        if let Self::Report(report) = self {
+            Self::guard_report(report)?.messages.push(reason);
+        }
+        Ok(())
+    }
+
+    /// Sends a message to the server's admin channel if one is configured
+    pub async fn notify_admins(
+        &self,
+        config: Option<&GuildConfig>,
+        msg: String,
+    ) -> anyhow::Result<()> {
+        match config.and_then(|x| x.channel_admin) {
+            Some(channel_id) => self.send(channel_id, Post::text(msg)).await?,
+            None => {
+                warn!("Not sending admin notification because `channel_admin` not set");
+                self.not_sent(
+                    "The admins are not notified because no admin channel is set".to_string(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn add_role(
+        &self,
+        guild_id: GuildId,
+        user_id_number: UserIdNumber,
+        role_id: RoleId,
+        reason: &str,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Live(cache_http) => {
+                cache_http
+                    .http()
+                    .add_member_role(guild_id, user_id_number.to_user_id(), role_id, Some(reason))
+                    .await?;
+            }
+            Self::Report(report) => Self::guard_report(report)?.roles.push(RoleChange {
+                role_id,
+                user_id_number,
+                is_added: true,
+                reason: reason.to_string(),
+            }),
+        }
+        Ok(())
+    }
+
+    pub async fn remove_role(
+        &self,
+        guild_id: GuildId,
+        user_id_number: UserIdNumber,
+        role_id: RoleId,
+        reason: &str,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Live(cache_http) => {
+                cache_http
+                    .http()
+                    .remove_member_role(
+                        guild_id,
+                        user_id_number.to_user_id(),
+                        role_id,
+                        Some(reason),
+                    )
+                    .await?;
+            }
+            Self::Report(report) => Self::guard_report(report)?.roles.push(RoleChange {
+                role_id,
+                user_id_number,
+                is_added: false,
+                reason: reason.to_string(),
+            }),
+        }
+        Ok(())
+    }
+}
+
+impl EffectsReport {
+    /// Describes the role changes with one line for each role and reason
+    pub fn role_lines(&self) -> Vec<String> {
+        let mut grouped: Vec<(&RoleChange, Vec<UserIdNumber>)> = vec![];
+        for change in self.roles.iter() {
+            let existing = grouped.iter_mut().find(|(x, _)| {
+                x.role_id == change.role_id
+                    && x.is_added == change.is_added
+                    && x.reason == change.reason
+            });
+            match existing {
+                Some((_, users)) => users.push(change.user_id_number),
+                None => grouped.push((change, vec![change.user_id_number])),
+            }
+        }
+        grouped
+            .into_iter()
+            .map(|(change, users)| {
+                format!(
+                    "{} is {} {} ({})",
+                    change.role_id.mention(),
+                    if change.is_added {
+                        "given to"
+                    } else {
+                        "removed from"
+                    },
+                    mention_list(&users),
+                    change.reason
+                )
+            })
+            .collect()
+    }
+}
+
+impl Post {
+    pub fn text(content: impl Into<String>) -> Self {
+        let content = content.into();
+        Self {
+            description: quote(&content),
+            builder: CreateMessage::new().content(content),
+        }
+    }
+
+    /// A message with only an embed, `what` describes the embed in a dry run
+    pub fn embed(embed: CreateEmbed, what: impl Into<String>) -> Self {
+        Self {
+            builder: CreateMessage::new().embed(embed),
+            description: what.into(),
+        }
+    }
+
+    /// Adds the rows of buttons, `what` describes them in a dry run
+    pub fn with_components(self, rows: Vec<CreateActionRow>, what: &str) -> Self {
+        Self {
+            builder: self.builder.components(rows),
+            description: format!("{} with {what}", self.description),
+        }
+    }
+}
+
+/// Shows the text of a message on one line so it is clear where it starts and ends
+fn quote(msg: &str) -> String {
+    format!("\"{}\"", msg.replace('\n', " / "))
+}
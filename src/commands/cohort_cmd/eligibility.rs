@@ -0,0 +1,188 @@
+//! Groups the functionality for the rules that decide who can sign up for a cohort
+
+use anyhow::Context as _;
+use poise::serenity_prelude::{CacheHttp, Role, Timestamp};
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::{
+        autocomplete_program, call_to_parent_command, can_manage_cohort, resolve_program,
+        tracing_handler_start,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            archive::CohortNumber,
+            eligibility::{EligibilityFacts, EligibilityRules, Ineligible},
+        },
+        schedule::UnixTimestamp,
+        user_serde::{UserIdNumber, UserRecordSupport as _},
+    },
+};
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort",
+    subcommand_required,
+    subcommands("show", "min_days", "required_role", "previous_feedback")
+)]
+#[instrument(name = "cohort-eligibility", skip(ctx))]
+/// Commands to set the rules members must meet to sign up
+pub async fn eligibility(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-eligibility-show", skip(ctx))]
+/// Show the current sign up rules
+pub async fn show(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let rules = cohort.settings()?.eligibility;
+    ctx.reply(format!("**Eligibility Rules**\n{rules}")).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-eligibility-min_days", skip(ctx))]
+/// Set how many days someone must have been in the server to sign up (0 removes the rule)
+pub async fn min_days(
+    ctx: Context<'_>,
+    #[description = "Days of server membership needed"] days: u16,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    update_rules(&ctx, &cohort, |rules| {
+        rules.min_membership_days = (days > 0).then_some(days);
+    })
+    .await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-eligibility-required_role", skip(ctx))]
+/// Set the role needed to sign up (leave empty to remove the rule)
+pub async fn required_role(
+    ctx: Context<'_>,
+    #[description = "Role needed to sign up"] role: Option<Role>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    update_rules(&ctx, &cohort, |rules| {
+        rules.required_role = role.map(|x| x.id);
+    })
+    .await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-eligibility-previous_feedback", skip(ctx))]
+/// Choose if members of the previous cohort must have given feedback to sign up again
+pub async fn previous_feedback(
+    ctx: Context<'_>,
+    required: bool,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    update_rules(&ctx, &cohort, |rules| {
+        rules.require_previous_feedback = required;
+    })
+    .await
+}
+
+async fn update_rules(
+    ctx: &Context<'_>,
+    cohort: &Cohort,
+    f: impl FnOnce(&mut EligibilityRules),
+) -> anyhow::Result<()> {
+    let mut rules = cohort.settings()?.eligibility;
+    f(&mut rules);
+    cohort.settings_eligibility_set(ctx.author_id_number(), rules.clone())?;
+    ctx.reply(format!("**Eligibility Rules Updated**\n{rules}"))
+        .await?;
+    Ok(())
+}
+
+/// Returns the reasons the user is not allowed to sign up (empty if they are allowed)
+#[instrument(skip(cache_http, cohort), fields(program = %cohort.program()))]
+pub async fn do_check_eligibility(
+    cache_http: impl CacheHttp,
+    cohort: &Cohort,
+    user_id_number: UserIdNumber,
+) -> anyhow::Result<Vec<Ineligible>> {
+    info!("START");
+    let rules = cohort.settings()?.eligibility;
+    let mut facts = EligibilityFacts {
+        ban: cohort.bans_active_ban(user_id_number, UnixTimestamp::now()?)?,
+        ..Default::default()
+    };
+    if rules.min_membership_days.is_some() || rules.required_role.is_some() {
+        // Not knowing the member must not be reported to them as not meeting the rules
+        let member = cohort
+            .guild_id()
+            .member(&cache_http, user_id_number.to_user_id())
+            .await
+            .with_context(|| {
+                format!("failed to look up User# {user_id_number} to check eligibility")
+            })?;
+        facts.membership_days = member.joined_at.map(|joined| {
+            let seconds = Timestamp::now().unix_timestamp() - joined.unix_timestamp();
+            seconds.max(0) as u64 / (24 * 60 * 60)
+        });
+        facts.has_required_role = rules
+            .required_role
+            .is_some_and(|role_id| member.roles.contains(&role_id));
+    }
+    if rules.require_previous_feedback {
+        let archive = cohort.archive()?;
+        if let Some(previous) = archive.cohorts().last()
+            && previous.group_of(user_id_number).is_some()
+            && !previous.feedback.contains_key(&user_id_number)
+        {
+            facts.missing_feedback = Some(CohortNumber::from_index(archive.cohorts().len() - 1));
+        }
+    }
+    let result = rules.check(facts);
+    info!(?result, "END");
+    Ok(result)
+}
@@ -0,0 +1,277 @@
+//! Groups the functionality for the end of cohort feedback survey
+
+use poise::{
+    Modal as _,
+    serenity_prelude::{
+        self as serenity, ButtonStyle, CacheHttp, ComponentInteraction, CreateActionRow,
+        CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+        ModalInteractionCollector,
+    },
+};
+use std::time::Duration;
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Context, Data,
+    commands::{
+        autocomplete_program, call_to_parent_command, can_manage_cohort, resolve_program,
+        resolve_program_for_interaction, tracing_handler_start,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            constraints::{Exclusion, ExclusionSource},
+            feedback::{FeedbackResponse, FeedbackSummary, parse_yes_no},
+            record::PublishedGroup,
+        },
+        schedule::UnixTimestamp,
+        user_serde::UserIdNumber,
+    },
+    sanitize_markdown,
+};
+
+/// Prefix of the custom ID of all feedback buttons, used to route interactions here
+pub const FEEDBACK_PREFIX: &str = "feedback:";
+const FEEDBACK_OPEN_ID: &str = "feedback:open";
+
+#[derive(Debug, poise::Modal)]
+#[name = "Cohort feedback"]
+struct FeedbackModal {
+    #[name = "Did you meet your goal? (yes / partly / no)"]
+    #[max_length = 10]
+    met_goal: String,
+    #[name = "How useful was your group? (1 to 5)"]
+    #[max_length = 1]
+    rating: String,
+    #[name = "Happy to be grouped again? (yes / no)"]
+    #[max_length = 3]
+    pair_again: String,
+    #[name = "Anything else you would like to share?"]
+    #[paragraph]
+    #[max_length = 1000]
+    comment: Option<String>,
+}
+
+#[poise::command(
+    prefix_command,
+    slash_command,
+    subcommand_required,
+    subcommands("request", "summary")
+)]
+#[instrument(name = "cohort-feedback", skip(ctx))]
+/// Commands related to the end of cohort feedback survey
+pub async fn feedback(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-feedback-request", skip(ctx))]
+/// Asks every member of the current cohort to fill in the feedback survey
+pub async fn request(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let record = cohort.record()?;
+    if !record.is_in_progress() {
+        ctx.reply("**No cohort in progress**").await?;
+        return Ok(());
+    }
+    for published in record.groups() {
+        do_request_feedback(ctx, &cohort, published).await?;
+    }
+    ctx.reply(format!(
+        "Feedback requested from {} groups",
+        record.groups().len()
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-feedback-summary", skip(ctx))]
+/// Show the totals of the feedback received for the current cohort
+pub async fn summary(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let record = cohort.record()?;
+    let summary = FeedbackSummary::new(record.feedback(), record.member_count());
+    ctx.reply(format!("**Feedback Summary**\n{summary}"))
+        .await?;
+    Ok(())
+}
+
+/// Posts the survey button in the group thread (or by DM if there is no thread)
+async fn do_request_feedback(
+    cache_http: impl CacheHttp,
+    cohort: &Cohort,
+    published: &PublishedGroup,
+) -> anyhow::Result<()> {
+    let custom_id = format!("{FEEDBACK_OPEN_ID}:{}", cohort.key().to_custom_id_part());
+    let msg = |mentions: String| {
+        CreateMessage::new()
+            .content(format!(
+                "**The cohort is wrapping up** {mentions}\n\
+                Please take a minute to tell us how it went. Your answers are only shared with the admins."
+            ))
+            .components(vec![CreateActionRow::Buttons(vec![
+                CreateButton::new(custom_id.as_str())
+                    .label("Give feedback")
+                    .style(ButtonStyle::Primary),
+            ])])
+    };
+    match published.thread_id {
+        Some(thread_id) => {
+            if let Err(e) = thread_id
+                .send_message(&cache_http, msg(published.group.to_string()))
+                .await
+            {
+                warn!("failed to post feedback request to thread {thread_id} with error: {e:?}");
+            }
+        }
+        None => {
+            for member in published.group.members.iter() {
+                let is_opted_out = cohort
+                    .scores_record(member.id_number)?
+                    .is_some_and(|x| x.dm_opt_out());
+                if is_opted_out {
+                    continue;
+                }
+                if let Err(e) = member
+                    .id_number
+                    .to_user_id()
+                    .direct_message(&cache_http, msg(String::new()))
+                    .await
+                {
+                    warn!("failed to DM feedback request to {member:?} with error: {e:?}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles presses of the feedback button by showing the survey and storing the answers
+#[instrument(skip(ctx, interaction, data))]
+pub async fn handle_feedback_interaction(
+    ctx: &serenity::Context,
+    interaction: &ComponentInteraction,
+    data: &Data,
+) -> anyhow::Result<()> {
+    const SURVEY_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+    info!("START");
+    // Buttons posted before programs existed do not include the program
+    let program = match interaction.data.custom_id.strip_prefix(FEEDBACK_OPEN_ID) {
+        Some("") => None,
+        Some(rest) if rest.starts_with(':') => Some(&rest[1..]),
+        _ => {
+            warn!(
+                "unexpected feedback custom id: {:?}",
+                interaction.data.custom_id
+            );
+            return Ok(());
+        }
+    };
+    let cohort = resolve_program_for_interaction(data, program)?;
+    // Based on `poise::execute_modal_on_component_interaction` which needs a poise context
+    let modal_custom_id = interaction.id.to_string();
+    interaction
+        .create_response(ctx, FeedbackModal::create(None, modal_custom_id.clone()))
+        .await?;
+    let Some(submission) = ModalInteractionCollector::new(&ctx.shard)
+        .filter(move |x| x.data.custom_id == modal_custom_id)
+        .timeout(SURVEY_TIMEOUT)
+        .await
+    else {
+        info!("feedback modal timed out");
+        return Ok(());
+    };
+    let modal = FeedbackModal::parse(submission.data.clone()).map_err(anyhow::Error::msg)?;
+    let user_id_number: UserIdNumber = interaction.user.id.into();
+    let msg = match store_feedback(&cohort, user_id_number, modal) {
+        Ok(()) => "Thank you for your feedback".to_string(),
+        Err(e) => format!("**Feedback not saved** {e}. Please press the button to try again"),
+    };
+    submission
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(msg)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    info!("END");
+    Ok(())
+}
+
+/// Saves the answers and excludes the partners from future groups if the member would not pair with them again
+fn store_feedback(
+    cohort: &Cohort,
+    user_id_number: UserIdNumber,
+    modal: FeedbackModal,
+) -> anyhow::Result<()> {
+    let partners: Vec<UserIdNumber> = cohort
+        .record()?
+        .group_of(user_id_number)
+        .map(|x| {
+            x.group
+                .member_ids()
+                .filter(|&other| other != user_id_number)
+                .collect()
+        })
+        .unwrap_or_default();
+    let response = FeedbackResponse {
+        goal_outcome: modal.met_goal.parse()?,
+        partnership_rating: modal.rating.parse()?,
+        would_pair_again: parse_yes_no(&modal.pair_again)?,
+        comment: modal
+            .comment
+            .filter(|x| !x.trim().is_empty())
+            .map(sanitize_markdown),
+        partners: partners.clone(),
+        submitted: UnixTimestamp::now()?,
+    };
+    let would_pair_again = response.would_pair_again;
+    cohort.record_feedback_set(user_id_number, response)?;
+    for other in partners {
+        if would_pair_again {
+            cohort.constraints_exclusion_remove(
+                user_id_number,
+                other,
+                ExclusionSource::Feedback,
+            )?;
+        } else {
+            cohort.constraints_exclusion_add(
+                user_id_number,
+                Exclusion {
+                    other,
+                    source: ExclusionSource::Feedback,
+                    reason: None,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+//! Groups the commands related to splitting the cohort into groups
+
+use std::{collections::BTreeSet, time::Duration};
+
+use anyhow::Context as _;
+use poise::serenity_prelude::{CacheHttp, ChannelId, GuildId, Mentionable as _, Timestamp};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Context, Data,
+    commands::{
+        autocomplete_program, can_manage_cohort,
+        cohort_cmd::{notifications::do_notify_group_members, threads::do_create_group_threads},
+        confirm::confirm,
+        resolve_program, split_into_messages, take_lines, tracing_handler_start,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            constraints::PairingConstraints,
+            grouping::{Group, GroupSize, GroupingRules, display_groups, form_groups},
+            history::PairingHistory,
+        },
+        user_serde::{UserIdNumber, UserRecord, UserRecordSupport as _},
+    },
+};
+
+/// Leaves room in the confirmation for the rest of the changes (the whole prompt is limited to 4096)
+const MAX_LISTING_LEN: usize = 3500;
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    track_edits,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-group_size", skip(ctx))]
+/// Sets the number of members per group (use no args to see the current value)
+pub async fn group_size(
+    ctx: Context<'_>,
+    #[description = "Members per group (2 for pairs, 3 for trios, etc.)"]
+    #[min = 2]
+    #[max = 8]
+    size: Option<u8>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    if let Some(size) = size {
+        let group_size = GroupSize::new(size)?;
+        cohort.settings_group_size_set(ctx.author_id_number(), group_size)?;
+        ctx.reply(format!("Group size set to {size} ({group_size})"))
+            .await?;
+    } else {
+        let group_size = cohort.settings()?.group_size;
+        ctx.reply(format!(
+            "Current group size is {} ({group_size})",
+            group_size.get()
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-pair", skip(ctx))]
+/// Splits the registered members into groups and posts them in the cohort channel
+pub async fn pair(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().context("pair is guild only")?;
+    let groups = GroupingInputs::load(ctx, guild_id, &cohort)
+        .await?
+        .form_groups()?;
+    let channel_id = cohort.channel_id();
+    let (mut changes, shown) = take_lines(&display_groups(&groups)?, MAX_LISTING_LEN);
+    if shown < groups.len() {
+        writeln!(changes, "...\n{} groups, first {shown} shown", groups.len())?;
+    }
+    write!(
+        changes,
+        "- These {} groups are posted in {} with a thread for each group and members are sent a DM",
+        groups.len(),
+        channel_id.mention()
+    )?;
+    if cohort.record()?.is_in_progress() {
+        changes.push_str("\n- The groups already published for this cohort are replaced");
+    }
+    changes.push_str(
+        "\nTo adjust the groups before they are posted cancel and use `/cohort pairs preview`",
+    );
+    if !confirm(
+        ctx,
+        &format!("Publish the {} groups", cohort.program()),
+        &changes,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    do_announce_groups(ctx, channel_id, data, &cohort, &groups).await?;
+    ctx.reply(format!(
+        "{} groups posted in {}",
+        groups.len(),
+        channel_id.mention()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Snapshot of everything needed to form groups so they can be recalculated without reloading
+pub struct GroupingInputs {
+    pub members: Vec<UserRecord>,
+    pub group_size: GroupSize,
+    history: PairingHistory,
+    constraints: PairingConstraints,
+    new_members: BTreeSet<UserIdNumber>,
+}
+
+impl GroupingInputs {
+    /// Loads the currently registered members, cohort settings and constraints
+    #[instrument(skip(cache_http, cohort), fields(program = %cohort.program()))]
+    pub async fn load(
+        cache_http: impl CacheHttp,
+        guild_id: GuildId,
+        cohort: &Cohort,
+    ) -> anyhow::Result<Self> {
+        let members = cohort.scores_registered_users()?;
+        Self::load_for_members(cache_http, guild_id, cohort, members).await
+    }
+
+    /// Loads the cohort settings and constraints to group the members given
+    #[instrument(skip(cache_http, cohort, members), fields(program = %cohort.program()))]
+    pub async fn load_for_members(
+        cache_http: impl CacheHttp,
+        guild_id: GuildId,
+        cohort: &Cohort,
+        members: Vec<UserRecord>,
+    ) -> anyhow::Result<Self> {
+        info!("START");
+        let group_size = cohort.settings()?.group_size;
+        let history = cohort.history()?;
+        let constraints = cohort.constraints()?;
+        let new_members = find_new_members(&cache_http, guild_id, &members, &constraints).await;
+        info!("END");
+        Ok(Self {
+            members,
+            group_size,
+            history,
+            constraints,
+            new_members,
+        })
+    }
+
+    pub fn rules(&self) -> GroupingRules<'_> {
+        GroupingRules {
+            history: &self.history,
+            constraints: &self.constraints,
+            new_members: &self.new_members,
+        }
+    }
+
+    pub fn form_groups(&self) -> anyhow::Result<Vec<Group>> {
+        form_groups(
+            self.members.clone(),
+            self.group_size,
+            &self.rules(),
+            &mut rand::rng(),
+        )
+    }
+}
+
+/// Returns the members that joined the server recently
+///
+/// Only looks them up if at least one member prefers to be grouped with new members
+#[instrument(skip(cache_http, members, constraints))]
+async fn find_new_members(
+    cache_http: impl CacheHttp,
+    guild_id: GuildId,
+    members: &[UserRecord],
+    constraints: &PairingConstraints,
+) -> BTreeSet<UserIdNumber> {
+    const NEW_MEMBER_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+    let mut result = BTreeSet::new();
+    if !members.iter().any(|x| {
+        constraints
+            .get(x.id_number)
+            .is_some_and(|c| c.preferences().prefer_new_members)
+    }) {
+        return result;
+    }
+    let cutoff = Timestamp::now().unix_timestamp() - NEW_MEMBER_MAX_AGE.as_secs() as i64;
+    for member in members {
+        match guild_id
+            .member(&cache_http, member.id_number.to_user_id())
+            .await
+        {
+            Ok(guild_member) => {
+                if guild_member
+                    .joined_at
+                    .is_some_and(|x| x.unix_timestamp() >= cutoff)
+                {
+                    result.insert(member.id_number);
+                }
+            }
+            Err(e) => warn!("failed to look up member {member:?} with error: {e:?}"),
+        }
+    }
+    info!(?result);
+    result
+}
+
+/// Posts the groups, records them so future rounds can avoid repeating them,
+/// creates a thread for each group and lets each member know the details of their group
+#[instrument(skip(cache_http, data, cohort, groups), fields(program = %cohort.program()))]
+pub async fn do_announce_groups(
+    cache_http: impl CacheHttp,
+    channel_id: ChannelId,
+    data: &Data,
+    cohort: &Cohort,
+    groups: &[Group],
+) -> anyhow::Result<()> {
+    info!("START");
+    let group_size = cohort.settings()?.group_size;
+    let msg = format!(
+        "**Cohort Groups**\nThis round we are meeting in {group_size}. Say hi to your group!\n\n{}",
+        display_groups(groups)?
+    );
+    // Large cohorts do not fit in one message
+    for chunk in split_into_messages(&msg) {
+        channel_id.say(&cache_http, chunk).await?;
+    }
+    cohort.history_record_round(groups)?;
+    cohort.record_groups_set(groups)?;
+    do_create_group_threads(&cache_http, channel_id, cohort, groups).await?;
+    do_notify_group_members(&cache_http, data, cohort, groups).await?;
+    info!("END");
+    Ok(())
+}
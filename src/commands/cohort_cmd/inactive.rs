@@ -0,0 +1,318 @@
+//! Groups the functionality for detecting inactive members and re-matching their partners
+
+use anyhow::Context as _;
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, CacheHttp, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateInteractionResponse, CreateInteractionResponseMessage, Mentionable as _,
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Context, Data,
+    commands::{
+        autocomplete_program, can_manage_cohort,
+        cohort_cmd::{
+            effects::{Effects, Post},
+            grouping::GroupingInputs,
+            notifications::{do_notify_admins, do_notify_group_members, unreachable_list},
+            threads::do_create_group_threads,
+        },
+        resolve_program, resolve_program_for_interaction, tracing_handler_start,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            grouping::{Group, display_groups},
+            program::ProgramKey,
+        },
+        guild::GuildConfig,
+        user_serde::{UserIdNumber, UserRecord, UserRecordSupport as _},
+    },
+};
+
+/// Prefix of the custom ID of all re-match buttons, used to route interactions here
+pub const REMATCH_PREFIX: &str = "rematch:";
+const REMATCH_REQUEST_ID: &str = "rematch:request";
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    track_edits,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-inactive_after", skip(ctx))]
+/// Sets how many check-ins in a row can be missed before a member is inactive (no args to see current)
+pub async fn inactive_after(
+    ctx: Context<'_>,
+    #[description = "Number of missed check-ins (0 disables detection)"] missed_check_ins: Option<
+        u8,
+    >,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    if let Some(limit) = missed_check_ins {
+        cohort.settings_missed_check_ins_limit_set(ctx.author_id_number(), limit)?;
+        ctx.reply(format!(
+            "Members will be considered inactive after missing {limit} check-ins in a row"
+        ))
+        .await?;
+    } else {
+        let limit = cohort.settings()?.missed_check_ins_limit;
+        ctx.reply(format!(
+            "Members are considered inactive after missing {limit} check-ins in a row"
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-rematch", skip(ctx))]
+/// Groups members whose partners are inactive with each other (only those that asked)
+pub async fn rematch(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().context("rematch is guild only")?;
+    let record = cohort.record()?;
+    let members: Vec<UserRecord> = record
+        .rematch_requests()
+        .iter()
+        .filter(|&&x| record.is_stranded(x))
+        .filter_map(|&x| {
+            record
+                .group_of(x)
+                .and_then(|published| published.group.members.iter().find(|m| m.id_number == x))
+                .cloned()
+        })
+        .collect();
+    info!(?members);
+    if members.len() < 2 {
+        ctx.reply(format!(
+            "**At least 2 members need to request a re-match but {} have**",
+            members.len()
+        ))
+        .await?;
+        return Ok(());
+    }
+    let groups = GroupingInputs::load_for_members(ctx, guild_id, &cohort, members)
+        .await?
+        .form_groups()?;
+    let channel_id = cohort.channel_id();
+    do_announce_rematch(ctx, channel_id, data, &cohort, &groups).await?;
+    ctx.reply(format!(
+        "{} new groups posted in {}",
+        groups.len(),
+        channel_id.mention()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Posts the new groups and moves the members into them
+#[instrument(skip(cache_http, data, cohort, groups), fields(program = %cohort.program()))]
+async fn do_announce_rematch(
+    cache_http: impl CacheHttp,
+    channel_id: serenity::ChannelId,
+    data: &Data,
+    cohort: &Cohort,
+    groups: &[Group],
+) -> anyhow::Result<()> {
+    info!("START");
+    let msg = format!(
+        "**Re-matched Groups**\nThese members have been given new groups for the rest of the cohort:\n\n{}",
+        display_groups(groups)?
+    );
+    channel_id.say(&cache_http, msg).await?;
+    cohort.history_record_round(groups)?;
+    cohort.record_rematch(groups)?;
+    do_create_group_threads(&cache_http, channel_id, cohort, groups).await?;
+    do_notify_group_members(&cache_http, data, cohort, groups).await?;
+    info!("END");
+    Ok(())
+}
+
+/// Flags members that have missed too many check-ins and lets their partners and the admins know
+#[instrument(skip(effects, config, cohort), fields(program = %cohort.program()))]
+pub async fn do_detect_inactive(
+    effects: &Effects<'_>,
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    info!("START");
+    let limit = cohort.settings()?.missed_check_ins_limit;
+    let newly_inactive = cohort.record_flag_inactive(limit.into())?;
+    if newly_inactive.is_empty() {
+        info!("END no newly inactive members");
+        return Ok(());
+    }
+    let record = cohort.record()?;
+    let mut admin_msg = format!("These members have missed the last {limit} check-ins:\n");
+    let mut unreachable: Vec<UserRecord> = vec![];
+    for &inactive in newly_inactive.iter() {
+        let stranded = record.stranded_partners_of(inactive);
+        writeln!(
+            admin_msg,
+            "- {} (stranded partners: {})",
+            inactive.to_user_id().mention(),
+            mention_list(&stranded)
+        )?;
+        if stranded.is_empty() {
+            continue;
+        }
+        let post = Post::text(stranded_partners_text(&stranded, inactive, limit)).with_components(
+            vec![rematch_request_row(cohort.key())],
+            "a button to ask for a new partner",
+        );
+        let Some(published) = record.group_of(inactive) else {
+            continue;
+        };
+        match published.thread_id {
+            Some(thread_id) => {
+                if let Err(e) = effects.send(thread_id, post).await {
+                    warn!("failed to notify thread {thread_id} with error: {e:?}");
+                }
+            }
+            None => {
+                for member in published
+                    .group
+                    .members
+                    .iter()
+                    .filter(|x| stranded.contains(&x.id_number))
+                {
+                    let is_opted_out = cohort
+                        .scores_record(member.id_number)?
+                        .is_some_and(|x| x.dm_opt_out());
+                    if is_opted_out {
+                        info!("{member:?} opted out of DMs");
+                        unreachable.push(member.clone());
+                        continue;
+                    }
+                    if let Err(e) = effects.direct_message(member.id_number, post.clone()).await {
+                        warn!("failed to DM {member:?} with error: {e:?}");
+                        unreachable.push(member.clone());
+                    }
+                }
+            }
+        }
+    }
+    write!(
+        admin_msg,
+        "Use `/cohort rematch program:{}` once stranded partners have requested a new partner",
+        cohort.program()
+    )?;
+    effects.notify_admins(config, admin_msg).await?;
+    if !unreachable.is_empty() {
+        effects
+            .notify_admins(
+                config,
+                format!(
+                    "Unable to offer these stranded members a new partner by DM (DMs turned off or closed): {}",
+                    unreachable_list(&unreachable)
+                ),
+            )
+            .await?;
+    }
+    info!("END");
+    Ok(())
+}
+
+/// Handles presses of the button offering a new partner
+#[instrument(skip(ctx, interaction, data))]
+pub async fn handle_rematch_interaction(
+    ctx: &serenity::Context,
+    interaction: &ComponentInteraction,
+    data: &Data,
+) -> anyhow::Result<()> {
+    info!("START");
+    // Buttons posted before programs existed do not include the program
+    let program = match interaction.data.custom_id.strip_prefix(REMATCH_REQUEST_ID) {
+        Some("") => None,
+        Some(rest) if rest.starts_with(':') => Some(&rest[1..]),
+        _ => {
+            warn!(
+                "unexpected re-match custom id: {:?}",
+                interaction.data.custom_id
+            );
+            return Ok(());
+        }
+    };
+    let cohort = resolve_program_for_interaction(data, program)?;
+    let user_id_number: UserIdNumber = interaction.user.id.into();
+    let msg = match cohort.record_rematch_request(user_id_number) {
+        Ok(()) => {
+            do_notify_admins(
+                ctx,
+                data,
+                cohort.guild_id(),
+                format!(
+                    "{} has requested a new partner. Use `/cohort rematch program:{}` to re-match",
+                    user_id_number.to_user_id().mention(),
+                    cohort.program()
+                ),
+            )
+            .await?;
+            "Request received. You will be re-matched once an admin runs the re-match".to_string()
+        }
+        Err(e) => e.to_string(),
+    };
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(msg)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    info!("END");
+    Ok(())
+}
+
+/// The button members press to ask to be re-matched
+pub fn rematch_request_row(key: &ProgramKey) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{REMATCH_REQUEST_ID}:{}", key.to_custom_id_part()))
+            .label("Request a new partner")
+            .style(ButtonStyle::Primary),
+    ])
+}
+
+/// Lets the partners of a member that stopped checking in know they can ask for a new partner
+fn stranded_partners_text(stranded: &[UserIdNumber], inactive: UserIdNumber, limit: u8) -> String {
+    format!(
+        "{} {} has missed the last {limit} check-ins. \
+        If you would like to be re-matched with another member press the button below.",
+        mention_list(stranded),
+        inactive.to_user_id().mention(),
+    )
+}
+
+pub fn mention_list(users: &[UserIdNumber]) -> String {
+    if users.is_empty() {
+        return "None".to_string();
+    }
+    let mentions: Vec<String> = users
+        .iter()
+        .map(|x| x.to_user_id().mention().to_string())
+        .collect();
+    mentions.join(", ")
+}
@@ -2,16 +2,36 @@
 
 use crate::{
     Context, Data,
-    commands::{is_auth, tracing_handler_start},
+    commands::{
+        audit::do_audit,
+        autocomplete_program, can_manage_cohort,
+        cohort_cmd::{
+            archive::do_archive_cohort,
+            effects::{Effects, Post},
+            eligibility::do_check_eligibility,
+            membership::{do_late_join, do_leave},
+            roles::{do_end_cohort_roles, do_grant_cohort_role, do_remove_cohort_role},
+            waitlist::do_promote_from_waitlist,
+        },
+        confirm::confirm,
+        resolve_program, tracing_handler_start,
+    },
     model::{
-        cohort::interested_list::{InterestedList, ScoreValue},
-        user_serde::UserRecordSupport as _,
+        audit::AuditAction,
+        cohort::{
+            Cohort,
+            archive::CohortNumber,
+            interested_list::{InterestedList, OutcomeSetScore, ScoreValue},
+        },
+        guild::GuildConfig,
+        schedule::UnixTimestamp,
+        user_serde::{UserIdNumber, UserRecord, UserRecordSupport as _},
     },
     sanitize_markdown,
 };
 use poise::{
     CreateReply,
-    serenity_prelude::{CacheHttp, ChannelId, CreateEmbed, CreateMessage},
+    serenity_prelude::{CacheHttp, ChannelId, CreateEmbed, Mentionable as _},
 };
 use std::fmt::Debug;
 use tracing::{info, instrument};
@@ -20,26 +40,45 @@ use tracing::{info, instrument};
     prefix_command,
     slash_command,
     track_edits,
-    subcommands("set", "remove", "leader_board", "message", "reset")
+    subcommands(
+        "set",
+        "remove",
+        "leader_board",
+        "message",
+        "reset",
+        "goal",
+        "availability",
+        "dms"
+    )
 )]
 #[instrument(name = "cohort-register", skip(ctx))]
 /// Commands related to scoring during the event and if called using `bbur score` sets the score
-pub async fn register(ctx: Context<'_>, value: ScoreValue) -> anyhow::Result<()> {
-    do_set_score(ctx, value).await
+pub async fn register(
+    ctx: Context<'_>,
+    value: ScoreValue,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    do_set_score(ctx, value, program).await
 }
 
 #[poise::command(prefix_command, slash_command)]
 #[instrument(name = "unranked-score-remove", skip(ctx))]
 /// Remove your score
-pub async fn remove(ctx: Context<'_>) -> anyhow::Result<()> {
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
-    let did_remove = ctx
-        .data()
-        .inner
-        .cohort
-        .score_remove(&ctx.author_to_user_record().await)?;
+    let cohort = resolve_program(&ctx, program).await?;
+    let did_remove =
+        do_remove_member(ctx, ctx.data(), &cohort, &ctx.author_to_user_record().await).await?;
     display_scores_with_msg(
         &ctx,
+        &cohort,
         if did_remove {
             "Score removed"
         } else {
@@ -53,17 +92,107 @@ pub async fn remove(ctx: Context<'_>) -> anyhow::Result<()> {
 #[poise::command(prefix_command, slash_command, aliases("disp"))]
 #[instrument(name = "unranked-score-leader_board", skip(ctx))]
 /// Show the current leader_board [aliases("disp")]
-pub async fn leader_board(ctx: Context<'_>) -> anyhow::Result<()> {
+pub async fn leader_board(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
-    display_scores(&ctx).await?;
+    let cohort = resolve_program(&ctx, program).await?;
+    display_scores(&ctx, &cohort).await?;
     Ok(())
 }
 
 #[poise::command(prefix_command, slash_command, track_edits)]
 #[instrument(name = "unranked-score-set", skip(ctx))]
 /// Set or overwrite your score
-pub async fn set(ctx: Context<'_>, score: ScoreValue) -> anyhow::Result<()> {
-    do_set_score(ctx, score).await
+pub async fn set(
+    ctx: Context<'_>,
+    score: ScoreValue,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    do_set_score(ctx, score, program).await
+}
+
+#[poise::command(prefix_command, slash_command, track_edits)]
+#[instrument(name = "unranked-score-goal", skip(ctx))]
+/// Set the goal you want to work on during the cohort (leave empty to clear)
+pub async fn goal(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    #[lazy]
+    program: Option<String>,
+    #[rest] goal: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let is_cleared = goal.is_none();
+    let goal = goal.map(sanitize_markdown);
+    let is_registered = cohort.scores_goal_set(ctx.author_id_number(), goal)?;
+    ctx.reply(if !is_registered {
+        "**You need to register before setting a goal**"
+    } else if is_cleared {
+        "Goal cleared"
+    } else {
+        "Goal set"
+    })
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, track_edits)]
+#[instrument(name = "unranked-score-availability", skip(ctx))]
+/// Set when you are usually available to meet your group (leave empty to clear)
+pub async fn availability(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    #[lazy]
+    program: Option<String>,
+    #[rest] availability: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let is_cleared = availability.is_none();
+    let availability = availability.map(sanitize_markdown);
+    let is_registered = cohort.scores_availability_set(ctx.author_id_number(), availability)?;
+    ctx.reply(if !is_registered {
+        "**You need to register before setting your availability**"
+    } else if is_cleared {
+        "Availability cleared"
+    } else {
+        "Availability set"
+    })
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "unranked-score-dms", skip(ctx))]
+/// Choose if the bot should send you direct messages about your group
+pub async fn dms(
+    ctx: Context<'_>,
+    enabled: bool,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let is_registered = cohort.scores_dm_opt_out_set(ctx.author_id_number(), !enabled)?;
+    ctx.reply(if !is_registered {
+        "**You need to register before changing your DM preference**"
+    } else if enabled {
+        "You will receive direct messages about your group"
+    } else {
+        "You will no longer receive direct messages about your group"
+    })
+    .await?;
+    Ok(())
 }
 
 #[poise::command(
@@ -72,20 +201,32 @@ pub async fn set(ctx: Context<'_>, score: ScoreValue) -> anyhow::Result<()> {
     track_edits,
     aliases("msg"),
     guild_only = true,
-    check = "is_auth"
+    check = "can_manage_cohort"
 )]
 #[instrument(name = "unranked-score-message", skip(ctx))]
 /// Set message displayed with scores (Replaces current message) [aliases("msg")]
-pub async fn message(ctx: Context<'_>, #[rest] msg: Option<String>) -> anyhow::Result<()> {
+pub async fn message(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    #[lazy]
+    program: Option<String>,
+    #[rest] msg: Option<String>,
+) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
     let is_cleared = msg.is_none();
     let msg = sanitize_markdown(msg.unwrap_or_default());
-    ctx.data()
-        .inner
-        .cohort
-        .scores_message(ctx.author_id_number(), msg)?;
+    cohort.scores_message(ctx.author_id_number(), msg.clone())?;
+    do_audit(
+        &ctx,
+        Some(&cohort),
+        AuditAction::ScoresMessageSet((!is_cleared).then_some(msg)),
+    )
+    .await?;
     display_scores_with_msg(
         &ctx,
+        &cohort,
         if is_cleared {
             "Message cleared"
         } else {
@@ -96,46 +237,215 @@ pub async fn message(ctx: Context<'_>, #[rest] msg: Option<String>) -> anyhow::R
     Ok(())
 }
 
-#[poise::command(hide_in_help, prefix_command, guild_only = true, check = "is_auth")]
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
 #[instrument(name = "unranked-score-reset", skip(ctx))]
 /// Sets scores back to the default
-pub async fn reset(ctx: Context<'_>) -> anyhow::Result<()> {
+pub async fn reset(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
-    do_scores_reset(&ctx, ctx.channel_id(), ctx.data()).await?;
+    let cohort = resolve_program(&ctx, program).await?;
+    let changes = describe_scores_reset(ctx.data(), &cohort)?;
+    if !confirm(
+        ctx,
+        &format!("Reset the {} scores", cohort.program()),
+        &changes,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    do_scores_reset(
+        &Effects::Live(&ctx),
+        ctx.channel_id(),
+        ctx.data().guild_config(cohort.guild_id())?.as_ref(),
+        &cohort,
+    )
+    .await?;
     ctx.reply("Scores reset").await?;
+    do_audit(&ctx, Some(&cohort), AuditAction::ScoresReset).await?;
     Ok(())
 }
 
-#[instrument(skip(cache_http, data))]
+#[instrument(skip(effects, config, cohort), fields(program = %cohort.program()))]
 pub async fn do_scores_reset(
-    cache_http: impl CacheHttp,
+    effects: &Effects<'_>,
     channel_id: ChannelId,
-    data: &Data,
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
 ) -> anyhow::Result<()> {
     info!("START");
-    channel_id.say(&cache_http, "Scores before reset").await?;
-    display_scores_channel(&cache_http, channel_id, data).await?;
-    data.inner.cohort.scores_reset()?;
+    let members = cohort_members(cohort)?;
+    if let Some(number) = do_archive_cohort(cohort)? {
+        effects
+            .send(channel_id, Post::text(archived_message(number)))
+            .await?;
+    }
+    effects
+        .send(channel_id, Post::text("Scores before reset"))
+        .await?;
+    display_scores_channel(effects, channel_id, cohort).await?;
+    cohort.scores_reset()?;
+    do_end_cohort_roles(effects, config, cohort, &members).await?;
     Ok(())
 }
 
-async fn do_set_score(ctx: Context<'_>, score: ScoreValue) -> anyhow::Result<()> {
+pub fn archived_message(number: CohortNumber) -> String {
+    format!("Cohort archived as cohort {number}, see `/cohort show number:{number}`")
+}
+
+/// Lists what [`do_scores_reset`] will change so it can be confirmed before anything is changed
+pub fn describe_scores_reset(data: &Data, cohort: &Cohort) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+    let mut result = String::new();
+    let record = cohort.record()?;
+    if record.is_in_progress() {
+        writeln!(
+            result,
+            "- The cohort in progress ({} groups) is archived",
+            record.groups().len()
+        )?;
+    }
+    writeln!(
+        result,
+        "- All {} registrations and the scores message are cleared",
+        cohort.scores_registered_users()?.len()
+    )?;
+    if let Some(config) = data.guild_config(cohort.guild_id())? {
+        let members = cohort_members(cohort)?.len();
+        if let Some(role_id) = config.role_cohort {
+            writeln!(
+                result,
+                "- {} is removed from {members} members",
+                role_id.mention()
+            )?;
+        }
+        if let Some(role_id) = config.role_veteran {
+            writeln!(
+                result,
+                "- {} is given to those of the {members} members that have now completed {} cohorts",
+                role_id.mention(),
+                config.veteran_cohorts
+            )?;
+        }
+    }
+    Ok(result)
+}
+
+/// Everyone taking part in the cohort, registered or already placed in a group
+pub fn cohort_members(cohort: &Cohort) -> anyhow::Result<Vec<UserIdNumber>> {
+    let mut result: Vec<UserIdNumber> = cohort
+        .scores_registered_users()?
+        .iter()
+        .map(|x| x.id_number)
+        .collect();
+    result.extend(
+        cohort
+            .record()?
+            .groups()
+            .iter()
+            .flat_map(|x| x.group.member_ids()),
+    );
+    result.sort();
+    result.dedup();
+    Ok(result)
+}
+
+async fn do_set_score(
+    ctx: Context<'_>,
+    score: ScoreValue,
+    program: Option<String>,
+) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
-    ctx.data()
-        .inner
-        .cohort
-        .score_set(ctx.author_to_user_record().await, score)?;
-    display_scores_with_msg(&ctx, "Score set").await?;
+    let data = ctx.data();
+    let cohort = resolve_program(&ctx, program).await?;
+    let user = ctx.author_to_user_record().await;
+    if cohort.scores_record(user.id_number)?.is_none() {
+        let reasons = do_check_eligibility(ctx, &cohort, user.id_number).await?;
+        if !reasons.is_empty() {
+            let reasons: Vec<String> = reasons.iter().map(|x| format!("- {x}")).collect();
+            ctx.send(
+                CreateReply::default()
+                    .content(format!(
+                        "**Unable to sign up for the {} cohort**\n{}",
+                        cohort.program(),
+                        reasons.join("\n")
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+    let max_participants = cohort.settings()?.max_participants();
+    let extra_msg = do_register_member(ctx, data, &cohort, user, score, max_participants).await?;
+    display_scores_with_msg(&ctx, &cohort, "Score set").await?;
+    if let Some(msg) = extra_msg {
+        ctx.send(CreateReply::default().content(msg).ephemeral(true))
+            .await?;
+    }
     Ok(())
 }
 
-#[instrument(skip(ctx))]
+/// Sets the member's score registering them if they are new and returns a message for the member if there is one
+///
+/// New members go on the waitlist if there are already `max_participants` registered
+#[instrument(skip(cache_http, data, cohort), fields(program = %cohort.program()))]
+pub async fn do_register_member(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user: UserRecord,
+    score: ScoreValue,
+    max_participants: Option<usize>,
+) -> anyhow::Result<Option<String>> {
+    let outcome = cohort.score_set(user.clone(), score, max_participants, UnixTimestamp::now()?)?;
+    Ok(match outcome {
+        OutcomeSetScore::Updated => None,
+        OutcomeSetScore::Registered => {
+            do_grant_cohort_role(&cache_http, data, cohort, user.id_number).await?;
+            do_late_join(&cache_http, data, cohort, user).await?
+        }
+        OutcomeSetScore::Waitlisted(position) => Some(format!(
+            "The cohort is full so you have been added to the waitlist at position {position}. \
+            You will get a DM if a place opens up"
+        )),
+    })
+}
+
+/// Removes the member from the cohort (including their group and role) and returns true iff they were registered
+#[instrument(skip(cache_http, data, cohort), fields(program = %cohort.program()))]
+pub async fn do_remove_member(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user: &UserRecord,
+) -> anyhow::Result<bool> {
+    let did_remove = cohort.score_remove(user)?;
+    if did_remove {
+        do_leave(&cache_http, data, cohort, user.id_number).await?;
+        do_remove_cohort_role(&cache_http, data, cohort, user.id_number).await?;
+        do_promote_from_waitlist(&cache_http, data, cohort).await?;
+    }
+    Ok(did_remove)
+}
+
+#[instrument(skip(ctx, cohort))]
 pub async fn do_display_scores<S: Into<String> + Debug>(
     ctx: &Context<'_>,
+    cohort: &Cohort,
     extra_msg: Option<S>,
 ) -> anyhow::Result<()> {
     info!("START");
-    let mut builder = display_generate_reply(ctx)?;
+    let mut builder = display_generate_reply(cohort)?;
     if let Some(msg) = extra_msg {
         builder = builder.content(msg);
     }
@@ -143,53 +453,57 @@ pub async fn do_display_scores<S: Into<String> + Debug>(
     Ok(())
 }
 
-#[instrument(skip(ctx))]
+#[instrument(skip(ctx, cohort))]
 async fn display_scores_with_msg<S: Into<String> + Debug>(
     ctx: &Context<'_>,
+    cohort: &Cohort,
     extra_msg: S,
 ) -> anyhow::Result<()> {
-    do_display_scores(ctx, Some(extra_msg)).await
+    do_display_scores(ctx, cohort, Some(extra_msg)).await
 }
 
-async fn display_scores(ctx: &Context<'_>) -> anyhow::Result<()> {
-    do_display_scores::<&str>(ctx, None).await
+async fn display_scores(ctx: &Context<'_>, cohort: &Cohort) -> anyhow::Result<()> {
+    do_display_scores::<&str>(ctx, cohort, None).await
 }
 
-#[instrument(skip(ctx))]
-fn display_generate_reply(ctx: &Context<'_>) -> anyhow::Result<CreateReply> {
+#[instrument(skip(cohort))]
+fn display_generate_reply(cohort: &Cohort) -> anyhow::Result<CreateReply> {
     info!("START");
-    let embed = display_generate_embed(ctx.data())?;
+    let embed = display_generate_embed(cohort)?;
     info!("END");
     Ok(CreateReply::default().embed(embed))
 }
 
-#[instrument(skip(data))]
-fn display_generate_message(data: &Data) -> anyhow::Result<CreateMessage> {
+#[instrument(skip(cohort))]
+fn display_generate_embed(cohort: &Cohort) -> anyhow::Result<CreateEmbed> {
     info!("START");
-    let embed = display_generate_embed(data)?;
-    info!("END");
-    Ok(CreateMessage::new().embed(embed))
-}
-
-#[instrument(skip(data))]
-fn display_generate_embed(data: &Data) -> anyhow::Result<CreateEmbed> {
-    info!("START");
-    let scores_as_string = data.inner.cohort.scores_as_string()?;
+    let scores_as_string = cohort.scores_as_string()?;
+    let title = if cohort.program().is_default() {
+        InterestedList::DISPLAY_TITLE.to_string()
+    } else {
+        format!("{} ({})", InterestedList::DISPLAY_TITLE, cohort.program())
+    };
     let embed = CreateEmbed::new()
-        .title(InterestedList::DISPLAY_TITLE)
+        .title(title)
         .description(scores_as_string);
     info!("END");
     Ok(embed)
 }
 
-#[instrument(skip(cache_http, data))]
+#[instrument(skip(effects, cohort))]
 pub async fn display_scores_channel(
-    cache_http: impl CacheHttp,
+    effects: &Effects<'_>,
     channel_id: ChannelId,
-    data: &Data,
+    cohort: &Cohort,
 ) -> anyhow::Result<()> {
     info!("START");
-    let builder = display_generate_message(data)?;
-    channel_id.send_message(&cache_http, builder).await?;
+    let post = Post::embed(
+        display_generate_embed(cohort)?,
+        format!(
+            "the scores of the {} registered members",
+            cohort.scores_registered_users()?.len()
+        ),
+    );
+    effects.send(channel_id, post).await?;
     Ok(())
 }
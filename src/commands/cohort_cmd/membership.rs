@@ -0,0 +1,226 @@
+//! Handles members joining or leaving while a cohort is in progress
+
+use poise::serenity_prelude::{CacheHttp, CreateMessage, Mentionable as _};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Data,
+    commands::cohort_cmd::{
+        inactive::rematch_request_row,
+        notifications::{
+            do_notify_admins, do_notify_group_members, do_notify_member, do_report_unreachable,
+            unreachable_list,
+        },
+        threads::do_create_group_threads,
+    },
+    model::{
+        cohort::{
+            Cohort,
+            record::{OutcomeLateJoin, OutcomeLeave, PublishedGroup},
+        },
+        user_serde::{UserIdNumber, UserRecord},
+    },
+};
+
+/// Places a member that registered after the groups were published
+///
+/// Returns a message to show the member if the cohort is in progress
+#[instrument(skip(cache_http, data, cohort), fields(program = %cohort.program()))]
+pub async fn do_late_join(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user: UserRecord,
+) -> anyhow::Result<Option<String>> {
+    info!("START");
+    let group_size = cohort.settings()?.group_size.get();
+    let constraints = cohort.constraints()?;
+    let result = match cohort.record_late_join(user.clone(), group_size, &constraints)? {
+        OutcomeLateJoin::NotInProgress | OutcomeLateJoin::AlreadyInGroup => None,
+        OutcomeLateJoin::JoinedGroup(before) => {
+            let mut after = before.clone();
+            after.group.members.push(user.clone());
+            cohort.history_record_late_join(user.id_number, &after.group)?;
+            if let Some(thread_id) = after.thread_id {
+                if let Err(e) = thread_id
+                    .add_thread_member(cache_http.http(), user.id_number.to_user_id())
+                    .await
+                {
+                    warn!("failed to add {user:?} to thread with error: {e:?}");
+                }
+                thread_id
+                    .say(
+                        &cache_http,
+                        format!(
+                            "{} please welcome {} who joined the cohort late",
+                            before.group,
+                            user.id_number.to_user_id().mention()
+                        ),
+                    )
+                    .await?;
+            }
+            if !do_notify_member(&cache_http, cohort, &user, &after).await? {
+                do_report_unreachable(&cache_http, data, cohort, std::slice::from_ref(&user)).await?;
+            }
+            do_notify_admins(
+                &cache_http,
+                data,
+                cohort.guild_id(),
+                format!(
+                    "{} joined late and was added to the group: {}",
+                    user.id_number.to_user_id().mention(),
+                    before.group
+                ),
+            )
+            .await?;
+            Some(format!(
+                "The cohort has already started so you have been added to the group with {}",
+                before.group
+            ))
+        }
+        OutcomeLateJoin::PairedWithLateJoiner(group) => {
+            cohort.history_record_late_join(user.id_number, &group)?;
+            let groups = [group];
+            do_create_group_threads(&cache_http, cohort.channel_id(), cohort, &groups).await?;
+            do_notify_group_members(&cache_http, data, cohort, &groups).await?;
+            let [group] = groups;
+            do_notify_admins(
+                &cache_http,
+                data,
+                cohort.guild_id(),
+                format!("Late joiners grouped together: {group}"),
+            )
+            .await?;
+            Some(format!(
+                "The cohort has already started so you have been grouped with another late joiner: {group}"
+            ))
+        }
+        OutcomeLateJoin::Waiting => Some(
+            "The cohort has already started. You will be grouped when a spot opens up or another member joins"
+                .to_string(),
+        ),
+    };
+    info!("END");
+    Ok(result)
+}
+
+/// Removes a member that left from their group and lets the rest of the group know
+///
+/// Remaining members without an active partner are offered a re-match
+#[instrument(skip(cache_http, data, cohort), fields(program = %cohort.program()))]
+pub async fn do_leave(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user_id_number: UserIdNumber,
+) -> anyhow::Result<()> {
+    info!("START");
+    let remaining = match cohort.record_leave(user_id_number)? {
+        OutcomeLeave::NotInGroup | OutcomeLeave::LeftWaitingList => {
+            info!("END not in a group");
+            return Ok(());
+        }
+        OutcomeLeave::LeftGroup(remaining) => remaining,
+    };
+    if let Some(thread_id) = remaining.thread_id
+        && let Err(e) = thread_id
+            .remove_thread_member(cache_http.http(), user_id_number.to_user_id())
+            .await
+    {
+        warn!("failed to remove User# {user_id_number} from thread with error: {e:?}");
+    }
+    do_notify_admins(
+        &cache_http,
+        data,
+        cohort.guild_id(),
+        format!(
+            "{} left the cohort. Remaining group: {}",
+            user_id_number.to_user_id().mention(),
+            if remaining.group.members.is_empty() {
+                "None".to_string()
+            } else {
+                remaining.group.to_string()
+            }
+        ),
+    )
+    .await?;
+    if !remaining.group.members.is_empty() {
+        notify_remaining(&cache_http, data, cohort, user_id_number, &remaining).await?;
+    }
+    info!("END");
+    Ok(())
+}
+
+/// Tells the rest of the group that a member left and offers a re-match if they have no active partner left
+///
+/// Without a thread the members are sent a DM, the admins are told about those that could not be reached
+async fn notify_remaining(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user_id_number: UserIdNumber,
+    remaining: &PublishedGroup,
+) -> anyhow::Result<()> {
+    let record = cohort.record()?;
+    let is_stranded = remaining.group.member_ids().any(|x| record.is_stranded(x));
+    let mut msg = CreateMessage::new();
+    if is_stranded {
+        msg = msg
+            .content(format!(
+                "{} {} has left the cohort. \
+                If you would like to be re-matched with another member press the button below.",
+                remaining.group,
+                user_id_number.to_user_id().mention()
+            ))
+            .components(vec![rematch_request_row(cohort.key())]);
+    } else {
+        msg = msg.content(format!(
+            "{} {} has left the cohort.",
+            remaining.group,
+            user_id_number.to_user_id().mention()
+        ));
+    }
+    match remaining.thread_id {
+        Some(thread_id) => {
+            if let Err(e) = thread_id.send_message(&cache_http, msg).await {
+                warn!("failed to notify thread {thread_id} with error: {e:?}");
+            }
+        }
+        None => {
+            let mut unreachable: Vec<UserRecord> = vec![];
+            for member in remaining.group.members.iter() {
+                let is_opted_out = cohort
+                    .scores_record(member.id_number)?
+                    .is_some_and(|x| x.dm_opt_out());
+                if is_opted_out {
+                    info!("{member:?} opted out of DMs");
+                    unreachable.push(member.clone());
+                    continue;
+                }
+                if let Err(e) = member
+                    .id_number
+                    .to_user_id()
+                    .direct_message(&cache_http, msg.clone())
+                    .await
+                {
+                    warn!("failed to DM {member:?} with error: {e:?}");
+                    unreachable.push(member.clone());
+                }
+            }
+            if !unreachable.is_empty() {
+                do_notify_admins(
+                    &cache_http,
+                    data,
+                    cohort.guild_id(),
+                    format!(
+                        "Unable to tell these members by DM that {} left (DMs turned off or closed): {}",
+                        user_id_number.to_user_id().mention(),
+                        unreachable_list(&unreachable)
+                    ),
+                )
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
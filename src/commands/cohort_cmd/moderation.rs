@@ -0,0 +1,290 @@
+//! Groups the commands admins use to manage cohort membership on other members' behalf
+//!
+//! Every change is recorded in the audit log
+
+use poise::serenity_prelude::{CreateMessage, Mentionable as _, User};
+use tracing::{instrument, warn};
+
+use crate::{
+    Context,
+    commands::{
+        audit::do_audit,
+        autocomplete_program, call_to_parent_command, can_moderate_members,
+        cohort_cmd::interested_list::{do_register_member, do_remove_member},
+        confirm::confirm,
+        resolve_program, tracing_handler_start,
+    },
+    errors::bail_user,
+    model::{
+        audit::AuditAction,
+        cohort::{bans::Ban, interested_list::ScoreValue},
+        schedule::UnixTimestamp,
+        user_serde::{UserIdNumber, UserRecord, UserRecordSupport as _},
+    },
+    sanitize_markdown,
+};
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members",
+    subcommand_required,
+    subcommands("add", "remove", "ban", "unban", "bans")
+)]
+#[instrument(name = "cohort-admin", skip(ctx))]
+/// Commands for admins to manage members of the cohort
+pub async fn admin(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members"
+)]
+#[instrument(name = "cohort-admin-add", skip(ctx))]
+/// Register a member for the cohort (skips the eligibility rules and participant cap)
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Member to register"] user: User,
+    #[description = "Score to give them (defaults to 0)"] score: Option<ScoreValue>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let data = ctx.data();
+    let record = UserRecord {
+        id_number: user.id.into(),
+        name: user.display_name().into(),
+    };
+    if cohort.scores_record(record.id_number)?.is_some() {
+        ctx.reply(format!("**{} is already registered**", user.name))
+            .await?;
+        return Ok(());
+    }
+    let msg = do_register_member(
+        ctx,
+        data,
+        &cohort,
+        record.clone(),
+        score.unwrap_or_default(),
+        None,
+    )
+    .await?;
+    do_audit(
+        &ctx,
+        Some(&cohort),
+        AuditAction::MemberAdded(record.id_number),
+    )
+    .await?;
+    if let Some(msg) = msg
+        && let Err(e) = user
+            .direct_message(ctx, CreateMessage::new().content(msg))
+            .await
+    {
+        warn!("failed to DM {record:?} with error: {e:?}");
+    }
+    ctx.reply(format!("{} registered", user.name)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members"
+)]
+#[instrument(name = "cohort-admin-remove", skip(ctx))]
+/// Remove a member from the cohort (including their group if groups are published)
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Member to remove"] user: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let data = ctx.data();
+    let record = UserRecord {
+        id_number: user.id.into(),
+        name: user.display_name().into(),
+    };
+    if !do_remove_member(ctx, data, &cohort, &record).await? {
+        ctx.reply(format!("**{} is not registered**", user.name))
+            .await?;
+        return Ok(());
+    }
+    do_audit(
+        &ctx,
+        Some(&cohort),
+        AuditAction::MemberRemoved(record.id_number),
+    )
+    .await?;
+    ctx.reply(format!("{} removed from the cohort", user.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members"
+)]
+#[instrument(name = "cohort-admin-ban", skip(ctx))]
+/// Stop a member from signing up for future cohorts (replaces any existing ban)
+pub async fn ban(
+    ctx: Context<'_>,
+    #[description = "Member to ban"] user: User,
+    #[description = "Number of days the ban lasts (leave empty for no expiry)"]
+    #[min = 1]
+    #[max = 3650]
+    days: Option<u16>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    #[lazy]
+    program: Option<String>,
+    #[description = "Reason for the ban"]
+    #[rest]
+    reason: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let user_id_number: UserIdNumber = user.id.into();
+    let now = UnixTimestamp::now()?;
+    let until = match days {
+        Some(days) => {
+            // `min` is only enforced by Discord for slash commands
+            if days == 0 {
+                bail_user!("A ban must last at least 1 day, leave the days empty for no expiry");
+            }
+            let Some(secs) = i32::from(days)
+                .checked_mul(24 * 60 * 60)
+                .and_then(|x| now.as_secs().checked_add(x))
+            else {
+                bail_user!("A ban of {days} days is too long, leave the days empty for no expiry");
+            };
+            Some(UnixTimestamp::new(secs))
+        }
+        None => None,
+    };
+    let reason = reason.map(sanitize_markdown);
+    let ban = Ban {
+        by: ctx.author_id_number(),
+        reason: reason.clone(),
+        created: now,
+        until,
+    };
+    let mut changes = format!(
+        "- {} can not sign up for the {} program ({ban})",
+        user.id.mention(),
+        cohort.program()
+    );
+    if let Some(existing) = cohort.bans()?.active_ban(user_id_number, now) {
+        write!(changes, "\n- Their current ban is replaced ({existing})")?;
+    }
+    if !confirm(ctx, &format!("Ban {}", user.name), &changes).await? {
+        return Ok(());
+    }
+    let previous = cohort.bans_add(user_id_number, ban.clone())?;
+    do_audit(
+        &ctx,
+        Some(&cohort),
+        AuditAction::Banned {
+            user: user_id_number,
+            reason,
+            until,
+        },
+    )
+    .await?;
+    let is_registered = cohort.scores_record(user_id_number)?.is_some();
+    ctx.reply(format!(
+        "{} {}{}{}",
+        user.name,
+        ban,
+        if previous.is_some() {
+            "\nThis replaces their previous ban"
+        } else {
+            ""
+        },
+        if is_registered {
+            "\nThey are still registered for the current cohort, use `/cohort admin remove` to remove them"
+        } else {
+            ""
+        }
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members"
+)]
+#[instrument(name = "cohort-admin-unban", skip(ctx))]
+/// Allow a banned member to sign up again
+pub async fn unban(
+    ctx: Context<'_>,
+    #[description = "Member to unban"] user: User,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let user_id_number: UserIdNumber = user.id.into();
+    if cohort.bans_remove(user_id_number)?.is_none() {
+        ctx.reply(format!("**{} is not banned**", user.name))
+            .await?;
+        return Ok(());
+    }
+    do_audit(&ctx, Some(&cohort), AuditAction::Unbanned(user_id_number)).await?;
+    ctx.reply(format!("{} can sign up again", user.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_moderate_members"
+)]
+#[instrument(name = "cohort-admin-bans", skip(ctx))]
+/// List the members that are currently banned from cohorts
+pub async fn bans(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let bans = cohort.bans()?;
+    let mut msg = String::from("**Cohort Bans**\n");
+    let mut is_empty = true;
+    for (user_id_number, ban) in bans.active(UnixTimestamp::now()?) {
+        is_empty = false;
+        writeln!(msg, "- {} {ban}", user_id_number.to_user_id().mention())?;
+    }
+    if is_empty {
+        writeln!(msg, "None")?;
+    }
+    ctx.reply(msg).await?;
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Sends direct messages to members and summaries to admins
+
+use poise::serenity_prelude::{CacheHttp, CreateMessage, GuildId, Mentionable as _};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Data,
+    commands::cohort_cmd::effects::Effects,
+    model::{
+        cohort::{Cohort, grouping::Group, record::PublishedGroup},
+        user_serde::UserRecord,
+    },
+};
+
+/// Sends each member of the groups the details of their group and tells the admins about any that could not be reached
+#[instrument(skip(cache_http, data, cohort, groups), fields(program = %cohort.program()))]
+pub async fn do_notify_group_members(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    groups: &[Group],
+) -> anyhow::Result<()> {
+    info!("START");
+    let published_groups: Vec<PublishedGroup> = cohort
+        .record()?
+        .groups()
+        .iter()
+        .filter(|x| groups.contains(&x.group))
+        .cloned()
+        .collect();
+    let mut unreachable: Vec<UserRecord> = vec![];
+    for published in published_groups.iter() {
+        for member in published.group.members.iter() {
+            if !do_notify_member(&cache_http, cohort, member, published).await? {
+                unreachable.push(member.clone());
+            }
+        }
+    }
+    do_report_unreachable(&cache_http, data, cohort, &unreachable).await?;
+    info!("END");
+    Ok(())
+}
+
+/// Sends one member the details of their group
+///
+/// Returns false if the member could not be reached (members that opted out count as reached)
+#[instrument(skip(cache_http, cohort, published))]
+pub async fn do_notify_member(
+    cache_http: impl CacheHttp,
+    cohort: &Cohort,
+    member: &UserRecord,
+    published: &PublishedGroup,
+) -> anyhow::Result<bool> {
+    let record = cohort.scores_record(member.id_number)?;
+    if record.as_ref().is_some_and(|x| x.dm_opt_out()) {
+        info!("{member:?} opted out of DMs");
+        return Ok(true);
+    }
+    let msg = group_details_message(cohort, member, published)?;
+    if let Err(e) = member
+        .id_number
+        .to_user_id()
+        .direct_message(&cache_http, CreateMessage::new().content(msg))
+        .await
+    {
+        warn!("failed to DM {member:?} with error: {e:?}");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Tells the admins which members could not be sent their group details
+pub async fn do_report_unreachable(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    unreachable: &[UserRecord],
+) -> anyhow::Result<()> {
+    if !unreachable.is_empty() {
+        do_notify_admins(
+            &cache_http,
+            data,
+            cohort.guild_id(),
+            format!(
+                "Unable to send group details by DM to these members (DMs likely closed): {}",
+                unreachable_list(unreachable)
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Lists the members for the admins, with their names in case the mentions don't resolve
+pub fn unreachable_list(unreachable: &[UserRecord]) -> String {
+    let mentions: Vec<String> = unreachable
+        .iter()
+        .map(|x| format!("{} ({})", x.id_number.to_user_id().mention(), x.name))
+        .collect();
+    mentions.join(", ")
+}
+
+/// Sends a message to the server's admin channel if one is configured
+#[instrument(skip(cache_http, data))]
+pub async fn do_notify_admins(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    guild_id: GuildId,
+    msg: String,
+) -> anyhow::Result<()> {
+    Effects::Live(&cache_http)
+        .notify_admins(data.guild_config(guild_id)?.as_ref(), msg)
+        .await
+}
+
+fn group_details_message(
+    cohort: &Cohort,
+    member: &UserRecord,
+    published: &PublishedGroup,
+) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+    let mut result = String::from("Your cohort group has been published!\n\n**Your group**\n");
+    for other in published
+        .group
+        .members
+        .iter()
+        .filter(|x| x.id_number != member.id_number)
+    {
+        let record = cohort.scores_record(other.id_number)?;
+        writeln!(
+            result,
+            "- {} ({})",
+            other.id_number.to_user_id().mention(),
+            other.name
+        )?;
+        writeln!(
+            result,
+            "  - Goal: {}",
+            record.as_ref().and_then(|x| x.goal()).unwrap_or("Not set")
+        )?;
+        writeln!(
+            result,
+            "  - Availability: {}",
+            record
+                .as_ref()
+                .and_then(|x| x.availability())
+                .unwrap_or("Not set")
+        )?;
+    }
+    if let Some(thread_id) = published.thread_id {
+        writeln!(result, "\nYour group thread: {}", thread_id.mention())?;
+    }
+    write!(
+        result,
+        "\nTo stop receiving these messages use `/unranked register dms enabled:False`"
+    )?;
+    Ok(result)
+}
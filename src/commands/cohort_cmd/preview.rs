@@ -0,0 +1,335 @@
+//! Groups the commands that let admins review the groups before they are published
+
+use std::{collections::BTreeSet, num::NonZeroUsize, time::Duration};
+
+use anyhow::Context as _;
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        ButtonStyle, ComponentInteraction, ComponentInteractionCollector, CreateActionRow,
+        CreateButton, CreateEmbed, CreateInteractionResponse, Mentionable as _,
+    },
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Context,
+    commands::{
+        autocomplete_program, call_to_parent_command, can_manage_cohort,
+        cohort_cmd::grouping::{GroupingInputs, do_announce_groups},
+        resolve_program, take_lines, tracing_handler_start, truncate_text,
+    },
+    model::{
+        cohort::grouping::{Group, MemberPosition, display_draft, regroup_unlocked, swap_members},
+        one_based_id::OneBasedId,
+    },
+};
+
+/// Discord's limit on the length of an embed description
+const MAX_DESCRIPTION_LEN: usize = 4096;
+
+/// Room left for the line saying how many groups are shown
+const MAX_SUMMARY_LEN: usize = 50;
+
+/// How long the preview stays interactive. Must be below the 15 minutes discord allows for editing the reply
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[poise::command(
+    prefix_command,
+    slash_command,
+    subcommand_required,
+    subcommands("preview")
+)]
+#[instrument(name = "cohort-pairs", skip(ctx))]
+/// Commands related to reviewing the groups before they are published
+pub async fn pairs(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Swap two members"]
+struct SwapModal {
+    #[name = "First member (position like 1.2)"]
+    #[placeholder = "1.2"]
+    first: String,
+    #[name = "Second member (position like 3.1)"]
+    #[placeholder = "3.1"]
+    second: String,
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Lock or unlock a group"]
+struct LockModal {
+    #[name = "Group number"]
+    #[placeholder = "1"]
+    group: String,
+}
+
+/// The groups being reviewed and which of them should be kept when reshuffling
+struct PairingDraft {
+    groups: Vec<Group>,
+    locked: BTreeSet<OneBasedId>,
+    /// Feedback about the last action taken
+    note: Option<String>,
+}
+
+/// Custom IDs for the buttons, prefixed with the context ID so other commands' buttons are ignored
+struct ButtonIds {
+    prefix: String,
+    reshuffle: String,
+    swap: String,
+    lock: String,
+    publish: String,
+    cancel: String,
+}
+
+#[poise::command(
+    hide_in_help,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-pairs-preview", skip(ctx))]
+/// Preview the groups and adjust them before publishing to the cohort channel
+pub async fn preview(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().context("preview is guild only")?;
+    let inputs = GroupingInputs::load(ctx, guild_id, &cohort).await?;
+    let mut draft = PairingDraft {
+        groups: inputs.form_groups()?,
+        locked: BTreeSet::new(),
+        note: None,
+    };
+    let ids = ButtonIds::new(ctx.id());
+    let reply_handle = ctx
+        .send(draft.reply(&inputs, &ids)?.ephemeral(true))
+        .await?;
+
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .filter({
+            let prefix = ids.prefix.clone();
+            move |press| press.data.custom_id.starts_with(&prefix)
+        })
+        .timeout(PREVIEW_TIMEOUT)
+        .await
+    {
+        let custom_id = press.data.custom_id.as_str();
+        info!(custom_id);
+        draft.note = None;
+        if custom_id == ids.reshuffle {
+            acknowledge(ctx, &press).await?;
+            draft.reshuffle(&inputs);
+        } else if custom_id == ids.swap {
+            if let Some(response) = poise::execute_modal_on_component_interaction::<SwapModal>(
+                ctx,
+                press.clone(),
+                None,
+                Some(PREVIEW_TIMEOUT),
+            )
+            .await?
+            {
+                draft.swap(&response);
+            }
+        } else if custom_id == ids.lock {
+            if let Some(response) = poise::execute_modal_on_component_interaction::<LockModal>(
+                ctx,
+                press.clone(),
+                None,
+                Some(PREVIEW_TIMEOUT),
+            )
+            .await?
+            {
+                draft.toggle_lock(&response);
+            }
+        } else if custom_id == ids.publish
+            && !inputs
+                .rules()
+                .exclusion_violations(&draft.groups)
+                .is_empty()
+        {
+            // The button is disabled while there are violations but the draft may have changed
+            // since the message was last shown
+            acknowledge(ctx, &press).await?;
+            draft.note = Some(
+                "⚠ These groups break exclusions and can not be published, swap or reshuffle first"
+                    .to_string(),
+            );
+        } else if custom_id == ids.publish {
+            acknowledge(ctx, &press).await?;
+            let channel_id = cohort.channel_id();
+            do_announce_groups(ctx, channel_id, data, &cohort, &draft.groups).await?;
+            reply_handle
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .content(format!(
+                            "{} groups published in {}",
+                            draft.groups.len(),
+                            channel_id.mention()
+                        ))
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        } else if custom_id == ids.cancel {
+            acknowledge(ctx, &press).await?;
+            reply_handle
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .content("Preview cancelled, nothing was published")
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        } else {
+            warn!("unexpected button id received: {custom_id:?}");
+            continue;
+        }
+        reply_handle.edit(ctx, draft.reply(&inputs, &ids)?).await?;
+    }
+
+    info!("preview timed out");
+    reply_handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Preview timed out, nothing was published")
+                .components(vec![]),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Lets discord know the button press was received (the message is edited separately)
+async fn acknowledge(ctx: Context<'_>, press: &ComponentInteraction) -> anyhow::Result<()> {
+    press
+        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+        .await?;
+    Ok(())
+}
+
+impl PairingDraft {
+    fn reshuffle(&mut self, inputs: &GroupingInputs) {
+        match regroup_unlocked(
+            &self.groups,
+            &self.locked,
+            inputs.group_size,
+            &inputs.rules(),
+            &mut rand::rng(),
+        ) {
+            Ok(groups) => {
+                // Locked groups are moved to the front
+                self.locked = (0..self.locked.len()).map(OneBasedId::from_index).collect();
+                self.groups = groups;
+                self.note = Some("Reshuffled".to_string());
+            }
+            Err(e) => self.note = Some(format!("⚠ {e}")),
+        }
+    }
+
+    fn swap(&mut self, response: &SwapModal) {
+        let result = response
+            .first
+            .parse::<MemberPosition>()
+            .and_then(|first| Ok((first, response.second.parse::<MemberPosition>()?)))
+            .and_then(|(first, second)| {
+                swap_members(&mut self.groups, first, second)?;
+                Ok(format!("Swapped {first} and {second}"))
+            });
+        self.note = Some(match result {
+            Ok(msg) => msg,
+            Err(e) => format!("⚠ {e}"),
+        });
+    }
+
+    fn toggle_lock(&mut self, response: &LockModal) {
+        let group = match response.group.trim().parse::<NonZeroUsize>() {
+            Ok(x) => OneBasedId::from(x),
+            Err(e) => {
+                self.note = Some(format!("⚠ Invalid group number: {e}"));
+                return;
+            }
+        };
+        if group.as_index() >= self.groups.len() {
+            self.note = Some(format!("⚠ There is no group {group}"));
+        } else if self.locked.remove(&group) {
+            self.note = Some(format!("Group {group} unlocked"));
+        } else {
+            self.locked.insert(group);
+            self.note = Some(format!("Group {group} locked"));
+        }
+    }
+
+    fn reply(&self, inputs: &GroupingInputs, ids: &ButtonIds) -> anyhow::Result<CreateReply> {
+        use std::fmt::Write as _;
+        let violations = inputs.rules().exclusion_violations(&self.groups);
+        // Violations go first so they are seen even when not every group fits
+        let mut description = String::new();
+        for (a, b) in violations.iter() {
+            writeln!(
+                description,
+                "⚠ {} and {} must not be grouped together",
+                a.name, b.name
+            )?;
+        }
+        let (listing, shown) = take_lines(
+            &display_draft(&self.groups, &self.locked)?,
+            MAX_DESCRIPTION_LEN.saturating_sub(description.len() + MAX_SUMMARY_LEN),
+        );
+        description.push_str(&listing);
+        if shown < self.groups.len() {
+            write!(
+                description,
+                "...\n{} groups, first {shown} shown",
+                self.groups.len()
+            )?;
+        }
+        let embed = CreateEmbed::new()
+            .title(format!("Proposed Groups ({})", inputs.group_size))
+            .description(truncate_text(&description, MAX_DESCRIPTION_LEN));
+        let buttons = CreateActionRow::Buttons(vec![
+            CreateButton::new(&ids.reshuffle)
+                .label("Reshuffle")
+                .style(ButtonStyle::Secondary),
+            CreateButton::new(&ids.swap)
+                .label("Swap")
+                .style(ButtonStyle::Secondary),
+            CreateButton::new(&ids.lock)
+                .label("Lock / Unlock")
+                .style(ButtonStyle::Secondary),
+            CreateButton::new(&ids.publish)
+                .label("Publish")
+                .style(ButtonStyle::Success)
+                .disabled(!violations.is_empty()),
+            CreateButton::new(&ids.cancel)
+                .label("Cancel")
+                .style(ButtonStyle::Danger),
+        ]);
+        Ok(CreateReply::default()
+            .content(self.note.clone().unwrap_or_default())
+            .embed(embed)
+            .components(vec![buttons]))
+    }
+}
+
+impl ButtonIds {
+    fn new(ctx_id: u64) -> Self {
+        let prefix = format!("{ctx_id}-preview-");
+        Self {
+            reshuffle: format!("{prefix}reshuffle"),
+            swap: format!("{prefix}swap"),
+            lock: format!("{prefix}lock"),
+            publish: format!("{prefix}publish"),
+            cancel: format!("{prefix}cancel"),
+            prefix,
+        }
+    }
+}
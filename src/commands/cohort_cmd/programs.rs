@@ -0,0 +1,154 @@
+//! Groups the commands for running several cohort programs at the same time
+
+use std::fmt::Write as _;
+
+use poise::serenity_prelude::{GuildChannel, Mentionable as _};
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::{
+        audit::do_audit, autocomplete_program, call_to_parent_command, can_manage_cohort,
+        command_guild_id, confirm::confirm, tracing_handler_start,
+    },
+    model::{
+        audit::AuditAction,
+        cohort::{Cohort, program::ProgramName},
+        programs::Programs,
+    },
+};
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort",
+    subcommand_required,
+    subcommands("add", "remove", "list")
+)]
+#[instrument(name = "cohort-program", skip(ctx))]
+/// Commands to manage the cohort programs that run at the same time
+pub async fn program(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-program-add", skip(ctx))]
+/// Start a new program that uses its own channel, schedule, settings and history
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name used to refer to the program in commands"] name: String,
+    #[description = "Channel for the program's announcements and group threads"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let name: ProgramName = match name.parse() {
+        Ok(name) => name,
+        Err(e) => {
+            ctx.reply(format!("**{e}**")).await?;
+            return Ok(());
+        }
+    };
+    // Loads any data saved from when a program with the same name existed before
+    let defaults = data
+        .guild_config_required(channel.guild_id)?
+        .cohort_defaults;
+    let key = data.program_key(channel.guild_id, name.clone());
+    let cohort = Cohort::new(data.inner.shared_config, key, channel.id, &defaults).await;
+    let msg = match data.programs_add(cohort) {
+        Ok(_) => format!(
+            "Program **{name}** added using {}. Use `program:{name}` in commands to manage it",
+            channel.id.mention()
+        ),
+        Err(e) => format!("**{e}**"),
+    };
+    ctx.reply(msg).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-program-remove", skip(ctx))]
+/// Stop a program and cancel its scheduled tasks (its data is kept if it is added again)
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Program to remove"]
+    #[autocomplete = "autocomplete_program"]
+    name: String,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let Some(cohort) = data.programs_get(guild_id, &name)? else {
+        ctx.reply(format!("**No program named {name:?} found**"))
+            .await?;
+        return Ok(());
+    };
+    if cohort.program().is_default() {
+        ctx.reply("**The default program cannot be removed**")
+            .await?;
+        return Ok(());
+    }
+    let mut changes = format!(
+        "- Program **{}** is stopped (its data is kept if it is added again)",
+        cohort.program()
+    );
+    for task in data.schedule_tasks_by_program(guild_id, cohort.program())? {
+        write!(changes, "\n- {task} is cancelled and will not run")?;
+    }
+    if !confirm(
+        ctx,
+        &format!("Remove program {}", cohort.program()),
+        &changes,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    let cohort = match data.programs_remove(guild_id, cohort.program().as_str()) {
+        Ok(cohort) => cohort,
+        Err(e) => {
+            ctx.reply(format!("**{e}**")).await?;
+            return Ok(());
+        }
+    };
+    let cancelled = data.schedule_cancel_tasks_by_program(cohort.guild_id(), cohort.program())?;
+    info!(cancelled = cancelled.len());
+    let msg = format!(
+        "Program **{}** removed{}",
+        cohort.program(),
+        if cancelled.is_empty() {
+            String::new()
+        } else {
+            format!(" and {} scheduled tasks cancelled", cancelled.len())
+        }
+    );
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, Some(&cohort), AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command, aliases("disp"))]
+#[instrument(name = "cohort-program-list", skip(ctx))]
+/// Show the programs that are running [aliases("disp")]
+pub async fn list(ctx: Context<'_>) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let msg = ctx.data().programs_as_string(command_guild_id(&ctx))?;
+    ctx.reply(format!("**{}**\n{msg}", Programs::DISPLAY_TITLE))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Grants and removes the roles for members taking part in cohorts
+//!
+//! Failing to change a role is logged but does not stop the rest of the command as the roles are only a convenience
+
+use poise::serenity_prelude::{CacheHttp, GuildId, RoleId};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Data,
+    commands::cohort_cmd::effects::Effects,
+    model::{
+        cohort::{Cohort, stats::UserStats},
+        guild::GuildConfig,
+        user_serde::UserIdNumber,
+    },
+};
+
+/// Gives the member the cohort role if one is configured
+#[instrument(skip(cache_http, data, cohort))]
+pub async fn do_grant_cohort_role(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user_id_number: UserIdNumber,
+) -> anyhow::Result<()> {
+    let guild_id = cohort.guild_id();
+    let Some(role_id) = data.guild_config(guild_id)?.and_then(|x| x.role_cohort) else {
+        return Ok(());
+    };
+    add_role(
+        &Effects::Live(&cache_http),
+        guild_id,
+        user_id_number,
+        role_id,
+        "Taking part in cohort",
+    )
+    .await;
+    Ok(())
+}
+
+/// Takes the cohort role away from the member if one is configured
+#[instrument(skip(cache_http, data, cohort))]
+pub async fn do_remove_cohort_role(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+    user_id_number: UserIdNumber,
+) -> anyhow::Result<()> {
+    let guild_id = cohort.guild_id();
+    let Some(role_id) = data.guild_config(guild_id)?.and_then(|x| x.role_cohort) else {
+        return Ok(());
+    };
+    remove_role(
+        &Effects::Live(&cache_http),
+        guild_id,
+        user_id_number,
+        role_id,
+    )
+    .await;
+    Ok(())
+}
+
+/// Removes the cohort role from the members and gives the veteran role to those that have completed enough cohorts
+///
+/// Expects the cohort to have been archived already so it counts towards the veteran role
+#[instrument(skip(effects, config, cohort, members))]
+pub async fn do_end_cohort_roles(
+    effects: &Effects<'_>,
+    config: Option<&GuildConfig>,
+    cohort: &Cohort,
+    members: &[UserIdNumber],
+) -> anyhow::Result<()> {
+    info!("START");
+    let guild_id = cohort.guild_id();
+    let Some(config) = config else {
+        info!("END server not set up");
+        return Ok(());
+    };
+    if config.role_cohort.is_none() && config.role_veteran.is_none() {
+        info!("END no roles configured");
+        return Ok(());
+    }
+    let archive = cohort.archive()?;
+    for &member in members {
+        if let Some(role_id) = config.role_cohort {
+            remove_role(effects, guild_id, member, role_id).await;
+        }
+        if let Some(role_id) = config.role_veteran
+            && UserStats::new(&archive, member).cohorts_joined >= config.veteran_cohorts
+        {
+            add_role(
+                effects,
+                guild_id,
+                member,
+                role_id,
+                "Completed enough cohorts to be a veteran",
+            )
+            .await;
+        }
+    }
+    info!("END");
+    Ok(())
+}
+
+async fn add_role(
+    effects: &Effects<'_>,
+    guild_id: GuildId,
+    user_id_number: UserIdNumber,
+    role_id: RoleId,
+    reason: &str,
+) {
+    if let Err(e) = effects
+        .add_role(guild_id, user_id_number, role_id, reason)
+        .await
+    {
+        warn!("failed to add role {role_id} to User# {user_id_number} with error: {e:?}");
+    }
+}
+
+async fn remove_role(
+    effects: &Effects<'_>,
+    guild_id: GuildId,
+    user_id_number: UserIdNumber,
+    role_id: RoleId,
+) {
+    if let Err(e) = effects
+        .remove_role(
+            guild_id,
+            user_id_number,
+            role_id,
+            "No longer taking part in cohort",
+        )
+        .await
+    {
+        warn!("failed to remove role {role_id} from User# {user_id_number} with error: {e:?}");
+    }
+}
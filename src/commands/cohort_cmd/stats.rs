@@ -0,0 +1,66 @@
+//! Groups the commands that show participation statistics
+
+use poise::serenity_prelude::User;
+use tracing::instrument;
+
+use crate::{
+    Context,
+    commands::{autocomplete_program, can_manage_cohort, resolve_program, tracing_handler_start},
+    model::{
+        cohort::stats::{UserStats, participation_trend},
+        user_serde::UserIdNumber,
+    },
+};
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-stats", skip(ctx))]
+/// Show how often you (or another member) have taken part in cohorts
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "Member to show stats for (defaults to you)"] user: Option<User>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let user_id_number: UserIdNumber = user.id.into();
+    let archive = resolve_program(&ctx, program).await?.archive()?;
+    let stats = UserStats::new(&archive, user_id_number);
+    ctx.reply(format!("**Cohort Stats for {}**\n{stats}", user.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-participation", skip(ctx))]
+/// Show how participation has changed over the archived cohorts
+pub async fn participation(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    /// Limit on how many cohorts are listed so the message stays within Discord's limits
+    const MAX_LISTED: usize = 24;
+    tracing_handler_start(&ctx).await;
+    let archive = resolve_program(&ctx, program).await?.archive()?;
+    let trend = participation_trend(&archive);
+    if trend.is_empty() {
+        ctx.reply("No cohorts have been archived yet").await?;
+        return Ok(());
+    }
+    let mut msg = String::from("**Participation Trend**\n");
+    for cohort in trend.iter().skip(trend.len().saturating_sub(MAX_LISTED)) {
+        writeln!(msg, "{cohort}")?;
+    }
+    ctx.reply(msg).await?;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Creates a thread for each group to talk in once groups are published
+
+use poise::serenity_prelude::{
+    CacheHttp, ChannelId, ChannelType, CreateThread, GuildChannel, Mentionable as _,
+};
+use rand::seq::IndexedRandom as _;
+use tracing::{error, info, instrument, warn};
+
+use crate::model::cohort::{Cohort, grouping::Group};
+
+const ICEBREAKERS: [&str; 6] = [
+    "What is one thing you hope to have finished by the end of this cohort?",
+    "What does a productive day look like for you?",
+    "What usually gets in the way when you are working towards a goal?",
+    "What is something you learned recently that surprised you?",
+    "How would you like your group to keep you accountable?",
+    "What time of day do you usually get your best work done?",
+];
+
+/// Creates a thread for each group, adds the members and posts an icebreaker with their goals
+///
+/// Failing to create the thread for one group does not stop the others from being created
+#[instrument(skip(cache_http, cohort, groups), fields(program = %cohort.program()))]
+pub async fn do_create_group_threads(
+    cache_http: impl CacheHttp,
+    channel_id: ChannelId,
+    cohort: &Cohort,
+    groups: &[Group],
+) -> anyhow::Result<()> {
+    info!("START");
+    for (i, group) in groups.iter().enumerate() {
+        match create_group_thread(&cache_http, channel_id, cohort, i, group).await {
+            Ok(thread) => cohort.record_thread_set(group, thread.id)?,
+            Err(e) => error!(
+                "failed to create thread for group {} with error: {e:?}",
+                i + 1
+            ),
+        }
+    }
+    info!("END");
+    Ok(())
+}
+
+#[instrument(skip(cache_http, cohort, group))]
+async fn create_group_thread(
+    cache_http: impl CacheHttp,
+    channel_id: ChannelId,
+    cohort: &Cohort,
+    group_index: usize,
+    group: &Group,
+) -> anyhow::Result<GuildChannel> {
+    let thread = create_thread(&cache_http, channel_id, thread_name(group_index, group)).await?;
+    for member in group.members.iter() {
+        if let Err(e) = thread
+            .id
+            .add_thread_member(cache_http.http(), member.id_number.to_user_id())
+            .await
+        {
+            warn!("failed to add {member:?} to thread with error: {e:?}");
+        }
+    }
+    let msg = thread_welcome_message(cohort, group)?;
+    thread.id.say(&cache_http, msg).await?;
+    Ok(thread)
+}
+
+/// Tries to create a private thread and falls back to a public thread if that is not possible
+async fn create_thread(
+    cache_http: impl CacheHttp,
+    channel_id: ChannelId,
+    name: String,
+) -> anyhow::Result<GuildChannel> {
+    let builder = CreateThread::new(name.clone())
+        .kind(ChannelType::PrivateThread)
+        .invitable(false);
+    match channel_id.create_thread(&cache_http, builder).await {
+        Ok(thread) => Ok(thread),
+        Err(e) => {
+            warn!("failed to create private thread, falling back to public thread. Error: {e:?}");
+            let builder = CreateThread::new(name).kind(ChannelType::PublicThread);
+            Ok(channel_id.create_thread(&cache_http, builder).await?)
+        }
+    }
+}
+
+fn thread_name(group_index: usize, group: &Group) -> String {
+    /// Discord's limit for channel names
+    const MAX_LEN: usize = 100;
+    let names: Vec<String> = group.members.iter().map(|x| x.name.to_string()).collect();
+    format!("Group {} - {}", group_index + 1, names.join(" & "))
+        .chars()
+        .take(MAX_LEN)
+        .collect()
+}
+
+fn thread_welcome_message(cohort: &Cohort, group: &Group) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+    let mut result = format!("Welcome {group}! This thread is for your group.\n\n**Goals**\n");
+    for member in group.members.iter() {
+        let record = cohort.scores_record(member.id_number)?;
+        let goal = record
+            .as_ref()
+            .and_then(|x| x.goal())
+            .unwrap_or("No goal set yet");
+        writeln!(
+            result,
+            "- {}: {goal}",
+            member.id_number.to_user_id().mention()
+        )?;
+    }
+    let icebreaker = ICEBREAKERS
+        .choose(&mut rand::rng())
+        .expect("icebreakers should not be empty");
+    write!(result, "\n**Icebreaker**\n{icebreaker}")?;
+    Ok(result)
+}
@@ -0,0 +1,173 @@
+//! Groups the functionality for limiting the number of participants and the waitlist for extra sign ups
+
+use poise::serenity_prelude::{CacheHttp, CreateMessage, Mentionable as _};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    Context, Data,
+    commands::{
+        autocomplete_program, can_manage_cohort,
+        cohort_cmd::{
+            membership::do_late_join,
+            notifications::{do_notify_admins, unreachable_list},
+            roles::do_grant_cohort_role,
+        },
+        resolve_program, tracing_handler_start,
+    },
+    model::{
+        cohort::Cohort,
+        user_serde::{UserRecord, UserRecordSupport as _},
+    },
+};
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    track_edits,
+    guild_only = true,
+    check = "can_manage_cohort"
+)]
+#[instrument(name = "cohort-max_participants", skip(ctx))]
+/// Sets the most members that can register before others go on the waitlist (no args to see current)
+pub async fn max_participants(
+    ctx: Context<'_>,
+    #[description = "Maximum number of participants (0 removes the limit)"] max: Option<u16>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let data = ctx.data();
+    let Some(max) = max else {
+        let msg = match cohort.settings()?.max_participants {
+            Some(max) => format!("At most {max} members can take part"),
+            None => "There is no limit on the number of members that can take part".to_string(),
+        };
+        ctx.reply(msg).await?;
+        return Ok(());
+    };
+    let max = (max > 0).then_some(max);
+    cohort.settings_max_participants_set(ctx.author_id_number(), max)?;
+    let promoted = do_promote_from_waitlist(ctx, data, &cohort).await?;
+    ctx.reply(format!(
+        "{}{}",
+        match max {
+            Some(max) => format!("At most {max} members can now take part"),
+            None => "Limit on members removed".to_string(),
+        },
+        if promoted > 0 {
+            format!(" and {promoted} members were moved off the waitlist")
+        } else {
+            String::new()
+        }
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, slash_command)]
+#[instrument(name = "cohort-status", skip(ctx))]
+/// Show if you are registered, on the waitlist or in a group for the current cohort
+pub async fn status(
+    ctx: Context<'_>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    use std::fmt::Write as _;
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let user_id_number = ctx.author_id_number();
+    let participant_count = cohort.scores_registered_users()?.len();
+    let waitlist = cohort.scores_waitlist()?;
+    let record = cohort.record()?;
+    let mut msg = String::from("**Cohort Status**\n");
+    writeln!(
+        msg,
+        "Participants: {participant_count}{}",
+        match cohort.settings()?.max_participants {
+            Some(max) => format!(" of {max}"),
+            None => String::new(),
+        }
+    )?;
+    writeln!(msg, "Waitlist: {}", waitlist.len())?;
+    writeln!(
+        msg,
+        "Groups published: {}",
+        if record.is_in_progress() { "Yes" } else { "No" }
+    )?;
+    let position = waitlist.iter().position(|x| x.id_number == user_id_number);
+    let your_status = if let Some(published) = record.group_of(user_id_number) {
+        format!("In a group with {}", published.group)
+    } else if let Some(i) = position {
+        format!("Number {} on the waitlist", i + 1)
+    } else if cohort.scores_record(user_id_number)?.is_some() {
+        "Registered".to_string()
+    } else {
+        "Not registered".to_string()
+    };
+    write!(msg, "You: {your_status}")?;
+    ctx.reply(msg).await?;
+    Ok(())
+}
+
+/// Fills any free places from the waitlist and lets the promoted members know
+///
+/// Members promoted after the groups are published are placed in a group as late joiners
+///
+/// Returns the number of members promoted
+#[instrument(skip(cache_http, data, cohort), fields(program = %cohort.program()))]
+pub async fn do_promote_from_waitlist(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    cohort: &Cohort,
+) -> anyhow::Result<usize> {
+    use std::fmt::Write as _;
+    info!("START");
+    let promoted = cohort.scores_promote_from_waitlist(cohort.settings()?.max_participants())?;
+    let mut unreachable: Vec<UserRecord> = vec![];
+    for user in promoted.iter() {
+        do_grant_cohort_role(&cache_http, data, cohort, user.id_number).await?;
+        let mut msg = "A place opened up and you have been moved off the waitlist. ".to_string();
+        match do_late_join(&cache_http, data, cohort, user.clone()).await? {
+            Some(late_join_msg) => msg.push_str(&late_join_msg),
+            None => msg.push_str("You are now registered for the next cohort!"),
+        }
+        let is_opted_out = cohort
+            .scores_record(user.id_number)?
+            .is_some_and(|x| x.dm_opt_out());
+        if is_opted_out {
+            info!("{user:?} opted out of DMs");
+            unreachable.push(user.clone());
+            continue;
+        }
+        if let Err(e) = user
+            .id_number
+            .to_user_id()
+            .direct_message(&cache_http, CreateMessage::new().content(msg))
+            .await
+        {
+            warn!("failed to DM {user:?} about promotion with error: {e:?}");
+            unreachable.push(user.clone());
+        }
+    }
+    if !promoted.is_empty() {
+        let mentions: Vec<String> = promoted
+            .iter()
+            .map(|x| x.id_number.to_user_id().mention().to_string())
+            .collect();
+        let mut msg = format!("Moved off the waitlist: {}", mentions.join(", "));
+        if !unreachable.is_empty() {
+            write!(
+                msg,
+                "\nUnable to tell these members by DM (DMs turned off or closed): {}",
+                unreachable_list(&unreachable)
+            )?;
+        }
+        do_notify_admins(&cache_http, data, cohort.guild_id(), msg).await?;
+    }
+    info!("END");
+    Ok(promoted.len())
+}
@@ -0,0 +1,379 @@
+//! Groups the commands to change the server's configuration while the bot is running
+//!
+//! Values set here are saved and take precedence over the environment (home server) or `/setup`
+
+use poise::{
+    ChoiceParameter as _, CreateReply,
+    serenity_prelude::{CreateEmbed, GuildChannel, Mentionable as _, Role},
+};
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::{
+        audit::do_audit, call_to_parent_command, can_configure_bot, command_guild_id, is_admin,
+        tracing_handler_start,
+    },
+    model::{audit::AuditAction, cohort::grouping::GroupSize, utc_offset::UtcOffset},
+};
+
+/// The settings that can be changed with `/config` and returned to their default with `/config reset`
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ConfigSetting {
+    #[name = "Cohort channel"]
+    CohortChannel,
+    #[name = "Admin roles"]
+    AdminRoles,
+    #[name = "Audit channel"]
+    AuditChannel,
+    #[name = "Startup channel"]
+    StartupChannel,
+    #[name = "Timezone"]
+    Timezone,
+    #[name = "Cohort defaults"]
+    CohortDefaults,
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot",
+    subcommand_required,
+    subcommands(
+        "show",
+        "channel",
+        "admin_role",
+        "audit_channel",
+        "startup_channel",
+        "timezone",
+        "cohort_defaults",
+        "reset"
+    )
+)]
+#[instrument(name = "config", skip(ctx))]
+/// Commands to view and change the server's configuration
+pub async fn config(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot",
+    aliases("disp")
+)]
+#[instrument(name = "config-show", skip(ctx))]
+/// Show the configuration in effect for this server [aliases("disp")]
+pub async fn show(ctx: Context<'_>) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let msg = ctx.data().guild_config_as_string(command_guild_id(&ctx))?;
+    let embed = CreateEmbed::new()
+        .title("Server Configuration")
+        .description(msg);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot"
+)]
+#[instrument(name = "config-channel", skip(ctx))]
+/// Set the channel used by the default program for announcements and group threads
+pub async fn channel(
+    ctx: Context<'_>,
+    #[description = "Channel for the default program"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let default_program = data.programs_default(guild_id)?;
+    if let Some(existing) = data.programs_find_by_channel(channel.id)?
+        && !existing.program().is_default()
+    {
+        ctx.reply(format!(
+            "**The channel is already used by the {:?} program**",
+            existing.program().as_str()
+        ))
+        .await?;
+        return Ok(());
+    }
+    data.guild_configs_set_overrides(guild_id, |x| x.channel_cohort = Some(channel.id))?;
+    default_program.set_channel_id(channel.id);
+    info!("cohort channel changed to {}", channel.id);
+    let msg = format!("Cohort channel set to {}", channel.id.mention());
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot",
+    subcommand_required,
+    subcommands("admin_role_add", "admin_role_remove")
+)]
+#[instrument(name = "config-admin_role", skip(ctx))]
+/// Change which roles can run the admin commands
+pub async fn admin_role(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "is_admin",
+    rename = "add"
+)]
+#[instrument(name = "config-admin_role-add", skip(ctx))]
+/// Allow members with the role to run the admin commands
+pub async fn admin_role_add(
+    ctx: Context<'_>,
+    #[description = "Role to allow"] role: Role,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let mut role_ids = data.guild_config_required(guild_id)?.auth_role_ids;
+    if role_ids.contains(&role.id) {
+        ctx.reply(format!("{} is already an admin role", role.id.mention()))
+            .await?;
+        return Ok(());
+    }
+    role_ids.push(role.id);
+    data.guild_configs_set_overrides(guild_id, |x| x.auth_role_ids = Some(role_ids))?;
+    let msg = format!("{} can now run the admin commands", role.id.mention());
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "is_admin",
+    rename = "remove"
+)]
+#[instrument(name = "config-admin_role-remove", skip(ctx))]
+/// Stop members with the role from running the admin commands
+pub async fn admin_role_remove(
+    ctx: Context<'_>,
+    #[description = "Role to remove"] role: Role,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let mut role_ids = data.guild_config_required(guild_id)?.auth_role_ids;
+    let Some(index) = role_ids.iter().position(|x| x == &role.id) else {
+        ctx.reply(format!("{} is not an admin role", role.id.mention()))
+            .await?;
+        return Ok(());
+    };
+    if role_ids.len() == 1 {
+        ctx.reply("**Unable to remove the last admin role, add another one first**")
+            .await?;
+        return Ok(());
+    }
+    role_ids.remove(index);
+    data.guild_configs_set_overrides(guild_id, |x| x.auth_role_ids = Some(role_ids))?;
+    let msg = format!("{} can no longer run the admin commands", role.id.mention());
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot"
+)]
+#[instrument(name = "config-startup_channel", skip(ctx))]
+/// Set the channel where the bot announces that it has started
+pub async fn startup_channel(
+    ctx: Context<'_>,
+    #[description = "Channel for startup announcements"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    ctx.data()
+        .guild_configs_set_overrides(command_guild_id(&ctx), |x| {
+            x.channel_startup = Some(channel.id)
+        })?;
+    let msg = format!("Startup channel set to {}", channel.id.mention());
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot"
+)]
+#[instrument(name = "config-audit_channel", skip(ctx))]
+/// Set the channel where privileged actions are posted as they are recorded in the audit log
+pub async fn audit_channel(
+    ctx: Context<'_>,
+    #[description = "Channel for the audit log"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    ctx.data()
+        .guild_configs_set_overrides(command_guild_id(&ctx), |x| {
+            x.channel_audit = Some(channel.id)
+        })?;
+    let msg = format!("Audit channel set to {}", channel.id.mention());
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot"
+)]
+#[instrument(name = "config-timezone", skip(ctx))]
+/// Set the timezone used to show times for this server (e.g. UTC-4 or UTC+05:30)
+pub async fn timezone(
+    ctx: Context<'_>,
+    #[description = "Offset from UTC (e.g. UTC-4 or UTC+05:30)"] timezone: String,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let timezone: UtcOffset = match timezone.parse() {
+        Ok(timezone) => timezone,
+        Err(e) => {
+            ctx.reply(format!("**{e}**")).await?;
+            return Ok(());
+        }
+    };
+    ctx.data()
+        .guild_configs_set_overrides(command_guild_id(&ctx), |x| x.timezone = Some(timezone))?;
+    let msg = format!("Timezone set to {timezone}");
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot"
+)]
+#[instrument(name = "config-cohort_defaults", skip(ctx))]
+/// Set the settings new programs start with (existing programs keep their settings)
+pub async fn cohort_defaults(
+    ctx: Context<'_>,
+    #[description = "Number of members in each group"] group_size: Option<u8>,
+    #[description = "Check-ins a member can miss in a row before they are considered inactive"]
+    missed_check_ins: Option<u8>,
+    #[description = "Maximum number of participants (0 removes the limit)"]
+    max_participants: Option<u16>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let group_size = match group_size.map(GroupSize::new).transpose() {
+        Ok(group_size) => group_size,
+        Err(e) => {
+            ctx.reply(format!("**{e}**")).await?;
+            return Ok(());
+        }
+    };
+    let config = ctx
+        .data()
+        .guild_configs_set_overrides(command_guild_id(&ctx), |x| {
+            if group_size.is_some() {
+                x.group_size = group_size;
+            }
+            if missed_check_ins.is_some() {
+                x.missed_check_ins_limit = missed_check_ins;
+            }
+            if max_participants.is_some() {
+                x.max_participants = max_participants;
+            }
+        })?;
+    ctx.reply(format!(
+        "**Cohort Defaults**\n{}\nOnly programs created from now on use these settings",
+        config.cohort_defaults
+    ))
+    .await?;
+    do_audit(
+        &ctx,
+        None,
+        AuditAction::ConfigChanged(format!(
+            "Cohort defaults set to group size {}, inactive after {} missed check-ins and {} maximum participants",
+            config.cohort_defaults.group_size.get(),
+            config.cohort_defaults.missed_check_ins_limit,
+            config
+                .cohort_defaults
+                .max_participants
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "no".to_string())
+        )),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot"
+)]
+#[instrument(name = "config-reset", skip(ctx))]
+/// Go back to the value from the environment or `/setup` for a setting
+pub async fn reset(
+    ctx: Context<'_>,
+    #[description = "Setting to reset"] setting: ConfigSetting,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let config = data.guild_configs_set_overrides(guild_id, |x| match setting {
+        ConfigSetting::CohortChannel => x.channel_cohort = None,
+        ConfigSetting::AdminRoles => x.auth_role_ids = None,
+        ConfigSetting::AuditChannel => x.channel_audit = None,
+        ConfigSetting::StartupChannel => x.channel_startup = None,
+        ConfigSetting::Timezone => x.timezone = None,
+        ConfigSetting::CohortDefaults => {
+            x.group_size = None;
+            x.missed_check_ins_limit = None;
+            x.max_participants = None;
+        }
+    })?;
+    if matches!(setting, ConfigSetting::CohortChannel) {
+        data.programs_default(guild_id)?
+            .set_channel_id(config.channel_cohort);
+    }
+    let msg = format!("{} reset", setting.name());
+    ctx.reply(&msg).await?;
+    do_audit(&ctx, None, AuditAction::ConfigChanged(msg)).await?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Asks the author to confirm before a destructive command makes any changes
+
+use std::time::Duration;
+
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        ButtonStyle, ComponentInteractionCollector, CreateActionRow, CreateButton, CreateEmbed,
+        CreateEmbedFooter, CreateInteractionResponse,
+    },
+};
+use tracing::{info, instrument};
+
+use crate::{Context, commands::truncate_text};
+
+/// How long the author has to decide before the command is cancelled
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Discord's limit on the length of an embed description
+const MAX_CHANGES_LEN: usize = 4096;
+
+/// Shows exactly what will change with confirm and cancel buttons and waits for the author to choose
+///
+/// Callers should keep `changes` short, anything past Discord's limit is cut off.
+/// Returns true only if the author confirmed. The buttons are then removed and the outcome is shown
+/// with the prompt so nothing more needs to be sent if the command is cancelled or times out
+#[instrument(skip(ctx, changes))]
+pub async fn confirm(ctx: Context<'_>, action: &str, changes: &str) -> anyhow::Result<bool> {
+    let confirm_id = format!("{}-confirm-yes", ctx.id());
+    let cancel_id = format!("{}-confirm-no", ctx.id());
+    let embed = CreateEmbed::new()
+        .title(format!("Confirm: {action}"))
+        .description(truncate_text(changes, MAX_CHANGES_LEN))
+        .footer(CreateEmbedFooter::new(format!(
+            "Nothing changes unless confirmed within {} seconds",
+            CONFIRM_TIMEOUT.as_secs()
+        )));
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(&confirm_id)
+            .label("Confirm")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(&cancel_id)
+            .label("Cancel")
+            .style(ButtonStyle::Secondary),
+    ]);
+    let reply_handle = ctx
+        .send(
+            CreateReply::default()
+                .embed(embed.clone())
+                .components(vec![buttons]),
+        )
+        .await?;
+
+    let author_id = ctx.author().id;
+    let press = ComponentInteractionCollector::new(ctx)
+        .filter({
+            let confirm_id = confirm_id.clone();
+            let cancel_id = cancel_id.clone();
+            // Only the author gets to decide, other members' presses are ignored
+            move |press| {
+                press.user.id == author_id
+                    && (press.data.custom_id == confirm_id || press.data.custom_id == cancel_id)
+            }
+        })
+        .timeout(CONFIRM_TIMEOUT)
+        .await;
+
+    let (is_confirmed, outcome) = match press {
+        Some(press) => {
+            press
+                .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            if press.data.custom_id == confirm_id {
+                (true, format!("Confirmed: {action}"))
+            } else {
+                (false, "Cancelled, nothing was changed".to_string())
+            }
+        }
+        None => (false, "Timed out, nothing was changed".to_string()),
+    };
+    info!(is_confirmed, outcome);
+    reply_handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content(outcome)
+                .embed(embed)
+                .components(vec![]),
+        )
+        .await?;
+    Ok(is_confirmed)
+}
@@ -0,0 +1,191 @@
+//! Handles the errors returned by commands and event handlers
+//!
+//! [`UserError`]s are shown to the member as is. Anything else is an internal error, the member is
+//! given an ID to share and the details are logged and posted to the server's admin channel
+
+use poise::{
+    CreateReply, FrameworkError,
+    serenity_prelude::{
+        self as serenity, CacheHttp, CreateInteractionResponse, CreateInteractionResponseMessage,
+        FullEvent, GuildId, Interaction, Mentionable as _,
+    },
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    Context, Data,
+    commands::{command_guild_id, truncate_text},
+    errors::UserError,
+};
+
+/// Keeps the admin alert within Discord's message length limit (the rest of the alert is short)
+const MAX_ALERT_ERROR_LEN: usize = 1200;
+
+/// The invocation of a prefix command can be as long as a message so the context is shortened too
+const MAX_ALERT_CONTEXT_LEN: usize = 500;
+
+/// Discord's limit on the length of a message
+const MAX_ALERT_LEN: usize = 2000;
+
+pub async fn on_error(error: FrameworkError<'_, Data, anyhow::Error>) {
+    match error {
+        FrameworkError::Command { error, ctx, .. } => handle_command_error(ctx, error).await,
+        FrameworkError::ArgumentParse {
+            error, input, ctx, ..
+        } => {
+            let msg = format!(
+                "**{error}**{}\nSee `/help {}` for how to use this command",
+                input.map(|x| format!(" (got {x:?})")).unwrap_or_default(),
+                ctx.command().qualified_name
+            );
+            reply(ctx, msg).await;
+        }
+        FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => handle_command_error(ctx, error).await,
+        FrameworkError::CommandPanic { payload, ctx, .. } => {
+            let error = anyhow::anyhow!(
+                "command panicked: {}",
+                payload.as_deref().unwrap_or("no details")
+            );
+            handle_command_error(ctx, error).await
+        }
+        FrameworkError::EventHandler {
+            error,
+            ctx,
+            event,
+            framework,
+            ..
+        } => handle_event_error(ctx, event, framework.user_data, error).await,
+        other => {
+            if let Err(e) = poise::builtins::on_error(other).await {
+                error!("failed to handle framework error with error: {e:?}");
+            }
+        }
+    }
+}
+
+async fn handle_command_error(ctx: Context<'_>, error: anyhow::Error) {
+    if let Some(user_error) = error.downcast_ref::<UserError>() {
+        info!(
+            "user error in {:?}: {user_error}",
+            ctx.command().qualified_name
+        );
+        reply(ctx, format!("**{user_error}**")).await;
+        return;
+    }
+    let id = new_correlation_id();
+    error!(
+        correlation_id = id,
+        "internal error in {:?}: {error:?}",
+        ctx.command().qualified_name
+    );
+    reply(
+        ctx,
+        format!(
+            "Something went wrong on our side. Please try again and if it keeps happening share this ID with an admin: `{id}`"
+        ),
+    )
+    .await;
+    let context = format!(
+        "Command: `{}` by {} in {}\nInvocation: `{}`",
+        ctx.command().qualified_name,
+        ctx.author().id.mention(),
+        ctx.channel_id().mention(),
+        ctx.invocation_string()
+    );
+    alert_admins(
+        ctx,
+        ctx.data(),
+        command_guild_id(&ctx),
+        &id,
+        &context,
+        &error,
+    )
+    .await;
+}
+
+async fn handle_event_error(
+    ctx: &serenity::Context,
+    event: &FullEvent,
+    data: &Data,
+    error: anyhow::Error,
+) {
+    let id = new_correlation_id();
+    error!(
+        correlation_id = id,
+        "internal error handling {:?}: {error:?}",
+        event.snake_case_name()
+    );
+    let mut guild_id = data.inner.home_guild_id;
+    let mut context = format!("Event: `{}`", event.snake_case_name());
+    if let FullEvent::InteractionCreate {
+        interaction: Interaction::Component(interaction),
+    } = event
+    {
+        guild_id = interaction.guild_id.unwrap_or(guild_id);
+        context = format!(
+            "Button `{}` pressed by {}",
+            interaction.data.custom_id,
+            interaction.user.id.mention()
+        );
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(format!(
+                    "Something went wrong on our side. Please try again and if it keeps happening share this ID with an admin: `{id}`"
+                ))
+                .ephemeral(true),
+        );
+        if let Err(e) = interaction.create_response(ctx, response).await {
+            // Expected if the handler already responded before failing
+            warn!("failed to tell the member about error {id} with error: {e:?}");
+        }
+    }
+    alert_admins(ctx, data, guild_id, &id, &context, &error).await;
+}
+
+/// Replies so only the author can see it, failures are only logged as there is nowhere else to report them
+async fn reply(ctx: Context<'_>, msg: String) {
+    let builder = CreateReply::default().content(msg).ephemeral(true);
+    if let Err(e) = ctx.send(builder).await {
+        error!("failed to send error reply with error: {e:?}");
+    }
+}
+
+/// Posts the details of an internal error to the server's admin channel if one is configured
+async fn alert_admins(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    guild_id: GuildId,
+    id: &str,
+    context: &str,
+    error: &anyhow::Error,
+) {
+    let channel_id = match data.guild_config(guild_id) {
+        Ok(config) => config.and_then(|x| x.channel_admin),
+        Err(e) => {
+            error!("failed to get guild config to alert admins with error: {e:?}");
+            None
+        }
+    };
+    let Some(channel_id) = channel_id else {
+        warn!("Not sending alert for internal error {id} because `channel_admin` not set");
+        return;
+    };
+    let context = truncate_text(context, MAX_ALERT_CONTEXT_LEN);
+    let details = truncate_text(&format!("{error:#}"), MAX_ALERT_ERROR_LEN);
+    let msg = truncate_text(
+        &format!("**Internal error** `{id}`\n{context}\n```\n{details}\n```"),
+        MAX_ALERT_LEN,
+    );
+    if let Err(e) = channel_id.say(cache_http, msg).await {
+        error!("failed to alert admins about internal error {id} with error: {e:?}");
+    }
+}
+
+/// Short random ID that links what the member is shown to the logs and the admin alert
+fn new_correlation_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
@@ -0,0 +1,71 @@
+//! Groups the commands for setting up the bot in a server
+
+use poise::serenity_prelude::{GuildChannel, Mentionable as _, Role};
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::tracing_handler_start,
+    model::{
+        cohort::{Cohort, program::ProgramName},
+        guild::GuildConfig,
+    },
+};
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+#[instrument(name = "setup", skip(ctx))]
+/// Set up the bot to run cohorts in this server
+pub async fn setup(
+    ctx: Context<'_>,
+    #[description = "Channel for the default program's announcements and group threads"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "Role allowed to run the admin commands"] auth_role: Role,
+    #[description = "Channel where admins are notified"]
+    #[channel_types("Text")]
+    admin_channel: Option<GuildChannel>,
+    #[description = "Role given to members taking part in a cohort"] cohort_role: Option<Role>,
+    #[description = "Role given to members that have completed enough cohorts"]
+    veteran_role: Option<Role>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let data = ctx.data();
+    let guild_id = channel.guild_id;
+    let config = GuildConfig {
+        guild_id,
+        auth_role_ids: vec![auth_role.id],
+        channel_cohort: channel.id,
+        channel_admin: admin_channel.map(|x| x.id),
+        channel_audit: None,
+        role_cohort: cohort_role.map(|x| x.id),
+        role_veteran: veteran_role.map(|x| x.id),
+        veteran_cohorts: data.inner.shared_config.veteran_cohorts,
+        channel_startup: None,
+        timezone: Default::default(),
+        cohort_defaults: Default::default(),
+    };
+    let defaults = config.cohort_defaults.clone();
+    if let Err(e) = data.guild_configs_add(config) {
+        ctx.reply(format!("**{e}**")).await?;
+        return Ok(());
+    }
+    // Loads any data saved from when the server was set up before
+    let key = data.program_key(guild_id, ProgramName::default());
+    let cohort = Cohort::new(data.inner.shared_config, key, channel.id, &defaults).await;
+    data.programs_add(cohort)?;
+    info!("server set up");
+    ctx.reply(format!(
+        "**Server set up.** Cohorts will run in {} and {} can run the admin commands. Use `/cohort program add` to run more programs at the same time",
+        channel.id.mention(),
+        auth_role.id.mention()
+    ))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Groups the commands to view and change who can run the privileged commands
+
+use poise::{
+    ChoiceParameter as _, CreateReply,
+    serenity_prelude::{CreateEmbed, Role, User},
+};
+use tracing::{info, instrument};
+
+use crate::{
+    Context,
+    commands::{
+        audit::do_audit, call_to_parent_command, can_configure_bot, command_guild_id, is_admin,
+        tracing_handler_start,
+    },
+    model::{
+        audit::AuditAction,
+        guild::permissions::{Capability, Grantee},
+    },
+};
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot",
+    subcommand_required,
+    subcommands("show", "grant", "revoke")
+)]
+#[instrument(name = "permissions", skip(ctx))]
+/// Commands to control which roles and members can run the privileged commands
+pub async fn permissions(ctx: Context<'_>) -> anyhow::Result<()> {
+    call_to_parent_command(ctx).await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_configure_bot",
+    aliases("disp")
+)]
+#[instrument(name = "permissions-show", skip(ctx))]
+/// Show who has been given each capability (admin roles have all of them) [aliases("disp")]
+pub async fn show(ctx: Context<'_>) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let permissions = ctx.data().guild_permissions(command_guild_id(&ctx))?;
+    let embed = CreateEmbed::new()
+        .title("Permissions")
+        .description(permissions.to_string());
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "is_admin"
+)]
+#[instrument(name = "permissions-grant", skip(ctx))]
+/// Allow a role or member to run the commands that need the capability (admin roles only)
+pub async fn grant(
+    ctx: Context<'_>,
+    #[description = "What to allow"] capability: Capability,
+    #[description = "Role to give the capability to"] role: Option<Role>,
+    #[description = "Member to give the capability to"] user: Option<User>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let Some(grantee) = to_grantee(&ctx, role, user).await? else {
+        return Ok(());
+    };
+    let msg = if ctx
+        .data()
+        .guild_permissions_grant(command_guild_id(&ctx), capability, grantee)?
+    {
+        info!("granted {capability:?} to {grantee:?}");
+        do_audit(
+            &ctx,
+            None,
+            AuditAction::PermissionGranted {
+                capability,
+                grantee,
+            },
+        )
+        .await?;
+        format!("{grantee} can now **{}**", capability.name())
+    } else {
+        format!("{grantee} already has **{}**", capability.name())
+    };
+    ctx.reply(msg).await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "is_admin"
+)]
+#[instrument(name = "permissions-revoke", skip(ctx))]
+/// Take a capability away from a role or member (admin roles only)
+pub async fn revoke(
+    ctx: Context<'_>,
+    #[description = "What to take away"] capability: Capability,
+    #[description = "Role to take the capability from"] role: Option<Role>,
+    #[description = "Member to take the capability from"] user: Option<User>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let Some(grantee) = to_grantee(&ctx, role, user).await? else {
+        return Ok(());
+    };
+    let msg = if ctx
+        .data()
+        .guild_permissions_revoke(command_guild_id(&ctx), capability, grantee)?
+    {
+        info!("revoked {capability:?} from {grantee:?}");
+        do_audit(
+            &ctx,
+            None,
+            AuditAction::PermissionRevoked {
+                capability,
+                grantee,
+            },
+        )
+        .await?;
+        format!("{grantee} can no longer **{}**", capability.name())
+    } else {
+        format!(
+            "{grantee} had not been given **{}** (admin roles always have it)",
+            capability.name()
+        )
+    };
+    ctx.reply(msg).await?;
+    Ok(())
+}
+
+/// Exactly one of the role or user must be given, replies with the problem and returns None otherwise
+async fn to_grantee(
+    ctx: &Context<'_>,
+    role: Option<Role>,
+    user: Option<User>,
+) -> anyhow::Result<Option<Grantee>> {
+    match (role, user) {
+        (Some(role), None) => Ok(Some(Grantee::Role(role.id))),
+        (None, Some(user)) => Ok(Some(Grantee::User(user.id))),
+        _ => {
+            ctx.reply("**Please give either a role or a member (not both)**")
+                .await?;
+            Ok(None)
+        }
+    }
+}
@@ -7,9 +7,16 @@ use tracing::{info, instrument};
 
 use crate::{
     Context,
-    commands::{call_to_parent_command, is_auth, tracing_handler_start},
-    model::schedule::{
-        Objective, OutcomeCreateScheduledTask, ScheduledTaskId, ScheduledTasks, UnixTimestamp,
+    commands::{
+        audit::do_audit, autocomplete_program, call_to_parent_command, can_manage_schedule,
+        command_guild_id, confirm::confirm, do_dry_run, reply_in_chunks, resolve_program,
+        tracing_handler_start,
+    },
+    model::{
+        audit::AuditAction,
+        schedule::{
+            Objective, OutcomeCreateScheduledTask, ScheduledTaskId, ScheduledTasks, UnixTimestamp,
+        },
     },
 };
 
@@ -18,7 +25,7 @@ use crate::{
     slash_command,
     track_edits,
     subcommand_required,
-    subcommands("set_unranked", "display", "cancel")
+    subcommands("set_unranked", "set_check_in", "display", "cancel", "dry_run")
 )]
 #[instrument(name = "schedule", skip(ctx))]
 /// Commands related to scheduling
@@ -32,7 +39,7 @@ pub async fn schedule(ctx: Context<'_>) -> anyhow::Result<()> {
     slash_command,
     track_edits,
     guild_only = true,
-    check = "is_auth"
+    check = "can_manage_schedule"
 )]
 #[instrument(name = "schedule-set_unranked", skip(ctx))]
 /// Sets when the next unranked is expected to start (use no args for more info)
@@ -40,19 +47,82 @@ pub async fn set_unranked(
     ctx: Context<'_>,
     #[description = "A unix timestamp. If you need more info just leave out argument for more info to be returned"]
     unix_timestamp: Option<i32>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    do_set_schedule(
+        ctx,
+        Objective::UnrankedStartEvent,
+        "Unranked Event Start",
+        unix_timestamp,
+        program,
+    )
+    .await
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    track_edits,
+    guild_only = true,
+    check = "can_manage_schedule"
+)]
+#[instrument(name = "schedule-set_check_in", skip(ctx))]
+/// Sets when the first weekly check-in happens, repeats weekly (use no args for more info)
+pub async fn set_check_in(
+    ctx: Context<'_>,
+    #[description = "A unix timestamp. If you need more info just leave out argument for more info to be returned"]
+    unix_timestamp: Option<i32>,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
 ) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
+    do_set_schedule(
+        ctx,
+        Objective::CohortCheckIn,
+        "Weekly Check-in",
+        unix_timestamp,
+        program,
+    )
+    .await
+}
+
+async fn do_set_schedule(
+    ctx: Context<'_>,
+    objective: Objective,
+    label: &str,
+    unix_timestamp: Option<i32>,
+    program: Option<String>,
+) -> anyhow::Result<()> {
     if let Some(unix_timestamp) = unix_timestamp {
         let timestamp = UnixTimestamp::new(unix_timestamp);
-        let outcome = ctx
-            .data()
-            .schedule_create_task(Objective::UnrankedStartEvent, timestamp)?;
-        let mut msg = format!("Unranked Event Start Scheduled for {timestamp}");
+        let cohort = resolve_program(&ctx, program).await?;
+        let program = cohort.program().clone();
+        let outcome = ctx.data().schedule_create_task(
+            objective,
+            cohort.guild_id(),
+            program.clone(),
+            timestamp,
+        )?;
+        let mut msg = format!("{label} ({program}) Scheduled for {timestamp}");
         if let OutcomeCreateScheduledTask::Replaced(prev) = outcome {
             use std::fmt::Write as _;
             write!(msg, "\nCancelled previous schedule for {prev}")?;
         }
         ctx.reply(msg).await?;
+        do_audit(
+            &ctx,
+            Some(&cohort),
+            AuditAction::ScheduleSet {
+                objective,
+                at: timestamp,
+            },
+        )
+        .await?;
     } else {
         info!("Info given, command not executed");
         ctx.reply(
@@ -71,7 +141,13 @@ Note: the command expects **ONLY** the number part",
 /// Shows the scheduled tasks [aliases("disp")]
 pub async fn display(ctx: Context<'_>) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
-    let tasks_as_string = ctx.data().schedule_as_string()?;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    let timezone = data
+        .guild_config(guild_id)?
+        .map(|x| x.timezone)
+        .unwrap_or_default();
+    let tasks_as_string = data.schedule_as_string(guild_id, timezone)?;
     let embed = CreateEmbed::new()
         .title(ScheduledTasks::DISPLAY_TITLE)
         .description(tasks_as_string);
@@ -86,7 +162,7 @@ pub async fn display(ctx: Context<'_>) -> anyhow::Result<()> {
     slash_command,
     track_edits,
     guild_only = true,
-    check = "is_auth"
+    check = "can_manage_schedule"
 )]
 #[instrument(name = "schedule-cancel", skip(ctx))]
 /// Cancel a scheduled event
@@ -96,11 +172,52 @@ pub async fn cancel(
 ) -> anyhow::Result<()> {
     tracing_handler_start(&ctx).await;
     let id: ScheduledTaskId = id.into();
-    let scheduled_task = ctx.data().schedule_cancel_task_by_id(id)?;
+    let data = ctx.data();
+    let guild_id = command_guild_id(&ctx);
+    // The IDs can shift while waiting for confirmation so the task is cancelled by its key instead
+    let (key, description) = data.schedule_task_by_id(guild_id, id)?;
+    let changes = format!("- {description} is cancelled and will not run");
+    if !confirm(ctx, &format!("Cancel scheduled task {id}"), &changes).await? {
+        return Ok(());
+    }
+    let scheduled_task = data.schedule_cancel_task_by_key(guild_id, &key)?;
     ctx.reply(format!(
         "{} cancelled for {}",
         scheduled_task.objective, scheduled_task.desired_execution_timestamp
     ))
     .await?;
+    let cohort = data.programs_get(guild_id, scheduled_task.program.as_str())?;
+    do_audit(
+        &ctx,
+        cohort.as_deref(),
+        AuditAction::ScheduleCancelled {
+            objective: scheduled_task.objective,
+            at: scheduled_task.desired_execution_timestamp,
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    hide_in_help,
+    prefix_command,
+    slash_command,
+    guild_only = true,
+    check = "can_manage_schedule"
+)]
+#[instrument(name = "schedule-dry_run", skip(ctx))]
+/// Show what a scheduled task would do if it ran now without doing any of it
+pub async fn dry_run(
+    ctx: Context<'_>,
+    #[description = "What the task does"] objective: Objective,
+    #[description = "Defaults to the program for this channel"]
+    #[autocomplete = "autocomplete_program"]
+    program: Option<String>,
+) -> anyhow::Result<()> {
+    tracing_handler_start(&ctx).await;
+    let cohort = resolve_program(&ctx, program).await?;
+    let report = do_dry_run(ctx.data(), &cohort, objective, cohort.channel_id()).await?;
+    reply_in_chunks(ctx, &report).await?;
     Ok(())
 }
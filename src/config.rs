@@ -7,7 +7,6 @@ use std::time::Instant;
 #[derive(Debug)]
 pub struct StartupConfig {
     pub test_guild_id: Option<GuildId>,
-    pub bot_startup_channel: Option<ChannelId>,
 }
 
 #[derive(Debug)]
@@ -16,15 +15,62 @@ pub struct SharedConfig {
     pub start_instant: Instant,
     pub auth_role_id: RoleId,
     pub channel_unranked: ChannelId,
+    pub channel_admin: Option<ChannelId>,
+    pub channel_startup: Option<ChannelId>,
+    pub role_cohort: Option<RoleId>,
+    pub role_veteran: Option<RoleId>,
+    /// Number of completed cohorts needed to be given `role_veteran`
+    pub veteran_cohorts: usize,
     // pub db_pool: sqlx::PgPool,
 }
 
+/// Checks every setting so all the problems are reported together instead of stopping at the first
+///
+/// The types used must match those used when loading the settings below
+pub fn validate_config() -> anyhow::Result<()> {
+    let results = [
+        KeyName::DiscordToken.get_secret_string().map(drop),
+        KeyName::TestGuildId
+            .get_non_secret_parse_opt::<GuildId, _>()
+            .map(drop),
+        KeyName::AuthRoleId
+            .get_non_secret_parse::<RoleId, _>()
+            .map(drop),
+        KeyName::CohortChannel
+            .get_non_secret_parse::<ChannelId, _>()
+            .map(drop),
+        KeyName::StartupMsgChannel
+            .get_non_secret_parse_opt::<ChannelId, _>()
+            .map(drop),
+        KeyName::AdminChannel
+            .get_non_secret_parse_opt::<ChannelId, _>()
+            .map(drop),
+        KeyName::CohortRoleId
+            .get_non_secret_parse_opt::<RoleId, _>()
+            .map(drop),
+        KeyName::VeteranRoleId
+            .get_non_secret_parse_opt::<RoleId, _>()
+            .map(drop),
+        KeyName::VeteranCohorts
+            .get_non_secret_parse_opt::<usize, _>()
+            .map(drop),
+    ];
+    let problems: Vec<String> = results
+        .into_iter()
+        .filter_map(|x| x.err())
+        .map(|e| format!("- {e:#}"))
+        .collect();
+    if !problems.is_empty() {
+        anyhow::bail!("invalid configuration:\n{}", problems.join("\n"));
+    }
+    Ok(())
+}
+
 impl StartupConfig {
-    pub fn new() -> Self {
-        Self {
-            test_guild_id: KeyName::TestGuildId.get_non_secret_parse_opt(),
-            bot_startup_channel: KeyName::StartupMsgChannel.get_non_secret_parse_opt(),
-        }
+    pub fn try_new() -> anyhow::Result<Self> {
+        Ok(Self {
+            test_guild_id: KeyName::TestGuildId.get_non_secret_parse_opt()?,
+        })
     }
 
     pub fn is_production(&self) -> bool {
@@ -32,20 +78,28 @@ impl StartupConfig {
     }
 }
 
-impl Default for StartupConfig {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl SharedConfig {
+    pub const DEFAULT_VETERAN_COHORTS: usize = 3;
+
     pub fn try_new() -> anyhow::Result<&'static Self> {
         let auth_role_id = KeyName::AuthRoleId.get_non_secret_parse()?;
         let channel_unranked = KeyName::CohortChannel.get_non_secret_parse()?;
+        let channel_admin = KeyName::AdminChannel.get_non_secret_parse_opt()?;
+        let channel_startup = KeyName::StartupMsgChannel.get_non_secret_parse_opt()?;
+        let role_cohort = KeyName::CohortRoleId.get_non_secret_parse_opt()?;
+        let role_veteran = KeyName::VeteranRoleId.get_non_secret_parse_opt()?;
+        let veteran_cohorts = KeyName::VeteranCohorts
+            .get_non_secret_parse_opt()?
+            .unwrap_or(Self::DEFAULT_VETERAN_COHORTS);
         let result = Box::new(Self {
             start_instant: Instant::now(),
             auth_role_id,
             channel_unranked,
+            channel_admin,
+            channel_startup,
+            role_cohort,
+            role_veteran,
+            veteran_cohorts,
         });
         Ok(Box::leak(result))
     }
@@ -93,7 +147,16 @@ impl SharedConfig {
         &self,
         key: &str,
     ) -> T {
-        T::default()
+        self.load_or_else_kv(key, T::default).await
+    }
+
+    /// Like [`Self::load_or_default_kv`] but uses `default` to create the value when nothing was saved
+    pub async fn load_or_else_kv<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+        default: impl FnOnce() -> T,
+    ) -> T {
+        default()
         // TODO: Connect to DB
         /*
         let record_opt = match sqlx::query!("SELECT content FROM kv_store where id = $1", key)
@@ -110,14 +173,14 @@ impl SharedConfig {
             Some(record) => record,
             None => {
                 info!("No content found in DB for key: {key}");
-                return T::default();
+                return default();
             }
         };
         match serde_json::from_str(&record.content) {
             Ok(x) => x,
             Err(err_msg) => {
                 error!(?err_msg, ?record.content, "Failed to convert content extracted from the database");
-                T::default()
+                default()
             }
         }
          */
@@ -0,0 +1,31 @@
+//! Errors that are caused by how the bot was used instead of a problem in the bot
+//!
+//! The error handler shows these to the member as is, other errors are treated as internal errors
+
+use std::fmt::Display;
+
+/// A problem the member can fix themselves (for example naming a program that does not exist)
+#[derive(Debug)]
+pub struct UserError(String);
+
+impl UserError {
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self(msg.into())
+    }
+}
+
+impl Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UserError {}
+
+/// Returns early with a [`UserError`], used the same way as [`anyhow::bail`]
+macro_rules! bail_user {
+    ($($arg:tt)*) => {
+        return Err($crate::errors::UserError::new(format!($($arg)*)).into())
+    };
+}
+pub(crate) use bail_user;
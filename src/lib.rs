@@ -16,13 +16,14 @@ use tracing_subscriber::{
 };
 
 pub use self::{
-    commands::commands_list,
+    commands::{commands_list, event_handler, on_error},
     config::{SharedConfig, StartupConfig},
     model::Data,
 };
 
 mod commands;
 mod config;
+mod errors;
 mod model;
 mod secrets;
 
@@ -83,6 +84,31 @@ fn sanitize_markdown(s: String) -> String {
     result
 }
 
+/// Prints every setting with where its value came from (secrets are redacted) and any problems found
+///
+/// Used to diagnose deployments without starting the bot. Returns false if the configuration is not valid
+pub fn print_config() -> bool {
+    dotenvy::dotenv().ok(); // Load environment variables
+    if let Err(e) = secrets::load_config_file() {
+        println!("{e:#}");
+        return false;
+    }
+    match KeyName::effective_config_redacted() {
+        Ok(msg) => print!("{msg}"),
+        Err(e) => println!("failed to describe configuration: {e:#}"),
+    }
+    match config::validate_config() {
+        Ok(()) => {
+            println!("Configuration is valid");
+            true
+        }
+        Err(e) => {
+            println!("{e:#}");
+            false
+        }
+    }
+}
+
 pub async fn start_bot() {
     tracing_subscriber::registry()
         .with(fmt::layer().with_span_events(FmtSpan::NEW | FmtSpan::CLOSE))
@@ -95,9 +121,15 @@ pub async fn start_bot() {
     info!("Bot version is {}", env!("CARGO_PKG_VERSION"));
 
     dotenvy::dotenv().ok(); // Load environment variables
+    secrets::load_config_file().expect("failed to load config file");
+    config::validate_config().expect("failed to validate configuration");
+    info!(
+        "Effective configuration:\n{}",
+        KeyName::effective_config_redacted().expect("failed to describe configuration")
+    );
 
     // Load startup configuration
-    let startup_config = StartupConfig::new();
+    let startup_config = StartupConfig::try_new().expect("failed to create startup_config");
     info!(?startup_config);
 
     let shared_config = SharedConfig::try_new().expect("failed to created shared_config");
@@ -106,6 +138,10 @@ pub async fn start_bot() {
     // Every option can be omitted to use its default value
     let options = poise::FrameworkOptions {
         commands: commands_list(),
+        event_handler: |ctx, event, framework, data| {
+            Box::pin(event_handler(ctx, event, framework, data))
+        },
+        on_error: |error| Box::pin(on_error(error)),
         ..Default::default()
     };
 
@@ -136,12 +172,16 @@ pub async fn start_bot() {
                 }
                 let connect_msg = format!("{} is connected! Version: {}", ready.user.name, env!("CARGO_PKG_VERSION"));
                 info!("{connect_msg}");
-                if let Some(channel) = startup_config.bot_startup_channel{
-                    channel.say(ctx, connect_msg).await?;
-                } else{
-                    warn!("Not sending connection notification because `bot_startup_channel` not set");
+                let data = Data::new(shared_config, ctx.clone()).await?;
+                for config in data.guild_configs_all()? {
+                    if let Some(channel) = config.channel_startup {
+                        if let Err(e) = channel.say(ctx, &connect_msg).await {
+                            warn!("failed to send connection notification to {channel} with error: {e:?}");
+                        }
+                    } else {
+                        warn!("Not sending connection notification to {} because `channel_startup` not set", config.guild_id);
+                    }
                 }
-                let data = Data::new(shared_config, ctx.clone()).await;
                 info!("END OF SETUP CLOSURE");
                 Ok(data)
             })
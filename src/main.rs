@@ -1,6 +1,16 @@
-use ale_bot::start_bot;
+use std::process::ExitCode;
+
+use ale_bot::{print_config, start_bot};
 
 #[tokio::main]
-async fn main() {
-    start_bot().await
+async fn main() -> ExitCode {
+    if std::env::args().skip(1).any(|x| x == "--print-config") {
+        return if print_config() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+    start_bot().await;
+    ExitCode::SUCCESS
 }
@@ -10,14 +10,27 @@
 
 use std::sync::{Arc, Mutex};
 
+use anyhow::Context as _;
+use poise::serenity_prelude::GuildId;
+
 use crate::config::SharedConfig;
 
-use self::{cohort::Cohort, schedule::ScheduledTasks};
+use self::{
+    audit::AuditLogs,
+    cohort::program::{ProgramKey, ProgramName},
+    guild::GuildConfigs,
+    programs::Programs,
+    schedule::ScheduledTasks,
+};
 
+pub mod audit;
 pub mod cohort;
+pub mod guild;
 pub mod one_based_id;
+pub mod programs;
 pub mod schedule;
 pub mod user_serde;
+pub mod utc_offset;
 
 /// User data, which is stored and accessible in all command invocations, cheap to clone uses an Arc
 #[derive(Clone)]
@@ -26,7 +39,11 @@ pub struct Data {
 }
 
 pub struct DataInner {
-    pub cohort: Cohort,
+    /// The server configured through the environment, data from before multiple servers were supported belongs to it
+    pub home_guild_id: GuildId,
+    pub guild_configs: Arc<Mutex<GuildConfigs>>,
+    pub audit_logs: Arc<Mutex<AuditLogs>>,
+    pub programs: Arc<Mutex<Programs>>,
     pub ctx: poise::serenity_prelude::Context,
     pub schedule_tasks: Arc<Mutex<ScheduledTasks>>,
     pub shared_config: &'static SharedConfig,
@@ -36,17 +53,39 @@ impl Data {
     pub async fn new(
         shared_config: &'static SharedConfig,
         ctx: poise::serenity_prelude::Context,
-    ) -> Self {
+    ) -> anyhow::Result<Self> {
+        let home_guild_id = shared_config
+            .channel_unranked
+            .to_channel(&ctx)
+            .await
+            .context("failed to look up the cohort channel to find the home server")?
+            .guild()
+            .context("the cohort channel must be a channel in a server")?
+            .guild_id;
+        let guild_configs = GuildConfigs::new(shared_config, home_guild_id).await;
+        let programs = Programs::new(shared_config, &guild_configs.all(), home_guild_id).await;
         let result = Data {
             inner: Arc::new(DataInner {
-                cohort: Cohort::new(shared_config).await,
+                home_guild_id,
+                guild_configs: Arc::new(Mutex::new(guild_configs)),
+                audit_logs: Arc::new(Mutex::new(AuditLogs::new(shared_config).await)),
+                programs: Arc::new(Mutex::new(programs)),
                 shared_config,
                 schedule_tasks: Arc::new(Mutex::new(ScheduledTasks::new(shared_config).await)),
                 ctx,
             }),
         };
         result.schedule_hydrate();
-        result
+        Ok(result)
+    }
+
+    /// Returns the key for the program in the server
+    pub fn program_key(&self, guild_id: GuildId, name: ProgramName) -> ProgramKey {
+        ProgramKey {
+            guild_id,
+            name,
+            is_home_guild: guild_id == self.inner.home_guild_id,
+        }
     }
 
     fn save<T: serde::Serialize>(&self, key: &str, value: &T) -> anyhow::Result<()> {
@@ -0,0 +1,443 @@
+//! Keeps a record of the privileged actions taken in each server
+//!
+//! Entries are also posted to the server's audit channel if one is set
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use poise::serenity_prelude::{GuildId, Mentionable as _};
+
+use crate::{
+    config::SharedConfig,
+    model::{
+        cohort::program::ProgramName,
+        guild::permissions::{Capability, Grantee},
+        schedule::{Objective, UnixTimestamp},
+        user_serde::UserIdNumber,
+    },
+};
+
+pub mod protected_ops;
+
+/// The audit log of every server
+#[derive(Debug, Default)]
+pub struct AuditLogs {
+    guilds: BTreeMap<GuildId, Vec<AuditEntry>>,
+}
+
+/// What is saved to be able to restore the audit logs after a restart
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct AuditLogList {
+    guilds: Vec<(GuildId, Vec<AuditEntry>)>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct AuditEntry {
+    pub at: UnixTimestamp,
+    /// The member that took (or attempted) the action
+    pub actor: UserIdNumber,
+    /// The program the action was taken in if it was for a specific program
+    #[serde(default)]
+    pub program: Option<ProgramName>,
+    pub action: AuditAction,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub enum AuditAction {
+    MemberAdded(UserIdNumber),
+    MemberRemoved(UserIdNumber),
+    Banned {
+        user: UserIdNumber,
+        reason: Option<String>,
+        until: Option<UnixTimestamp>,
+    },
+    Unbanned(UserIdNumber),
+    ScheduleSet {
+        objective: Objective,
+        at: UnixTimestamp,
+    },
+    ScheduleCancelled {
+        objective: Objective,
+        at: UnixTimestamp,
+    },
+    EventStarted,
+    ScoresReset,
+    /// None if the message was cleared
+    ScoresMessageSet(Option<String>),
+    ConfigChanged(String),
+    PermissionGranted {
+        capability: Capability,
+        grantee: Grantee,
+    },
+    PermissionRevoked {
+        capability: Capability,
+        grantee: Grantee,
+    },
+    PermissionDenied {
+        command: String,
+        /// None for the commands only admin roles can run
+        capability: Option<Capability>,
+    },
+}
+
+/// Groups of actions that can be searched for with `/audit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum AuditActionKind {
+    #[name = "Membership"]
+    Membership,
+    #[name = "Bans"]
+    Bans,
+    #[name = "Schedule"]
+    Schedule,
+    #[name = "Cohort resets"]
+    CohortResets,
+    #[name = "Scores message"]
+    ScoresMessage,
+    #[name = "Configuration"]
+    Configuration,
+    #[name = "Permission denied"]
+    PermissionDenied,
+}
+
+impl AuditLogs {
+    const DATA_KEY: &'static str = "audit_logs";
+
+    /// Oldest entries are dropped once a server has more than this many
+    const MAX_ENTRIES_PER_GUILD: usize = 1000;
+
+    pub async fn new(shared_config: &SharedConfig) -> Self {
+        let list: AuditLogList = shared_config.load_or_default_kv(Self::DATA_KEY).await;
+        Self {
+            guilds: list.guilds.into_iter().collect(),
+        }
+    }
+
+    pub fn add(&mut self, guild_id: GuildId, entry: AuditEntry) {
+        let entries = self.guilds.entry(guild_id).or_default();
+        entries.push(entry);
+        if entries.len() > Self::MAX_ENTRIES_PER_GUILD {
+            let excess = entries.len() - Self::MAX_ENTRIES_PER_GUILD;
+            entries.drain(..excess);
+        }
+    }
+
+    /// Returns up to `limit` of the most recent matching entries (oldest first)
+    ///
+    /// `program` matches the name the program had when the action was taken so entries for removed
+    /// programs can still be found
+    pub fn search(
+        &self,
+        guild_id: GuildId,
+        user: Option<UserIdNumber>,
+        kind: Option<AuditActionKind>,
+        program: Option<&str>,
+        limit: usize,
+    ) -> Vec<AuditEntry> {
+        let Some(entries) = self.guilds.get(&guild_id) else {
+            return Vec::new();
+        };
+        let mut result: Vec<AuditEntry> = entries
+            .iter()
+            .rev()
+            .filter(|x| user.is_none_or(|user| x.involves(user)))
+            .filter(|x| kind.is_none_or(|kind| x.action.kind() == kind))
+            .filter(|x| {
+                program.is_none_or(|name| x.program.as_ref().is_some_and(|p| p.matches(name)))
+            })
+            .take(limit)
+            .cloned()
+            .collect();
+        result.reverse();
+        result
+    }
+
+    fn to_list(&self) -> AuditLogList {
+        AuditLogList {
+            guilds: self
+                .guilds
+                .iter()
+                .map(|(&guild_id, entries)| (guild_id, entries.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl AuditEntry {
+    pub fn new(
+        actor: UserIdNumber,
+        program: Option<ProgramName>,
+        action: AuditAction,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            at: UnixTimestamp::now()?,
+            actor,
+            program,
+            action,
+        })
+    }
+
+    /// True if the user took the action or is the member it was taken on
+    pub fn involves(&self, user: UserIdNumber) -> bool {
+        self.actor == user || self.action.target() == Some(user)
+    }
+}
+
+impl AuditAction {
+    pub fn kind(&self) -> AuditActionKind {
+        match self {
+            AuditAction::MemberAdded(_) | AuditAction::MemberRemoved(_) => {
+                AuditActionKind::Membership
+            }
+            AuditAction::Banned { .. } | AuditAction::Unbanned(_) => AuditActionKind::Bans,
+            AuditAction::ScheduleSet { .. } | AuditAction::ScheduleCancelled { .. } => {
+                AuditActionKind::Schedule
+            }
+            AuditAction::EventStarted | AuditAction::ScoresReset => AuditActionKind::CohortResets,
+            AuditAction::ScoresMessageSet(_) => AuditActionKind::ScoresMessage,
+            AuditAction::ConfigChanged(_)
+            | AuditAction::PermissionGranted { .. }
+            | AuditAction::PermissionRevoked { .. } => AuditActionKind::Configuration,
+            AuditAction::PermissionDenied { .. } => AuditActionKind::PermissionDenied,
+        }
+    }
+
+    /// The member the action was taken on if there is one
+    pub fn target(&self) -> Option<UserIdNumber> {
+        match self {
+            AuditAction::MemberAdded(user)
+            | AuditAction::MemberRemoved(user)
+            | AuditAction::Banned { user, .. }
+            | AuditAction::Unbanned(user) => Some(*user),
+            AuditAction::PermissionGranted {
+                grantee: Grantee::User(user_id),
+                ..
+            }
+            | AuditAction::PermissionRevoked {
+                grantee: Grantee::User(user_id),
+                ..
+            } => Some(user_id.into()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<t:{}:f> {} {}",
+            self.at.as_secs(),
+            self.actor.to_user_id().mention(),
+            self.action
+        )?;
+        if let Some(program) = self.program.as_ref() {
+            write!(f, " ({program})")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use poise::ChoiceParameter as _;
+        match self {
+            AuditAction::MemberAdded(user) => write!(f, "added {}", user.to_user_id().mention()),
+            AuditAction::MemberRemoved(user) => {
+                write!(f, "removed {}", user.to_user_id().mention())
+            }
+            AuditAction::Banned {
+                user,
+                reason,
+                until,
+            } => {
+                write!(f, "banned {}", user.to_user_id().mention())?;
+                match until {
+                    Some(until) => write!(f, " until <t:{}:f>", until.as_secs())?,
+                    None => write!(f, " indefinitely")?,
+                }
+                if let Some(reason) = reason {
+                    write!(f, " - {reason}")?;
+                }
+                Ok(())
+            }
+            AuditAction::Unbanned(user) => write!(f, "unbanned {}", user.to_user_id().mention()),
+            AuditAction::ScheduleSet { objective, at } => {
+                write!(f, "scheduled {objective} for <t:{}:f>", at.as_secs())
+            }
+            AuditAction::ScheduleCancelled { objective, at } => {
+                write!(f, "cancelled {objective} for <t:{}:f>", at.as_secs())
+            }
+            AuditAction::EventStarted => write!(f, "started sign ups for the next cohort"),
+            AuditAction::ScoresReset => write!(f, "reset the scores"),
+            AuditAction::ScoresMessageSet(Some(msg)) => {
+                write!(f, "set the scores message to {msg:?}")
+            }
+            AuditAction::ScoresMessageSet(None) => write!(f, "cleared the scores message"),
+            AuditAction::ConfigChanged(change) => write!(f, "changed the configuration: {change}"),
+            AuditAction::PermissionGranted {
+                capability,
+                grantee,
+            } => write!(f, "gave {grantee} **{}**", capability.name()),
+            AuditAction::PermissionRevoked {
+                capability,
+                grantee,
+            } => write!(f, "took **{}** from {grantee}", capability.name()),
+            AuditAction::PermissionDenied {
+                command,
+                capability,
+            } => match capability {
+                Some(capability) => write!(
+                    f,
+                    "was denied `{command}` (needs **{}**)",
+                    capability.name()
+                ),
+                None => write!(f, "was denied `{command}` (needs an admin role)"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+
+    use super::*;
+
+    const GUILD: GuildId = GuildId::new(1);
+
+    fn user_id_number(i: u64) -> UserIdNumber {
+        UserId::new(i).into()
+    }
+
+    /// `at` is used to tell the entries apart
+    fn entry(at: i32, actor: u64, program: Option<&str>, action: AuditAction) -> AuditEntry {
+        AuditEntry {
+            at: UnixTimestamp::new(at),
+            actor: user_id_number(actor),
+            program: program.map(|x| x.parse().unwrap()),
+            action,
+        }
+    }
+
+    fn times(entries: &[AuditEntry]) -> Vec<i32> {
+        entries.iter().map(|x| x.at.as_secs()).collect()
+    }
+
+    /// Entries 0 to 3 taken by different members in different programs
+    fn logs() -> AuditLogs {
+        let mut logs = AuditLogs::default();
+        logs.add(
+            GUILD,
+            entry(
+                0,
+                1,
+                Some("main"),
+                AuditAction::MemberAdded(user_id_number(2)),
+            ),
+        );
+        logs.add(
+            GUILD,
+            entry(1, 1, None, AuditAction::ConfigChanged("x".into())),
+        );
+        logs.add(
+            GUILD,
+            entry(
+                2,
+                3,
+                Some("Study Group"),
+                AuditAction::Unbanned(user_id_number(4)),
+            ),
+        );
+        logs.add(
+            GUILD,
+            entry(
+                3,
+                3,
+                Some("main"),
+                AuditAction::MemberRemoved(user_id_number(2)),
+            ),
+        );
+        logs
+    }
+
+    #[test]
+    fn most_recent_entries_are_returned_oldest_first() {
+        let logs = logs();
+        assert_eq!(
+            times(&logs.search(GUILD, None, None, None, 10)),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(times(&logs.search(GUILD, None, None, None, 2)), [2, 3]);
+        assert!(
+            logs.search(GuildId::new(2), None, None, None, 10)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn user_filter_matches_actor_and_target() {
+        let logs = logs();
+        assert_eq!(
+            times(&logs.search(GUILD, Some(user_id_number(2)), None, None, 10)),
+            [0, 3]
+        );
+        assert_eq!(
+            times(&logs.search(GUILD, Some(user_id_number(3)), None, None, 10)),
+            [2, 3]
+        );
+    }
+
+    #[test]
+    fn kind_filter_matches_action_kind() {
+        let logs = logs();
+        assert_eq!(
+            times(&logs.search(GUILD, None, Some(AuditActionKind::Membership), None, 10)),
+            [0, 3]
+        );
+        assert_eq!(
+            times(&logs.search(GUILD, None, Some(AuditActionKind::Configuration), None, 10)),
+            [1]
+        );
+        assert!(
+            logs.search(GUILD, None, Some(AuditActionKind::Schedule), None, 10)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn program_filter_ignores_case_and_entries_without_program() {
+        let logs = logs();
+        assert_eq!(
+            times(&logs.search(GUILD, None, None, Some("MAIN"), 10)),
+            [0, 3]
+        );
+        assert_eq!(
+            times(&logs.search(
+                GUILD,
+                Some(user_id_number(1)),
+                Some(AuditActionKind::Membership),
+                Some("main"),
+                10
+            )),
+            [0]
+        );
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped_past_limit() {
+        let mut logs = AuditLogs::default();
+        let count = AuditLogs::MAX_ENTRIES_PER_GUILD + 5;
+        for at in 0..count {
+            logs.add(GUILD, entry(at as i32, 1, None, AuditAction::ScoresReset));
+        }
+        logs.add(GuildId::new(2), entry(0, 1, None, AuditAction::ScoresReset));
+
+        let entries = logs.search(GUILD, None, None, None, count);
+        assert_eq!(entries.len(), AuditLogs::MAX_ENTRIES_PER_GUILD);
+        assert_eq!(entries[0].at.as_secs(), 5);
+        assert_eq!(entries.last().unwrap().at.as_secs(), count as i32 - 1);
+        assert_eq!(
+            logs.search(GuildId::new(2), None, None, None, count).len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use poise::serenity_prelude::GuildId;
+use tracing::info;
+
+use crate::{
+    Data,
+    model::{
+        audit::{AuditActionKind, AuditEntry},
+        user_serde::UserIdNumber,
+    },
+};
+
+use super::AuditLogs;
+
+impl Data {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_audit_logs(&self) -> anyhow::Result<MutexGuard<'_, AuditLogs>> {
+        match self.inner.audit_logs.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_audit_logs(&self, data: &AuditLogs) -> anyhow::Result<()> {
+        self.save(AuditLogs::DATA_KEY, &data.to_list())
+    }
+
+    pub fn audit_logs_add(&self, guild_id: GuildId, entry: AuditEntry) -> anyhow::Result<()> {
+        let mut guard = self.guard_audit_logs()?;
+        info!(?guild_id, ?entry, "audit");
+        guard.add(guild_id, entry);
+        self.save_audit_logs(&guard)?;
+        Ok(())
+    }
+
+    /// Returns up to `limit` of the most recent matching entries (oldest first)
+    pub fn audit_logs_search(
+        &self,
+        guild_id: GuildId,
+        user: Option<UserIdNumber>,
+        kind: Option<AuditActionKind>,
+        program: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AuditEntry>> {
+        let guard = self.guard_audit_logs()?;
+        Ok(guard.search(guild_id, user, kind, program, limit))
+    }
+}
@@ -1,25 +1,131 @@
 //! Groups the functionality related to accountability cohorts
 
-use crate::{config::SharedConfig, model::cohort::interested_list::InterestedList};
-use std::sync::{Arc, Mutex};
+use poise::serenity_prelude::{ChannelId, GuildId};
 
+use crate::{
+    config::SharedConfig,
+    model::cohort::{
+        archive::CohortArchive,
+        bans::CohortBans,
+        constraints::PairingConstraints,
+        history::PairingHistory,
+        interested_list::InterestedList,
+        program::{ProgramKey, ProgramName},
+        record::CohortRecord,
+        settings::CohortSettings,
+    },
+};
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+pub mod archive;
+pub mod bans;
+pub mod check_in;
+pub mod constraints;
+pub mod eligibility;
+pub mod feedback;
+pub mod grouping;
+pub mod history;
 pub mod interested_list;
+pub mod program;
+pub mod record;
+pub mod settings;
+pub mod stats;
 
+/// All the state of one cohort program, each program has its own channel and data
 pub struct Cohort {
+    key: ProgramKey,
+    /// Stored as the raw ID so the channel can be changed with `/config` while the program runs
+    channel_id: AtomicU64,
     scores: Arc<Mutex<InterestedList>>,
+    settings: Arc<Mutex<CohortSettings>>,
+    history: Arc<Mutex<PairingHistory>>,
+    constraints: Arc<Mutex<PairingConstraints>>,
+    record: Arc<Mutex<CohortRecord>>,
+    archive: Arc<Mutex<CohortArchive>>,
+    bans: Arc<Mutex<CohortBans>>,
     shared_config: &'static SharedConfig,
+    /// Set for copies made by [`Cohort::sandbox`] which are never saved
+    is_sandbox: bool,
 }
 
 impl Cohort {
-    pub async fn new(shared_config: &'static SharedConfig) -> Self {
-        let scores = Arc::new(Mutex::new(InterestedList::new(shared_config).await));
+    pub async fn new(
+        shared_config: &'static SharedConfig,
+        key: ProgramKey,
+        channel_id: ChannelId,
+        defaults: &CohortSettings,
+    ) -> Self {
+        let p = &key;
+        let scores = Arc::new(Mutex::new(InterestedList::new(shared_config, p).await));
+        let settings = Arc::new(Mutex::new(
+            CohortSettings::new(shared_config, p, defaults).await,
+        ));
+        let history = Arc::new(Mutex::new(PairingHistory::new(shared_config, p).await));
+        let constraints = Arc::new(Mutex::new(PairingConstraints::new(shared_config, p).await));
+        let record = Arc::new(Mutex::new(CohortRecord::new(shared_config, p).await));
+        let archive = Arc::new(Mutex::new(CohortArchive::new(shared_config, p).await));
+        let bans = Arc::new(Mutex::new(CohortBans::new(shared_config, p).await));
         Self {
+            key,
+            channel_id: AtomicU64::new(channel_id.get()),
             scores,
+            settings,
+            history,
+            constraints,
+            record,
+            archive,
+            bans,
             shared_config,
+            is_sandbox: false,
         }
     }
 
+    /// Returns a copy of the program that is never saved so changes can be tried out on it
+    pub fn sandbox(&self) -> anyhow::Result<Self> {
+        Ok(Self {
+            key: self.key.clone(),
+            channel_id: AtomicU64::new(self.channel_id().get()),
+            scores: Arc::new(Mutex::new(self.scores()?)),
+            settings: Arc::new(Mutex::new(self.settings()?)),
+            history: Arc::new(Mutex::new(self.history()?)),
+            constraints: Arc::new(Mutex::new(self.constraints()?)),
+            record: Arc::new(Mutex::new(self.record()?)),
+            archive: Arc::new(Mutex::new(self.archive()?)),
+            bans: Arc::new(Mutex::new(self.bans()?)),
+            shared_config: self.shared_config,
+            is_sandbox: true,
+        })
+    }
+
+    pub fn key(&self) -> &ProgramKey {
+        &self.key
+    }
+
+    pub fn program(&self) -> &ProgramName {
+        &self.key.name
+    }
+
+    /// The server the program runs in
+    pub fn guild_id(&self) -> GuildId {
+        self.key.guild_id
+    }
+
+    /// The channel used for this program's announcements and group threads
+    pub fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id.load(Ordering::Relaxed))
+    }
+
+    pub fn set_channel_id(&self, channel_id: ChannelId) {
+        self.channel_id.store(channel_id.get(), Ordering::Relaxed);
+    }
+
     fn save<T: serde::Serialize>(&self, key: &str, value: &T) -> anyhow::Result<()> {
-        self.shared_config.save_kv(key, value)
+        if self.is_sandbox {
+            return Ok(());
+        }
+        self.shared_config.save_kv(&self.key.data_key(key), value)
     }
 }
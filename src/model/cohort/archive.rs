@@ -0,0 +1,94 @@
+//! Stores the cohorts that have been completed so members can look back at them
+
+use std::collections::BTreeMap;
+
+use crate::{
+    config::SharedConfig,
+    model::{
+        cohort::{
+            check_in::CheckInRound, feedback::FeedbackResponse, grouping::Group,
+            program::ProgramKey, record::CohortRecord,
+        },
+        one_based_id::OneBasedId,
+        schedule::UnixTimestamp,
+        user_serde::{UserIdNumber, UserRecord},
+    },
+};
+
+pub mod protected_ops;
+
+/// Cohorts are numbered in the order they were archived starting from 1
+pub type CohortNumber = OneBasedId;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct CohortArchive {
+    cohorts: Vec<ArchivedCohort>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ArchivedCohort {
+    pub archived: UnixTimestamp,
+    pub members: Vec<ArchivedMember>,
+    pub groups: Vec<Group>,
+    pub check_ins: Vec<CheckInRound>,
+    pub feedback: BTreeMap<UserIdNumber, FeedbackResponse>,
+}
+
+/// The details of a member as they were at the end of the cohort
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ArchivedMember {
+    pub user: UserRecord,
+    pub goal: Option<String>,
+}
+
+impl CohortArchive {
+    const DATA_KEY: &'static str = "cohort_archive";
+
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey) -> Self {
+        shared_config
+            .load_or_default_kv(&key.data_key(Self::DATA_KEY))
+            .await
+    }
+
+    pub fn cohorts(&self) -> &[ArchivedCohort] {
+        &self.cohorts
+    }
+
+    pub fn get(&self, number: CohortNumber) -> Option<&ArchivedCohort> {
+        self.cohorts.get(number.as_index())
+    }
+
+    /// Adds the cohort and returns the number it was given
+    pub fn add(&mut self, cohort: ArchivedCohort) -> CohortNumber {
+        self.cohorts.push(cohort);
+        CohortNumber::from_index(self.cohorts.len() - 1)
+    }
+}
+
+impl ArchivedCohort {
+    pub fn new(
+        record: CohortRecord,
+        members: Vec<ArchivedMember>,
+        archived: UnixTimestamp,
+    ) -> Self {
+        let (groups, check_ins, feedback) = record.into_parts();
+        Self {
+            archived,
+            members,
+            groups,
+            check_ins,
+            feedback,
+        }
+    }
+
+    pub fn group_of(&self, user_id_number: UserIdNumber) -> Option<&Group> {
+        self.groups.iter().find(|x| x.contains(user_id_number))
+    }
+
+    pub fn goal_of(&self, user_id_number: UserIdNumber) -> Option<&str> {
+        self.members
+            .iter()
+            .find(|x| x.user.id_number == user_id_number)
+            .and_then(|x| x.goal.as_deref())
+    }
+}
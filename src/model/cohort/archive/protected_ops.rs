@@ -0,0 +1,38 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use crate::model::cohort::{
+    Cohort,
+    archive::{ArchivedCohort, CohortNumber},
+};
+
+use super::CohortArchive;
+
+impl Cohort {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_archive(&self) -> anyhow::Result<MutexGuard<'_, CohortArchive>> {
+        match self.archive.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_archive(&self, data: &CohortArchive) -> anyhow::Result<()> {
+        self.save(CohortArchive::DATA_KEY, data)
+    }
+
+    /// Returns a copy of the archive to use without holding the lock
+    pub fn archive(&self) -> anyhow::Result<CohortArchive> {
+        let guard = self.guard_archive()?;
+        Ok(guard.clone())
+    }
+
+    pub fn archive_add(&self, cohort: ArchivedCohort) -> anyhow::Result<CohortNumber> {
+        let mut guard = self.guard_archive()?;
+        let result = guard.add(cohort);
+        self.save_archive(&guard)?;
+        Ok(result)
+    }
+}
@@ -0,0 +1,145 @@
+//! Stores the members that are not allowed to join cohorts
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use poise::serenity_prelude::Mentionable as _;
+
+use crate::{
+    config::SharedConfig,
+    model::{cohort::program::ProgramKey, schedule::UnixTimestamp, user_serde::UserIdNumber},
+};
+
+pub mod protected_ops;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct CohortBans {
+    bans: BTreeMap<UserIdNumber, Ban>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct Ban {
+    /// The admin that set the ban
+    pub by: UserIdNumber,
+    pub reason: Option<String>,
+    pub created: UnixTimestamp,
+    /// The ban no longer applies after this time (never expires if not set)
+    pub until: Option<UnixTimestamp>,
+}
+
+impl CohortBans {
+    const DATA_KEY: &'static str = "cohort_bans";
+
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey) -> Self {
+        shared_config
+            .load_or_default_kv(&key.data_key(Self::DATA_KEY))
+            .await
+    }
+
+    /// Bans the user replacing any existing ban and returns the previous ban if there was one
+    pub fn add(&mut self, user_id_number: UserIdNumber, ban: Ban) -> Option<Ban> {
+        self.bans.insert(user_id_number, ban)
+    }
+
+    /// Returns the ban that was removed if the user was banned
+    pub fn remove(&mut self, user_id_number: UserIdNumber) -> Option<Ban> {
+        self.bans.remove(&user_id_number)
+    }
+
+    /// Returns the bans that still apply at `now`
+    pub fn active(&self, now: UnixTimestamp) -> impl Iterator<Item = (&UserIdNumber, &Ban)> {
+        self.bans.iter().filter(move |(_, ban)| ban.is_active(now))
+    }
+
+    /// Returns the ban if the user is banned at `now`
+    pub fn active_ban(&self, user_id_number: UserIdNumber, now: UnixTimestamp) -> Option<&Ban> {
+        self.bans.get(&user_id_number).filter(|x| x.is_active(now))
+    }
+}
+
+impl Ban {
+    pub fn is_active(&self, now: UnixTimestamp) -> bool {
+        self.until
+            .is_none_or(|until| now.as_secs() < until.as_secs())
+    }
+}
+
+impl Display for Ban {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "banned by {}", self.by.to_user_id().mention())?;
+        match self.until {
+            Some(until) => write!(f, " until {until}")?,
+            None => write!(f, " indefinitely")?,
+        }
+        if let Some(reason) = self.reason.as_ref() {
+            write!(f, " - {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+
+    use super::*;
+
+    fn user_id_number(i: u64) -> UserIdNumber {
+        UserId::new(i).into()
+    }
+
+    fn ban(until: Option<i32>) -> Ban {
+        Ban {
+            by: user_id_number(100),
+            reason: None,
+            created: UnixTimestamp::new(0),
+            until: until.map(UnixTimestamp::new),
+        }
+    }
+
+    #[test]
+    fn ban_without_expiry_is_always_active() {
+        let ban = ban(None);
+        assert!(ban.is_active(UnixTimestamp::new(0)));
+        assert!(ban.is_active(UnixTimestamp::new(i32::MAX)));
+    }
+
+    #[test]
+    fn ban_ends_at_expiry() {
+        let ban = ban(Some(1000));
+        assert!(ban.is_active(UnixTimestamp::new(999)));
+        assert!(!ban.is_active(UnixTimestamp::new(1000)));
+        assert!(!ban.is_active(UnixTimestamp::new(1001)));
+    }
+
+    #[test]
+    fn only_active_bans_are_returned() {
+        let mut bans = CohortBans::default();
+        bans.add(user_id_number(1), ban(None));
+        bans.add(user_id_number(2), ban(Some(1000)));
+        bans.add(user_id_number(3), ban(Some(500)));
+        let now = UnixTimestamp::new(500);
+
+        assert!(bans.active_ban(user_id_number(1), now).is_some());
+        assert!(bans.active_ban(user_id_number(2), now).is_some());
+        assert!(bans.active_ban(user_id_number(3), now).is_none());
+        assert!(bans.active_ban(user_id_number(4), now).is_none());
+        let active: Vec<UserIdNumber> = bans.active(now).map(|(&x, _)| x).collect();
+        assert_eq!(active, [user_id_number(1), user_id_number(2)]);
+    }
+
+    #[test]
+    fn new_ban_replaces_expired_one() {
+        let mut bans = CohortBans::default();
+        bans.add(user_id_number(1), ban(Some(500)));
+        assert!(bans.add(user_id_number(1), ban(None)).is_some());
+        assert!(
+            bans.active_ban(user_id_number(1), UnixTimestamp::new(1000))
+                .is_some()
+        );
+        assert!(bans.remove(user_id_number(1)).is_some());
+        assert!(
+            bans.active_ban(user_id_number(1), UnixTimestamp::new(0))
+                .is_none()
+        );
+    }
+}
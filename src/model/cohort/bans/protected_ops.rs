@@ -0,0 +1,57 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use crate::model::{
+    cohort::{Cohort, bans::Ban},
+    schedule::UnixTimestamp,
+    user_serde::UserIdNumber,
+};
+
+use super::CohortBans;
+
+impl Cohort {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_bans(&self) -> anyhow::Result<MutexGuard<'_, CohortBans>> {
+        match self.bans.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_bans(&self, data: &CohortBans) -> anyhow::Result<()> {
+        self.save(CohortBans::DATA_KEY, data)
+    }
+
+    /// Returns a copy of the bans to use without holding the lock
+    pub fn bans(&self) -> anyhow::Result<CohortBans> {
+        let guard = self.guard_bans()?;
+        Ok(guard.clone())
+    }
+
+    /// Returns the previous ban if there was one
+    pub fn bans_add(&self, user_id_number: UserIdNumber, ban: Ban) -> anyhow::Result<Option<Ban>> {
+        let mut guard = self.guard_bans()?;
+        let result = guard.add(user_id_number, ban);
+        self.save_bans(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns the ban that was removed if the user was banned
+    pub fn bans_remove(&self, user_id_number: UserIdNumber) -> anyhow::Result<Option<Ban>> {
+        let mut guard = self.guard_bans()?;
+        let result = guard.remove(user_id_number);
+        self.save_bans(&guard)?;
+        Ok(result)
+    }
+
+    pub fn bans_active_ban(
+        &self,
+        user_id_number: UserIdNumber,
+        now: UnixTimestamp,
+    ) -> anyhow::Result<Option<Ban>> {
+        let guard = self.guard_bans()?;
+        Ok(guard.active_ban(user_id_number, now).cloned())
+    }
+}
@@ -0,0 +1,111 @@
+//! Types for the weekly check-ins where members report progress on their goal
+
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use anyhow::bail;
+
+use crate::model::{one_based_id::OneBasedId, schedule::UnixTimestamp, user_serde::UserIdNumber};
+
+pub type CheckInId = OneBasedId;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct CheckInRound {
+    pub started: UnixTimestamp,
+    responses: BTreeMap<UserIdNumber, CheckInResponse>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct CheckInResponse {
+    pub status: Option<CheckInStatus>,
+    pub note: Option<String>,
+    pub updated: UnixTimestamp,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CheckInStatus {
+    OnTrack,
+    Behind,
+    Done,
+}
+
+impl CheckInRound {
+    pub fn new(started: UnixTimestamp) -> Self {
+        Self {
+            started,
+            responses: BTreeMap::new(),
+        }
+    }
+
+    pub fn response(&self, user_id_number: UserIdNumber) -> Option<&CheckInResponse> {
+        self.responses.get(&user_id_number)
+    }
+
+    pub fn set_status(
+        &mut self,
+        user_id_number: UserIdNumber,
+        status: CheckInStatus,
+        now: UnixTimestamp,
+    ) {
+        let response = self.responses.entry(user_id_number).or_default();
+        response.status = Some(status);
+        response.updated = now;
+    }
+
+    pub fn set_note(&mut self, user_id_number: UserIdNumber, note: String, now: UnixTimestamp) {
+        let response = self.responses.entry(user_id_number).or_default();
+        response.note = Some(note);
+        response.updated = now;
+    }
+}
+
+impl CheckInStatus {
+    pub const ALL: [Self; 3] = [Self::OnTrack, Self::Behind, Self::Done];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CheckInStatus::OnTrack => "On track",
+            CheckInStatus::Behind => "Behind",
+            CheckInStatus::Done => "Goal done",
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckInStatus::OnTrack => "on_track",
+            CheckInStatus::Behind => "behind",
+            CheckInStatus::Done => "done",
+        }
+    }
+}
+
+impl FromStr for CheckInStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::ALL.into_iter().find(|x| x.as_str() == s) {
+            Some(x) => Ok(x),
+            None => bail!("unknown check-in status: {s:?}"),
+        }
+    }
+}
+
+/// Used in custom IDs so must round trip with [`FromStr`]
+impl Display for CheckInStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Display for CheckInResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.status.map(|x| x.label()).unwrap_or("No status")
+        )?;
+        if let Some(note) = self.note.as_ref() {
+            write!(f, " - {note}")?;
+        }
+        Ok(())
+    }
+}
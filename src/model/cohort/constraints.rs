@@ -0,0 +1,258 @@
+//! Stores the per user rules the pairing solver must (exclusions) or should (preferences) follow
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use poise::serenity_prelude::Mentionable as _;
+
+use crate::{
+    config::SharedConfig,
+    model::{cohort::program::ProgramKey, user_serde::UserIdNumber},
+};
+
+pub mod protected_ops;
+
+/// Constraints for every user that has set any, keyed by the user they belong to
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct PairingConstraints {
+    users: BTreeMap<UserIdNumber, UserConstraints>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct UserConstraints {
+    /// Users this user must never be grouped with
+    #[serde(default)]
+    exclusions: Vec<Exclusion>,
+    #[serde(default)]
+    preferences: SoftPreferences,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct Exclusion {
+    pub other: UserIdNumber,
+    pub source: ExclusionSource,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionSource {
+    /// Set by the user themselves
+    SelfSet,
+
+    /// Set by an admin on the user's behalf (includes the admin's ID)
+    Admin(UserIdNumber),
+
+    /// Set by the user saying they would not pair again in the end of cohort feedback
+    Feedback,
+}
+
+/// Preferences the solver tries to honour but will break if needed
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct SoftPreferences {
+    #[serde(default)]
+    pub prefer_new_members: bool,
+    #[serde(default)]
+    pub preferred_partners: Vec<UserIdNumber>,
+}
+
+impl PairingConstraints {
+    pub const DISPLAY_TITLE: &'static str = "Pairing Constraints";
+    const DATA_KEY: &'static str = "pairing_constraints";
+
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey) -> Self {
+        shared_config
+            .load_or_default_kv(&key.data_key(Self::DATA_KEY))
+            .await
+    }
+
+    pub fn get(&self, user_id_number: UserIdNumber) -> Option<&UserConstraints> {
+        self.users.get(&user_id_number)
+    }
+
+    /// Returns true iff either user has excluded the other
+    pub fn is_excluded(&self, a: UserIdNumber, b: UserIdNumber) -> bool {
+        let has_excluded = |user, other| {
+            self.get(user)
+                .is_some_and(|x| x.exclusions.iter().any(|e| e.other == other))
+        };
+        has_excluded(a, b) || has_excluded(b, a)
+    }
+
+    /// Returns true iff the exclusion was added (false if the user was already excluded by the same source)
+    pub fn add_exclusion(&mut self, user_id_number: UserIdNumber, exclusion: Exclusion) -> bool {
+        let constraints = self.users.entry(user_id_number).or_default();
+        if constraints
+            .exclusions
+            .iter()
+            .any(|x| x.other == exclusion.other && x.source.is_same_kind(&exclusion.source))
+        {
+            return false;
+        }
+        constraints.exclusions.push(exclusion);
+        true
+    }
+
+    /// Removes exclusions of `other` matching the kind of source given
+    ///
+    /// Users can only remove exclusions they set themselves (directly or through feedback), while
+    /// admins can only remove exclusions set by admins
+    ///
+    /// Returns true iff an exclusion was removed
+    pub fn remove_exclusion(
+        &mut self,
+        user_id_number: UserIdNumber,
+        other: UserIdNumber,
+        source: ExclusionSource,
+    ) -> bool {
+        let Some(constraints) = self.users.get_mut(&user_id_number) else {
+            return false;
+        };
+        let before = constraints.exclusions.len();
+        constraints
+            .exclusions
+            .retain(|x| !(x.other == other && x.source.is_same_kind(&source)));
+        let result = before != constraints.exclusions.len();
+        self.remove_if_empty(user_id_number);
+        result
+    }
+
+    pub fn set_prefer_new_members(&mut self, user_id_number: UserIdNumber, value: bool) {
+        self.users
+            .entry(user_id_number)
+            .or_default()
+            .preferences
+            .prefer_new_members = value;
+        self.remove_if_empty(user_id_number);
+    }
+
+    /// Returns true iff the partner was not already preferred
+    pub fn add_preferred_partner(
+        &mut self,
+        user_id_number: UserIdNumber,
+        other: UserIdNumber,
+    ) -> bool {
+        let partners = &mut self
+            .users
+            .entry(user_id_number)
+            .or_default()
+            .preferences
+            .preferred_partners;
+        if partners.contains(&other) {
+            return false;
+        }
+        partners.push(other);
+        true
+    }
+
+    /// Returns true iff the partner was found and removed
+    pub fn remove_preferred_partner(
+        &mut self,
+        user_id_number: UserIdNumber,
+        other: UserIdNumber,
+    ) -> bool {
+        use crate::RemoveElement as _;
+        let result = self
+            .users
+            .get_mut(&user_id_number)
+            .is_some_and(|x| x.preferences.preferred_partners.remove_element(&other));
+        self.remove_if_empty(user_id_number);
+        result
+    }
+
+    /// Removes the entry for the user if there is nothing stored for them to keep the saved data small
+    fn remove_if_empty(&mut self, user_id_number: UserIdNumber) {
+        if self
+            .users
+            .get(&user_id_number)
+            .is_some_and(|x| x.is_empty())
+        {
+            self.users.remove(&user_id_number);
+        }
+    }
+}
+
+impl UserConstraints {
+    pub fn exclusions(&self) -> &[Exclusion] {
+        &self.exclusions
+    }
+
+    pub fn preferences(&self) -> &SoftPreferences {
+        &self.preferences
+    }
+
+    fn is_empty(&self) -> bool {
+        self.exclusions.is_empty()
+            && !self.preferences.prefer_new_members
+            && self.preferences.preferred_partners.is_empty()
+    }
+
+    /// Returns a description of the constraints
+    ///
+    /// Admin set exclusions are only included if `include_admin_set` is true as the user may not be aware of them
+    pub fn display(&self, include_admin_set: bool) -> anyhow::Result<String> {
+        use std::fmt::Write as _;
+        let mut result = String::new();
+        writeln!(result, "Exclusions:")?;
+        let exclusions: Vec<&Exclusion> = self
+            .exclusions
+            .iter()
+            .filter(|x| include_admin_set || !matches!(x.source, ExclusionSource::Admin(_)))
+            .collect();
+        if exclusions.is_empty() {
+            writeln!(result, "- None")?;
+        }
+        for exclusion in exclusions {
+            writeln!(result, "- {exclusion}")?;
+        }
+        writeln!(result, "\nPreferences:")?;
+        writeln!(
+            result,
+            "- Prefer new members: {}",
+            if self.preferences.prefer_new_members {
+                "Yes"
+            } else {
+                "No"
+            }
+        )?;
+        for partner in self.preferences.preferred_partners.iter() {
+            writeln!(
+                result,
+                "- Prefer partner: {}",
+                partner.to_user_id().mention()
+            )?;
+        }
+        Ok(result)
+    }
+}
+
+impl ExclusionSource {
+    fn is_same_kind(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SelfSet, Self::SelfSet)
+                | (Self::Admin(_), Self::Admin(_))
+                | (Self::Feedback, Self::Feedback)
+        )
+    }
+}
+
+impl Display for Exclusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.other.to_user_id().mention(), self.source)?;
+        if let Some(reason) = self.reason.as_ref() {
+            write!(f, " - {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ExclusionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExclusionSource::SelfSet => write!(f, "self set"),
+            ExclusionSource::Admin(admin) => {
+                write!(f, "set by admin {}", admin.to_user_id().mention())
+            }
+            ExclusionSource::Feedback => write!(f, "from cohort feedback"),
+        }
+    }
+}
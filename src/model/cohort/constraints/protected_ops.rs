@@ -0,0 +1,108 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use tracing::info;
+
+use crate::model::{cohort::Cohort, user_serde::UserIdNumber};
+
+use super::{Exclusion, ExclusionSource, PairingConstraints};
+
+impl Cohort {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_constraints(&self) -> anyhow::Result<MutexGuard<'_, PairingConstraints>> {
+        match self.constraints.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_constraints(&self, data: &PairingConstraints) -> anyhow::Result<()> {
+        self.save(PairingConstraints::DATA_KEY, data)
+    }
+
+    /// Returns a copy of the constraints to use without holding the lock
+    pub fn constraints(&self) -> anyhow::Result<PairingConstraints> {
+        let guard = self.guard_constraints()?;
+        Ok(guard.clone())
+    }
+
+    /// Returns true iff the exclusion was added
+    pub fn constraints_exclusion_add(
+        &self,
+        user_id_number: UserIdNumber,
+        exclusion: Exclusion,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_constraints()?;
+        info!(
+            "User# {user_id_number} exclusion of User# {} being added ({:?})",
+            exclusion.other, exclusion.source
+        );
+        let result = guard.add_exclusion(user_id_number, exclusion);
+        self.save_constraints(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns true iff the exclusion was removed
+    pub fn constraints_exclusion_remove(
+        &self,
+        user_id_number: UserIdNumber,
+        other: UserIdNumber,
+        source: ExclusionSource,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_constraints()?;
+        info!("User# {user_id_number} exclusion of User# {other} being removed ({source:?})");
+        let result = guard.remove_exclusion(user_id_number, other, source);
+        self.save_constraints(&guard)?;
+        Ok(result)
+    }
+
+    pub fn constraints_prefer_new_members_set(
+        &self,
+        user_id_number: UserIdNumber,
+        value: bool,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_constraints()?;
+        guard.set_prefer_new_members(user_id_number, value);
+        self.save_constraints(&guard)?;
+        Ok(())
+    }
+
+    /// Returns true iff the partner was added
+    pub fn constraints_preferred_partner_add(
+        &self,
+        user_id_number: UserIdNumber,
+        other: UserIdNumber,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_constraints()?;
+        let result = guard.add_preferred_partner(user_id_number, other);
+        self.save_constraints(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns true iff the partner was removed
+    pub fn constraints_preferred_partner_remove(
+        &self,
+        user_id_number: UserIdNumber,
+        other: UserIdNumber,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_constraints()?;
+        let result = guard.remove_preferred_partner(user_id_number, other);
+        self.save_constraints(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns the description of the constraints for the user
+    pub fn constraints_user_as_string(
+        &self,
+        user_id_number: UserIdNumber,
+        include_admin_set: bool,
+    ) -> anyhow::Result<String> {
+        let guard = self.guard_constraints()?;
+        match guard.get(user_id_number) {
+            Some(constraints) => constraints.display(include_admin_set),
+            None => Ok("No exclusions or preferences set".to_string()),
+        }
+    }
+}
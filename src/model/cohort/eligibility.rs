@@ -0,0 +1,109 @@
+//! Rules that decide who is allowed to sign up for a cohort
+
+use std::fmt::Display;
+
+use poise::serenity_prelude::{Mentionable as _, RoleId};
+
+use crate::model::{cohort::archive::CohortNumber, cohort::bans::Ban};
+
+/// The rules a user must meet to register, each rule is skipped if it is not set
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct EligibilityRules {
+    /// Minimum number of days the user must have been a member of the server
+    #[serde(default)]
+    pub min_membership_days: Option<u16>,
+    #[serde(default)]
+    pub required_role: Option<RoleId>,
+    /// If set users that were in the previous cohort must have given their feedback
+    #[serde(default)]
+    pub require_previous_feedback: bool,
+}
+
+/// What is known about the user when checking the rules
+#[derive(Debug, Default)]
+pub struct EligibilityFacts {
+    /// Number of days since the user joined the server (None if unknown)
+    pub membership_days: Option<u64>,
+    pub has_required_role: bool,
+    pub ban: Option<Ban>,
+    /// The previous cohort if the user was in it but did not give feedback
+    pub missing_feedback: Option<CohortNumber>,
+}
+
+/// A reason the user is not allowed to register
+#[derive(Debug)]
+pub enum Ineligible {
+    MembershipTooShort { required_days: u16 },
+    MissingRole(RoleId),
+    Banned(Ban),
+    MissingFeedback(CohortNumber),
+}
+
+impl EligibilityRules {
+    /// Returns every rule the user does not meet (empty if they are eligible)
+    pub fn check(&self, facts: EligibilityFacts) -> Vec<Ineligible> {
+        let mut result = vec![];
+        if let Some(ban) = facts.ban {
+            result.push(Ineligible::Banned(ban));
+        }
+        if let Some(required_days) = self.min_membership_days
+            && facts
+                .membership_days
+                .is_none_or(|days| days < required_days.into())
+        {
+            result.push(Ineligible::MembershipTooShort { required_days });
+        }
+        if let Some(role_id) = self.required_role
+            && !facts.has_required_role
+        {
+            result.push(Ineligible::MissingRole(role_id));
+        }
+        if self.require_previous_feedback
+            && let Some(number) = facts.missing_feedback
+        {
+            result.push(Ineligible::MissingFeedback(number));
+        }
+        result
+    }
+}
+
+impl Display for EligibilityRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.min_membership_days {
+            Some(days) => writeln!(f, "Minimum server membership: {days} days")?,
+            None => writeln!(f, "Minimum server membership: None")?,
+        }
+        match self.required_role {
+            Some(role_id) => writeln!(f, "Required role: {}", role_id.mention())?,
+            None => writeln!(f, "Required role: None")?,
+        }
+        writeln!(
+            f,
+            "Previous cohort feedback required: {}",
+            if self.require_previous_feedback {
+                "Yes"
+            } else {
+                "No"
+            }
+        )
+    }
+}
+
+impl Display for Ineligible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ineligible::MembershipTooShort { required_days } => write!(
+                f,
+                "You need to have been a member of the server for at least {required_days} days"
+            ),
+            Ineligible::MissingRole(role_id) => {
+                write!(f, "You need the {} role", role_id.mention())
+            }
+            Ineligible::Banned(ban) => write!(f, "You are {ban}"),
+            Ineligible::MissingFeedback(number) => write!(
+                f,
+                "You need to give your feedback for cohort {number} first (ask an admin for the survey)"
+            ),
+        }
+    }
+}
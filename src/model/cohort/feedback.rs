@@ -0,0 +1,156 @@
+//! Types for the survey members fill in at the end of a cohort
+
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use anyhow::{Context as _, bail};
+
+use crate::model::{schedule::UnixTimestamp, user_serde::UserIdNumber};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct FeedbackResponse {
+    pub goal_outcome: GoalOutcome,
+    pub partnership_rating: PartnershipRating,
+    pub would_pair_again: bool,
+    pub comment: Option<String>,
+    /// The other members of the group when the feedback was given
+    pub partners: Vec<UserIdNumber>,
+    pub submitted: UnixTimestamp,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum GoalOutcome {
+    Met,
+    Partly,
+    NotMet,
+}
+
+/// How useful the member found their partnership from 1 (not at all) to 5 (very)
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PartnershipRating(u8);
+
+/// Totals over all the feedback received for a cohort
+#[derive(Debug, Default)]
+pub struct FeedbackSummary {
+    pub member_count: usize,
+    pub response_count: usize,
+    pub goal_met: usize,
+    pub goal_partly: usize,
+    pub goal_not_met: usize,
+    pub rating_total: usize,
+    pub would_pair_again: usize,
+}
+
+impl PartnershipRating {
+    pub const MIN: u8 = 1;
+    pub const MAX: u8 = 5;
+
+    pub fn new(value: u8) -> anyhow::Result<Self> {
+        if !(Self::MIN..=Self::MAX).contains(&value) {
+            bail!(
+                "Rating must be between {} and {} but got {value}",
+                Self::MIN,
+                Self::MAX
+            );
+        }
+        Ok(Self(value))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl FromStr for PartnershipRating {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: u8 = s
+            .trim()
+            .parse()
+            .with_context(|| format!("expected a rating from 1 to 5 but got {s:?}"))?;
+        Self::new(value)
+    }
+}
+
+impl GoalOutcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            GoalOutcome::Met => "Met",
+            GoalOutcome::Partly => "Partly met",
+            GoalOutcome::NotMet => "Not met",
+        }
+    }
+}
+
+impl FromStr for GoalOutcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "yes" | "y" | "met" => Self::Met,
+            "partly" | "partially" | "some" => Self::Partly,
+            "no" | "n" | "not met" => Self::NotMet,
+            _ => bail!("expected yes, partly or no but got {s:?}"),
+        })
+    }
+}
+
+/// Parses a yes or no answer
+pub fn parse_yes_no(s: &str) -> anyhow::Result<bool> {
+    Ok(match s.trim().to_lowercase().as_str() {
+        "yes" | "y" => true,
+        "no" | "n" => false,
+        _ => bail!("expected yes or no but got {s:?}"),
+    })
+}
+
+impl FeedbackSummary {
+    pub fn new(responses: &BTreeMap<UserIdNumber, FeedbackResponse>, member_count: usize) -> Self {
+        let mut result = Self {
+            member_count,
+            ..Default::default()
+        };
+        for response in responses.values() {
+            result.response_count += 1;
+            match response.goal_outcome {
+                GoalOutcome::Met => result.goal_met += 1,
+                GoalOutcome::Partly => result.goal_partly += 1,
+                GoalOutcome::NotMet => result.goal_not_met += 1,
+            }
+            result.rating_total += usize::from(response.partnership_rating.get());
+            if response.would_pair_again {
+                result.would_pair_again += 1;
+            }
+        }
+        result
+    }
+}
+
+impl Display for FeedbackSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Responses: {} of {} members",
+            self.response_count, self.member_count
+        )?;
+        if self.response_count == 0 {
+            return Ok(());
+        }
+        writeln!(
+            f,
+            "Goals: {} met, {} partly met, {} not met",
+            self.goal_met, self.goal_partly, self.goal_not_met
+        )?;
+        writeln!(
+            f,
+            "Average partnership rating: {:.1} / {}",
+            self.rating_total as f64 / self.response_count as f64,
+            PartnershipRating::MAX
+        )?;
+        write!(
+            f,
+            "Happy to be paired again: {} of {}",
+            self.would_pair_again, self.response_count
+        )
+    }
+}
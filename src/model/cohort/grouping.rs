@@ -0,0 +1,419 @@
+//! Splits the members of a cohort into groups (pairs, trios or larger pods)
+
+use std::{collections::BTreeSet, fmt::Display, num::NonZeroUsize, str::FromStr};
+
+use anyhow::{Context as _, bail};
+use poise::serenity_prelude::Mentionable as _;
+use rand::Rng;
+use tracing::{info, instrument, warn};
+
+use crate::model::{
+    one_based_id::OneBasedId,
+    user_serde::{UserIdNumber, UserRecord},
+};
+
+pub use self::solver::GroupingRules;
+
+pub mod solver;
+
+/// The number of members each group should have
+///
+/// Groups may end up with one more or one less member than this to handle remainders
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "u8")]
+pub struct GroupSize(u8);
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub members: Vec<UserRecord>,
+}
+
+/// Identifies a member by their position in a list of groups, written as `group.member` (e.g. `2.1`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemberPosition {
+    pub group: OneBasedId,
+    pub member: OneBasedId,
+}
+
+impl GroupSize {
+    pub const MIN: u8 = 2;
+    pub const MAX: u8 = 8;
+    pub const PAIRS: Self = Self(2);
+
+    pub fn new(value: u8) -> anyhow::Result<Self> {
+        if !(Self::MIN..=Self::MAX).contains(&value) {
+            bail!(
+                "Group size must be between {} and {} but got {value}",
+                Self::MIN,
+                Self::MAX
+            );
+        }
+        Ok(Self(value))
+    }
+
+    pub fn get(self) -> usize {
+        self.0.into()
+    }
+
+    /// The smallest group we are willing to create when there are leftover members
+    fn min_acceptable(self) -> usize {
+        (self.get() - 1).max(Self::MIN.into())
+    }
+}
+
+impl TryFrom<u8> for GroupSize {
+    type Error = anyhow::Error;
+
+    /// Saved sizes go through the same bounds check as sizes set by a command
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl Default for GroupSize {
+    fn default() -> Self {
+        Self::PAIRS
+    }
+}
+
+impl Display for GroupSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            2 => write!(f, "pairs"),
+            3 => write!(f, "trios"),
+            n => write!(f, "pods of {n}"),
+        }
+    }
+}
+
+impl Group {
+    pub fn member_ids(&self) -> impl Iterator<Item = UserIdNumber> + '_ {
+        self.members.iter().map(|x| x.id_number)
+    }
+
+    pub fn contains(&self, user_id_number: UserIdNumber) -> bool {
+        self.member_ids().any(|x| x == user_id_number)
+    }
+}
+
+impl Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mentions: Vec<String> = self
+            .members
+            .iter()
+            .map(|x| x.id_number.to_user_id().mention().to_string())
+            .collect();
+        write!(f, "{}", mentions.join(", "))
+    }
+}
+
+/// Calculates how many members should go into each group
+///
+/// Groups are as close to `group_size` as possible and differ in size by at most one.
+/// If the remainder would leave a group too small the leftover members are spread
+/// over the other groups instead
+#[instrument]
+pub fn balanced_group_sizes(member_count: usize, group_size: GroupSize) -> Vec<usize> {
+    if member_count < GroupSize::MIN.into() {
+        return vec![];
+    }
+    let mut group_count = member_count.div_ceil(group_size.get());
+    while group_count > 1 && member_count / group_count < group_size.min_acceptable() {
+        group_count -= 1;
+    }
+    let base = member_count / group_count;
+    let extra = member_count % group_count;
+    let result = (0..group_count)
+        .map(|i| if i < extra { base + 1 } else { base })
+        .collect();
+    info!(?result);
+    result
+}
+
+/// Splits `members` into balanced groups of `group_size` following the rules given
+///
+/// Fails if the exclusions cannot all be honoured
+pub fn form_groups(
+    members: Vec<UserRecord>,
+    group_size: GroupSize,
+    rules: &GroupingRules,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Vec<Group>> {
+    if members.len() < GroupSize::MIN.into() {
+        bail!(
+            "At least {} members are needed to form groups but only {} registered",
+            GroupSize::MIN,
+            members.len()
+        );
+    }
+    let groups = rules.solve(members, group_size, rng);
+    let violations = rules.exclusion_violations(&groups);
+    if !violations.is_empty() {
+        let violations: Vec<String> = violations
+            .iter()
+            .map(|(a, b)| format!("{} & {}", a.name, b.name))
+            .collect();
+        warn!(?violations, "unable to honour all exclusions");
+        bail!(
+            "Unable to form groups without breaking these exclusions: {}",
+            violations.join(", ")
+        );
+    }
+    Ok(groups)
+}
+
+/// Splits the members into groups in the order given without shuffling
+pub fn split_into_groups(members: Vec<UserRecord>, group_size: GroupSize) -> Vec<Group> {
+    let mut members = members.into_iter();
+    balanced_group_sizes(members.len(), group_size)
+        .into_iter()
+        .map(|size| Group {
+            members: members.by_ref().take(size).collect(),
+        })
+        .collect()
+}
+
+/// Returns the announcement listing each group on its own line
+pub fn display_groups(groups: &[Group]) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+    let mut result = String::new();
+    for (i, group) in groups.iter().enumerate() {
+        writeln!(result, "{}. {group}", i + 1)?;
+    }
+    Ok(result)
+}
+
+/// Regroups every member that is not in a locked group
+///
+/// Locked groups are kept unchanged and placed first so their new numbers are `1..=locked.len()`
+pub fn regroup_unlocked(
+    groups: &[Group],
+    locked: &BTreeSet<OneBasedId>,
+    group_size: GroupSize,
+    rules: &GroupingRules,
+    rng: &mut impl Rng,
+) -> anyhow::Result<Vec<Group>> {
+    let mut result = vec![];
+    let mut unlocked_members = vec![];
+    for (i, group) in groups.iter().enumerate() {
+        if locked.contains(&OneBasedId::from_index(i)) {
+            result.push(group.clone());
+        } else {
+            unlocked_members.extend(group.members.iter().cloned());
+        }
+    }
+    if unlocked_members.is_empty() {
+        bail!("All groups are locked, nothing to reshuffle");
+    }
+    result.extend(form_groups(unlocked_members, group_size, rules, rng)?);
+    Ok(result)
+}
+
+/// Swaps two members between groups
+pub fn swap_members(
+    groups: &mut [Group],
+    a: MemberPosition,
+    b: MemberPosition,
+) -> anyhow::Result<()> {
+    if a.group == b.group {
+        bail!("Both members are already in group {}", a.group);
+    }
+    for position in [a, b] {
+        if groups
+            .get(position.group.as_index())
+            .is_none_or(|x| position.member.as_index() >= x.members.len())
+        {
+            bail!("No member found at position {position}");
+        }
+    }
+    let member_a = groups[a.group.as_index()].members[a.member.as_index()].clone();
+    let member_b = std::mem::replace(
+        &mut groups[b.group.as_index()].members[b.member.as_index()],
+        member_a,
+    );
+    groups[a.group.as_index()].members[a.member.as_index()] = member_b;
+    Ok(())
+}
+
+/// Returns a listing of the groups showing each member's position for use while the groups are still a draft
+pub fn display_draft(groups: &[Group], locked: &BTreeSet<OneBasedId>) -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+    let mut result = String::new();
+    for (i, group) in groups.iter().enumerate() {
+        let group_id = OneBasedId::from_index(i);
+        let members: Vec<String> = group
+            .members
+            .iter()
+            .enumerate()
+            .map(|(j, member)| format!("`{group_id}.{}` {}", j + 1, member.name))
+            .collect();
+        writeln!(
+            result,
+            "{}{group_id}. {}",
+            if locked.contains(&group_id) {
+                "🔒 "
+            } else {
+                ""
+            },
+            members.join(", ")
+        )?;
+    }
+    Ok(result)
+}
+
+impl FromStr for MemberPosition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, member) = s
+            .trim()
+            .split_once('.')
+            .with_context(|| format!("expected a position like `2.1` but got {s:?}"))?;
+        let group: NonZeroUsize = group
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid group number in {s:?}"))?;
+        let member: NonZeroUsize = member
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid member number in {s:?}"))?;
+        Ok(Self {
+            group: group.into(),
+            member: member.into(),
+        })
+    }
+}
+
+impl Display for MemberPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.group, self.member)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+    use serde::{Deserialize as _, de::IntoDeserializer as _};
+
+    use super::*;
+
+    pub(super) fn members(count: u64) -> Vec<UserRecord> {
+        (1..=count)
+            .map(|i| UserRecord {
+                id_number: UserId::new(i).into(),
+                name: format!("member {i}").into(),
+            })
+            .collect()
+    }
+
+    fn all_group_sizes() -> impl Iterator<Item = GroupSize> {
+        (GroupSize::MIN..=GroupSize::MAX).map(|x| GroupSize::new(x).unwrap())
+    }
+
+    #[test]
+    fn group_size_bounds() {
+        assert!(GroupSize::new(GroupSize::MIN - 1).is_err());
+        assert!(GroupSize::new(GroupSize::MAX + 1).is_err());
+        assert_eq!(all_group_sizes().count(), 7);
+    }
+
+    #[test]
+    fn group_size_deserialize_checks_bounds() {
+        let parse = |value: u8| {
+            GroupSize::deserialize(value.into_deserializer())
+                .map_err(|e: serde::de::value::Error| e.to_string())
+        };
+        assert_eq!(parse(3), Ok(GroupSize::new(3).unwrap()));
+        assert!(parse(0).is_err());
+        assert!(parse(1).is_err());
+        assert!(parse(9).is_err());
+    }
+
+    #[test]
+    fn balanced_sizes_use_every_member_and_differ_by_at_most_one() {
+        for group_size in all_group_sizes() {
+            for member_count in 0..=20 {
+                let sizes = balanced_group_sizes(member_count, group_size);
+                let context = format!("{member_count} members in {group_size}: {sizes:?}");
+                if member_count < GroupSize::MIN.into() {
+                    assert!(sizes.is_empty(), "{context}");
+                    continue;
+                }
+                assert_eq!(sizes.iter().sum::<usize>(), member_count, "{context}");
+                let smallest = *sizes.iter().min().unwrap();
+                let largest = *sizes.iter().max().unwrap();
+                assert!(largest - smallest <= 1, "{context}");
+                assert!(smallest >= GroupSize::MIN.into(), "{context}");
+                if sizes.len() > 1 {
+                    assert!(smallest >= group_size.min_acceptable(), "{context}");
+                }
+                assert!(
+                    sizes.len() <= member_count.div_ceil(group_size.get()),
+                    "{context}"
+                );
+                // One more group would leave a group too small (or be more than needed)
+                let more_groups = sizes.len() + 1;
+                assert!(
+                    more_groups > member_count.div_ceil(group_size.get())
+                        || member_count / more_groups < group_size.min_acceptable(),
+                    "{context}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn balanced_sizes_handle_remainders() {
+        let pairs = GroupSize::PAIRS;
+        let trios = GroupSize::new(3).unwrap();
+        assert_eq!(balanced_group_sizes(2, pairs), vec![2]);
+        assert_eq!(balanced_group_sizes(3, pairs), vec![3]);
+        assert_eq!(balanced_group_sizes(5, pairs), vec![3, 2]);
+        assert_eq!(balanced_group_sizes(4, trios), vec![2, 2]);
+        assert_eq!(balanced_group_sizes(7, trios), vec![3, 2, 2]);
+        assert_eq!(balanced_group_sizes(10, trios), vec![3, 3, 2, 2]);
+        assert_eq!(balanced_group_sizes(9, GroupSize::new(8).unwrap()), vec![9]);
+    }
+
+    #[test]
+    fn split_keeps_order_and_uses_balanced_sizes() {
+        for group_size in all_group_sizes() {
+            for member_count in 0..=20 {
+                let members = members(member_count);
+                let groups = split_into_groups(members.clone(), group_size);
+                let sizes: Vec<usize> = groups.iter().map(|x| x.members.len()).collect();
+                assert_eq!(
+                    sizes,
+                    balanced_group_sizes(members.len(), group_size),
+                    "{member_count} members in {group_size}"
+                );
+                let flattened: Vec<UserRecord> =
+                    groups.into_iter().flat_map(|x| x.members).collect();
+                if member_count >= GroupSize::MIN.into() {
+                    assert_eq!(flattened, members);
+                } else {
+                    assert!(flattened.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swap_members_between_groups() {
+        let mut groups = split_into_groups(members(4), GroupSize::PAIRS);
+        let position = |s: &str| s.parse::<MemberPosition>().unwrap();
+        swap_members(&mut groups, position("1.2"), position("2.1")).unwrap();
+        let names = |group: &Group| {
+            group
+                .members
+                .iter()
+                .map(|x| x.name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&groups[0]), ["member 1", "member 3"]);
+        assert_eq!(names(&groups[1]), ["member 2", "member 4"]);
+        assert!(swap_members(&mut groups, position("1.1"), position("1.2")).is_err());
+        assert!(swap_members(&mut groups, position("1.1"), position("3.1")).is_err());
+        assert!(swap_members(&mut groups, position("1.1"), position("2.3")).is_err());
+    }
+}
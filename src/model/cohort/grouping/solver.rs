@@ -0,0 +1,304 @@
+//! Searches for groups that honour the pairing constraints and avoid repeating previous groups
+//!
+//! Uses random restarts followed by swapping members between groups while that lowers the cost
+
+use std::collections::BTreeSet;
+
+use rand::{Rng, seq::SliceRandom as _};
+use tracing::{info, instrument};
+
+use crate::model::{
+    cohort::{constraints::PairingConstraints, history::PairingHistory},
+    user_serde::{UserIdNumber, UserRecord},
+};
+
+use super::{Group, GroupSize, split_into_groups};
+
+type Cost = u64;
+
+/// Everything other than the members themselves that affects how good a grouping is
+pub struct GroupingRules<'a> {
+    pub history: &'a PairingHistory,
+    pub constraints: &'a PairingConstraints,
+    /// Members that count as new to the server for the "prefer new members" preference
+    pub new_members: &'a BTreeSet<UserIdNumber>,
+}
+
+impl GroupingRules<'_> {
+    const EXCLUSION_COST: Cost = 1_000_000;
+    const REPEAT_COST: Cost = 100;
+    const UNMET_PREFERENCE_COST: Cost = 10;
+    const RESTARTS: usize = 20;
+
+    fn pair_cost(&self, a: UserIdNumber, b: UserIdNumber) -> Cost {
+        let mut result = self.history.times_grouped(a, b) as Cost * Self::REPEAT_COST;
+        if self.constraints.is_excluded(a, b) {
+            result += Self::EXCLUSION_COST;
+        }
+        result
+    }
+
+    fn group_cost(&self, group: &Group) -> Cost {
+        let ids: Vec<UserIdNumber> = group.member_ids().collect();
+        let mut result = 0;
+        for (i, &a) in ids.iter().enumerate() {
+            for &b in ids.iter().skip(i + 1) {
+                result += self.pair_cost(a, b);
+            }
+            let Some(constraints) = self.constraints.get(a) else {
+                continue;
+            };
+            let preferences = constraints.preferences();
+            if preferences.prefer_new_members
+                && !ids
+                    .iter()
+                    .any(|&other| other != a && self.new_members.contains(&other))
+            {
+                result += Self::UNMET_PREFERENCE_COST;
+            }
+            result += preferences
+                .preferred_partners
+                .iter()
+                .filter(|x| !ids.contains(x))
+                .count() as Cost
+                * Self::UNMET_PREFERENCE_COST;
+        }
+        result
+    }
+
+    fn total_cost(&self, groups: &[Group]) -> Cost {
+        groups.iter().map(|x| self.group_cost(x)).sum()
+    }
+
+    /// Returns the pairs of members that were grouped despite an exclusion between them
+    pub fn exclusion_violations(&self, groups: &[Group]) -> Vec<(UserRecord, UserRecord)> {
+        let mut result = vec![];
+        for group in groups {
+            for (i, a) in group.members.iter().enumerate() {
+                for b in group.members.iter().skip(i + 1) {
+                    if self.constraints.is_excluded(a.id_number, b.id_number) {
+                        result.push((a.clone(), b.clone()));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the lowest cost grouping found
+    #[instrument(skip(self, members, rng))]
+    pub fn solve(
+        &self,
+        mut members: Vec<UserRecord>,
+        group_size: GroupSize,
+        rng: &mut impl Rng,
+    ) -> Vec<Group> {
+        let mut best: Option<(Cost, Vec<Group>)> = None;
+        for _ in 0..Self::RESTARTS {
+            members.shuffle(rng);
+            let mut groups = split_into_groups(members.clone(), group_size);
+            self.improve_by_swapping(&mut groups);
+            let cost = self.total_cost(&groups);
+            if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                best = Some((cost, groups));
+            }
+            if cost == 0 {
+                break;
+            }
+        }
+        let (cost, groups) = best.unwrap_or_default();
+        info!(cost, "groups formed");
+        groups
+    }
+
+    /// Swaps members between groups as long as any swap lowers the cost
+    fn improve_by_swapping(&self, groups: &mut [Group]) {
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for gi in 0..groups.len() {
+                for gj in (gi + 1)..groups.len() {
+                    for mi in 0..groups[gi].members.len() {
+                        for mj in 0..groups[gj].members.len() {
+                            let before =
+                                self.group_cost(&groups[gi]) + self.group_cost(&groups[gj]);
+                            swap_members(groups, (gi, mi), (gj, mj));
+                            let after = self.group_cost(&groups[gi]) + self.group_cost(&groups[gj]);
+                            if after < before {
+                                improved = true;
+                            } else {
+                                // Undo swap
+                                swap_members(groups, (gi, mi), (gj, mj));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Swaps two members in different groups (the first group index must be lower than the second)
+fn swap_members(groups: &mut [Group], (gi, mi): (usize, usize), (gj, mj): (usize, usize)) {
+    debug_assert!(gi < gj);
+    let (left, right) = groups.split_at_mut(gj);
+    std::mem::swap(&mut left[gi].members[mi], &mut right[0].members[mj]);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng as _, rngs::StdRng};
+
+    use crate::model::cohort::{
+        constraints::{Exclusion, ExclusionSource},
+        grouping::{balanced_group_sizes, form_groups, tests::members},
+    };
+
+    use super::*;
+
+    fn exclude(constraints: &mut PairingConstraints, user: &UserRecord, other: &UserRecord) {
+        constraints.add_exclusion(
+            user.id_number,
+            Exclusion {
+                other: other.id_number,
+                source: ExclusionSource::SelfSet,
+                reason: None,
+            },
+        );
+    }
+
+    fn sorted_ids(groups: &[Group]) -> Vec<UserIdNumber> {
+        let mut result: Vec<UserIdNumber> = groups.iter().flat_map(|x| x.member_ids()).collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn solve_places_every_member_once() {
+        let history = PairingHistory::default();
+        let constraints = PairingConstraints::default();
+        let new_members = BTreeSet::new();
+        let rules = GroupingRules {
+            history: &history,
+            constraints: &constraints,
+            new_members: &new_members,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        for size in GroupSize::MIN..=GroupSize::MAX {
+            let group_size = GroupSize::new(size).unwrap();
+            for member_count in 2..=20 {
+                let members = members(member_count);
+                let groups = rules.solve(members.clone(), group_size, &mut rng);
+                let mut sizes: Vec<usize> = groups.iter().map(|x| x.members.len()).collect();
+                sizes.sort_by(|a, b| b.cmp(a));
+                assert_eq!(
+                    sizes,
+                    balanced_group_sizes(members.len(), group_size),
+                    "{member_count} members in {group_size}"
+                );
+                assert_eq!(
+                    sorted_ids(&groups),
+                    members.iter().map(|x| x.id_number).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn solve_honours_exclusions_when_possible() {
+        let members = members(6);
+        let history = PairingHistory::default();
+        let mut constraints = PairingConstraints::default();
+        exclude(&mut constraints, &members[0], &members[1]);
+        exclude(&mut constraints, &members[0], &members[2]);
+        exclude(&mut constraints, &members[3], &members[4]);
+        let new_members = BTreeSet::new();
+        let rules = GroupingRules {
+            history: &history,
+            constraints: &constraints,
+            new_members: &new_members,
+        };
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let groups = rules.solve(members.clone(), GroupSize::PAIRS, &mut rng);
+            assert!(rules.exclusion_violations(&groups).is_empty(), "{groups:?}");
+            let mut rng = StdRng::seed_from_u64(seed);
+            assert!(form_groups(members.clone(), GroupSize::PAIRS, &rules, &mut rng).is_ok());
+        }
+    }
+
+    #[test]
+    fn solve_reports_exclusions_that_cannot_be_honoured() {
+        // Everyone has excluded the first member so whoever they are grouped with breaks an exclusion
+        let members = members(4);
+        let history = PairingHistory::default();
+        let mut constraints = PairingConstraints::default();
+        for other in members.iter().skip(1) {
+            exclude(&mut constraints, other, &members[0]);
+        }
+        let new_members = BTreeSet::new();
+        let rules = GroupingRules {
+            history: &history,
+            constraints: &constraints,
+            new_members: &new_members,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let groups = rules.solve(members.clone(), GroupSize::PAIRS, &mut rng);
+        assert_eq!(sorted_ids(&groups).len(), members.len());
+        let violations = rules.exclusion_violations(&groups);
+        assert_eq!(violations.len(), 1, "{violations:?}");
+        let error = form_groups(members.clone(), GroupSize::PAIRS, &rules, &mut rng)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("member 1"), "{error}");
+    }
+
+    #[test]
+    fn solve_reports_exclusion_inside_the_only_group() {
+        // Three members make a single group so the exclusion can not be avoided
+        let members = members(3);
+        let history = PairingHistory::default();
+        let mut constraints = PairingConstraints::default();
+        exclude(&mut constraints, &members[0], &members[1]);
+        let new_members = BTreeSet::new();
+        let rules = GroupingRules {
+            history: &history,
+            constraints: &constraints,
+            new_members: &new_members,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(form_groups(members, GroupSize::PAIRS, &rules, &mut rng).is_err());
+    }
+
+    #[test]
+    fn solve_avoids_repeats_and_honours_preferences() {
+        let members = members(4);
+        let mut history = PairingHistory::default();
+        history.record_round(&[
+            Group {
+                members: vec![members[0].clone(), members[1].clone()],
+            },
+            Group {
+                members: vec![members[2].clone(), members[3].clone()],
+            },
+        ]);
+        let mut constraints = PairingConstraints::default();
+        constraints.add_preferred_partner(members[0].id_number, members[3].id_number);
+        let new_members = BTreeSet::new();
+        let rules = GroupingRules {
+            history: &history,
+            constraints: &constraints,
+            new_members: &new_members,
+        };
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let groups = rules.solve(members.clone(), GroupSize::PAIRS, &mut rng);
+            assert_eq!(rules.total_cost(&groups), 0, "{groups:?}");
+            assert!(
+                groups
+                    .iter()
+                    .any(|x| x.contains(members[0].id_number) && x.contains(members[3].id_number))
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Keeps track of who has been grouped with whom in previous rounds
+
+use crate::{
+    config::SharedConfig,
+    model::{
+        cohort::{grouping::Group, program::ProgramKey},
+        user_serde::UserIdNumber,
+    },
+};
+
+pub mod protected_ops;
+
+/// Groups from every previous round of pairing
+///
+/// Only the IDs are kept as names can change between rounds
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct PairingHistory {
+    rounds: Vec<PairingRound>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct PairingRound {
+    groups: Vec<Vec<UserIdNumber>>,
+}
+
+impl PairingHistory {
+    const DATA_KEY: &'static str = "pairing_history";
+
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey) -> Self {
+        shared_config
+            .load_or_default_kv(&key.data_key(Self::DATA_KEY))
+            .await
+    }
+
+    pub fn record_round(&mut self, groups: &[Group]) {
+        let groups = groups.iter().map(|x| x.member_ids().collect()).collect();
+        self.rounds.push(PairingRound { groups });
+    }
+
+    /// Adds a member that joined after the groups were formed to their group in the latest round
+    ///
+    /// `group` is the group including the new member
+    pub fn record_late_join(&mut self, user_id_number: UserIdNumber, group: &Group) {
+        let ids: Vec<UserIdNumber> = group.member_ids().collect();
+        let Some(round) = self.rounds.last_mut() else {
+            self.record_round(std::slice::from_ref(group));
+            return;
+        };
+        match round
+            .groups
+            .iter_mut()
+            .find(|x| ids.iter().any(|id| *id != user_id_number && x.contains(id)))
+        {
+            Some(existing) => {
+                if !existing.contains(&user_id_number) {
+                    existing.push(user_id_number);
+                }
+            }
+            None => round.groups.push(ids),
+        }
+    }
+
+    /// Returns the number of rounds where both users were in the same group
+    pub fn times_grouped(&self, a: UserIdNumber, b: UserIdNumber) -> usize {
+        self.rounds
+            .iter()
+            .flat_map(|round| round.groups.iter())
+            .filter(|group| group.contains(&a) && group.contains(&b))
+            .count()
+    }
+}
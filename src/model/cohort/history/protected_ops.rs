@@ -0,0 +1,49 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use crate::model::{
+    cohort::{Cohort, grouping::Group},
+    user_serde::UserIdNumber,
+};
+
+use super::PairingHistory;
+
+impl Cohort {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_history(&self) -> anyhow::Result<MutexGuard<'_, PairingHistory>> {
+        match self.history.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_history(&self, data: &PairingHistory) -> anyhow::Result<()> {
+        self.save(PairingHistory::DATA_KEY, data)
+    }
+
+    /// Returns a copy of the history to use without holding the lock
+    pub fn history(&self) -> anyhow::Result<PairingHistory> {
+        let guard = self.guard_history()?;
+        Ok(guard.clone())
+    }
+
+    pub fn history_record_round(&self, groups: &[Group]) -> anyhow::Result<()> {
+        let mut guard = self.guard_history()?;
+        guard.record_round(groups);
+        self.save_history(&guard)?;
+        Ok(())
+    }
+
+    pub fn history_record_late_join(
+        &self,
+        user_id_number: UserIdNumber,
+        group: &Group,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_history()?;
+        guard.record_late_join(user_id_number, group);
+        self.save_history(&guard)?;
+        Ok(())
+    }
+}
@@ -6,18 +6,25 @@ use tracing::{error, info};
 use crate::{
     RemoveElement as _, Resettable,
     config::SharedConfig,
-    model::user_serde::{UserIdNumber, UserName, UserRecord},
+    model::{
+        cohort::program::ProgramKey,
+        one_based_id::OneBasedId,
+        schedule::UnixTimestamp,
+        user_serde::{UserIdNumber, UserName, UserRecord},
+    },
 };
 
 pub mod protected_ops;
 
 pub type ScoreValue = i8;
+
+/// The names of the registered users by score (users on the waitlist are left out)
 type ScoresCache = BTreeMap<ScoreValue, Vec<UserName>>;
 
 /// Users scores
 ///
 /// Assumes that each user has at most one record
-#[derive(serde::Serialize, serde::Deserialize, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
 pub struct InterestedList {
     pub message: String,
     records: Vec<ScoreRecord>,
@@ -29,12 +36,59 @@ pub struct InterestedList {
 pub struct ScoreRecord {
     user: UserRecord,
     score: ScoreValue,
+    /// What the user wants to work on during the cohort
+    #[serde(default)]
+    goal: Option<String>,
+    /// When the user is usually available to meet
+    #[serde(default)]
+    availability: Option<String>,
+    /// If set the user does not want to receive direct messages from the bot
+    #[serde(default)]
+    dm_opt_out: bool,
+    /// Set while the user is on the waitlist because the cohort was full when they registered
+    #[serde(default)]
+    waitlisted_since: Option<UnixTimestamp>,
+}
+
+pub enum OutcomeSetScore {
+    Updated,
+    Registered,
+
+    /// The cohort is full so the user was added to the waitlist at this position
+    Waitlisted(OneBasedId),
+}
+
+impl ScoreRecord {
+    pub fn goal(&self) -> Option<&str> {
+        self.goal.as_deref()
+    }
+
+    pub fn availability(&self) -> Option<&str> {
+        self.availability.as_deref()
+    }
+
+    pub fn dm_opt_out(&self) -> bool {
+        self.dm_opt_out
+    }
+
+    pub fn waitlisted_since(&self) -> Option<UnixTimestamp> {
+        self.waitlisted_since
+    }
 }
 
 impl InterestedList {
     pub const DISPLAY_TITLE: &'static str = "UNRANKED CHALLENGE";
     const DATA_KEY: &'static str = "scores";
-    pub fn set_score(&mut self, user: UserRecord, score: ScoreValue) -> anyhow::Result<()> {
+    /// Sets the score of the user registering them if they are new
+    ///
+    /// New users are put on the waitlist if there are already `max_participants` registered
+    pub fn set_score(
+        &mut self,
+        user: UserRecord,
+        score: ScoreValue,
+        max_participants: Option<usize>,
+        now: UnixTimestamp,
+    ) -> anyhow::Result<OutcomeSetScore> {
         // Generate cache if it doesn't exist so that the code later can assume it already exists for the current data
         self.cache()?;
 
@@ -44,7 +98,10 @@ impl InterestedList {
         for record in self.records.iter_mut() {
             if record.user.id_number == user.id_number {
                 // User found. Update score if different and update cache
-                if record.score != score {
+                if record.waitlisted_since.is_some() {
+                    // Not in the cache until they are promoted
+                    record.score = score;
+                } else if record.score != score {
                     let old_score = record.score;
 
                     // Update user record
@@ -56,15 +113,29 @@ impl InterestedList {
                     // Add user to new list in cache
                     self.cache()?.entry(score).or_default().push(user_name);
                 }
-                return Ok(());
+                return Ok(OutcomeSetScore::Updated);
             }
         }
 
         // New user, not found. Create record and update cache
+        let is_full = max_participants.is_some_and(|max| self.participant_count() >= max);
         let user_name = user.name.clone();
-        self.records.push(ScoreRecord { user, score });
-        self.cache()?.entry(score).or_default().push(user_name);
-        Ok(())
+        self.records.push(ScoreRecord {
+            user,
+            score,
+            goal: None,
+            availability: None,
+            dm_opt_out: false,
+            waitlisted_since: is_full.then_some(now),
+        });
+        if !is_full {
+            self.cache()?.entry(score).or_default().push(user_name);
+        }
+        Ok(if is_full {
+            OutcomeSetScore::Waitlisted(OneBasedId::from_index(self.waitlist().len() - 1))
+        } else {
+            OutcomeSetScore::Registered
+        })
     }
 
     /// Removes a user from the cache which means this function depends on the cache existing
@@ -77,7 +148,7 @@ impl InterestedList {
     ) -> Result<(), anyhow::Error> {
         if self.cache.is_none() {
             error!("Attempt to remove from the cache while it does not exist");
-            bail!("Internal Error. Please try again.");
+            bail!("attempted to remove from the cache while it does not exist");
         }
         match self.cache()?.get_mut(score_in_cache) {
             Some(users) => {
@@ -106,7 +177,7 @@ impl InterestedList {
                 self.records.len()
             );
             let mut map: ScoresCache = BTreeMap::new();
-            for record in self.records.iter() {
+            for record in self.records.iter().filter(|x| x.waitlisted_since.is_none()) {
                 map.entry(record.score)
                     .or_default()
                     .push(record.user.name.clone());
@@ -133,7 +204,9 @@ impl InterestedList {
 
         Ok(if let Some(i) = index {
             let record = self.records.remove(i);
-            self.remove_user_from_cache(&record.score, &record.user.name)?;
+            if record.waitlisted_since.is_none() {
+                self.remove_user_from_cache(&record.score, &record.user.name)?;
+            }
             true
         } else {
             // User not found
@@ -155,6 +228,84 @@ impl InterestedList {
         Ok(result)
     }
 
+    /// Returns the users that are currently registered (excludes the waitlist)
+    pub fn registered_users(&self) -> Vec<UserRecord> {
+        self.records
+            .iter()
+            .filter(|x| x.waitlisted_since.is_none())
+            .map(|x| x.user.clone())
+            .collect()
+    }
+
+    pub fn participant_count(&self) -> usize {
+        self.records
+            .iter()
+            .filter(|x| x.waitlisted_since.is_none())
+            .count()
+    }
+
+    /// Returns the users on the waitlist in the order they registered
+    ///
+    /// Records are only ever appended so their order is the order the users registered in
+    pub fn waitlist(&self) -> Vec<UserRecord> {
+        self.records
+            .iter()
+            .filter(|x| x.waitlisted_since.is_some())
+            .map(|x| x.user.clone())
+            .collect()
+    }
+
+    /// Moves users from the front of the waitlist until there are `max_participants` registered and returns those moved
+    pub fn promote_from_waitlist(&mut self, max_participants: Option<usize>) -> Vec<UserRecord> {
+        let mut available =
+            max_participants.map(|max| max.saturating_sub(self.participant_count()));
+        let mut result = vec![];
+        for record in self
+            .records
+            .iter_mut()
+            .filter(|x| x.waitlisted_since.is_some())
+        {
+            if available == Some(0) {
+                break;
+            }
+            record.waitlisted_since = None;
+            result.push(record.user.clone());
+            if let Some(available) = available.as_mut() {
+                *available -= 1;
+            }
+        }
+        if !result.is_empty() {
+            // Rebuilt on next use to include the promoted users
+            self.cache = None;
+        }
+        result
+    }
+
+    /// Applies `f` to the record of the user and returns false if the user is not registered
+    pub fn update_record(
+        &mut self,
+        user_id_number: UserIdNumber,
+        f: impl FnOnce(&mut ScoreRecord),
+    ) -> bool {
+        match self
+            .records
+            .iter_mut()
+            .find(|x| x.user.id_number == user_id_number)
+        {
+            Some(record) => {
+                f(record);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn record(&self, user_id_number: UserIdNumber) -> Option<&ScoreRecord> {
+        self.records
+            .iter()
+            .find(|x| x.user.id_number == user_id_number)
+    }
+
     pub fn set_message(&mut self, user_id_number: UserIdNumber, msg: String) {
         info!(
             "User# {user_id_number} is replacing scores message from {:?} to {msg:?}",
@@ -163,9 +314,67 @@ impl InterestedList {
         self.message = msg;
     }
 
-    pub async fn new(shared_config: &SharedConfig) -> Self {
-        shared_config.load_or_default_kv(Self::DATA_KEY).await
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey) -> Self {
+        shared_config
+            .load_or_default_kv(&key.data_key(Self::DATA_KEY))
+            .await
     }
 }
 
 impl Resettable for InterestedList {}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+
+    use super::*;
+
+    fn user(i: u64) -> UserRecord {
+        UserRecord {
+            id_number: UserId::new(i).into(),
+            name: format!("member{i}").into(),
+        }
+    }
+
+    #[test]
+    fn display_leaves_out_the_waitlist() {
+        let mut list = InterestedList::default();
+        let now = UnixTimestamp::new(0);
+        let max = Some(1);
+        assert!(matches!(
+            list.set_score(user(1), 3, max, now).unwrap(),
+            OutcomeSetScore::Registered
+        ));
+        assert!(matches!(
+            list.set_score(user(2), 5, max, now).unwrap(),
+            OutcomeSetScore::Waitlisted(_)
+        ));
+        let display = list.display().unwrap();
+        assert!(display.contains("member1"), "{display}");
+        assert!(!display.contains("member2"), "{display}");
+
+        // Changing the score while waitlisted keeps them off the rankings
+        assert!(matches!(
+            list.set_score(user(2), 4, max, now).unwrap(),
+            OutcomeSetScore::Updated
+        ));
+        assert!(!list.display().unwrap().contains("member2"));
+
+        assert!(list.remove_score(&user(1)).unwrap());
+        assert_eq!(list.promote_from_waitlist(max), vec![user(2)]);
+        let display = list.display().unwrap();
+        assert!(display.contains("4 WINS - member2"), "{display}");
+        assert!(!display.contains("member1"), "{display}");
+    }
+
+    #[test]
+    fn remove_waitlisted_user() {
+        let mut list = InterestedList::default();
+        let now = UnixTimestamp::new(0);
+        list.set_score(user(1), 3, Some(1), now).unwrap();
+        list.set_score(user(2), 3, Some(1), now).unwrap();
+        assert!(list.remove_score(&user(2)).unwrap());
+        assert!(list.waitlist().is_empty());
+        assert!(list.display().unwrap().contains("3 WINS - member1"));
+    }
+}
@@ -7,15 +7,16 @@ use crate::{
     Resettable as _,
     model::{
         cohort::Cohort,
+        schedule::UnixTimestamp,
         user_serde::{UserIdNumber, UserRecord},
     },
 };
 
-use super::{InterestedList, ScoreValue};
+use super::{InterestedList, OutcomeSetScore, ScoreRecord, ScoreValue};
 
 impl Cohort {
     /// Serves as the link to the private function that returns the guard
-    fn guard_scores(&self) -> anyhow::Result<MutexGuard<InterestedList>> {
+    fn guard_scores(&self) -> anyhow::Result<MutexGuard<'_, InterestedList>> {
         match self.scores.lock() {
             Ok(guard) => Ok(guard),
             Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
@@ -26,11 +27,18 @@ impl Cohort {
         self.save(InterestedList::DATA_KEY, data)
     }
 
-    pub fn score_set(&self, user: UserRecord, score: ScoreValue) -> anyhow::Result<()> {
+    /// Takes the participant cap as a parameter because the settings are protected by a different lock
+    pub fn score_set(
+        &self,
+        user: UserRecord,
+        score: ScoreValue,
+        max_participants: Option<usize>,
+        now: UnixTimestamp,
+    ) -> anyhow::Result<OutcomeSetScore> {
         let mut guard = self.guard_scores()?;
-        guard.set_score(user, score)?;
+        let result = guard.set_score(user, score, max_participants, now)?;
         self.save_scores(&guard)?;
-        Ok(())
+        Ok(result)
     }
 
     /// Returns true iff score was removed
@@ -48,6 +56,78 @@ impl Cohort {
         Ok(result)
     }
 
+    /// Returns a copy of the scores to use without holding the lock
+    pub fn scores(&self) -> anyhow::Result<InterestedList> {
+        let guard = self.guard_scores()?;
+        Ok(guard.clone())
+    }
+
+    pub fn scores_registered_users(&self) -> anyhow::Result<Vec<UserRecord>> {
+        let guard = self.guard_scores()?;
+        Ok(guard.registered_users())
+    }
+
+    pub fn scores_waitlist(&self) -> anyhow::Result<Vec<UserRecord>> {
+        let guard = self.guard_scores()?;
+        Ok(guard.waitlist())
+    }
+
+    /// Returns the users that were moved off the waitlist
+    pub fn scores_promote_from_waitlist(
+        &self,
+        max_participants: Option<usize>,
+    ) -> anyhow::Result<Vec<UserRecord>> {
+        let mut guard = self.guard_scores()?;
+        let result = guard.promote_from_waitlist(max_participants);
+        self.save_scores(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns false if the user is not registered
+    pub fn scores_goal_set(
+        &self,
+        user_id_number: UserIdNumber,
+        goal: Option<String>,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_scores()?;
+        let result = guard.update_record(user_id_number, |x| x.goal = goal);
+        self.save_scores(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns false if the user is not registered
+    pub fn scores_availability_set(
+        &self,
+        user_id_number: UserIdNumber,
+        availability: Option<String>,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_scores()?;
+        let result = guard.update_record(user_id_number, |x| x.availability = availability);
+        self.save_scores(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns false if the user is not registered
+    pub fn scores_dm_opt_out_set(
+        &self,
+        user_id_number: UserIdNumber,
+        dm_opt_out: bool,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.guard_scores()?;
+        let result = guard.update_record(user_id_number, |x| x.dm_opt_out = dm_opt_out);
+        self.save_scores(&guard)?;
+        Ok(result)
+    }
+
+    /// Returns a copy of the user's registration if they are registered
+    pub fn scores_record(
+        &self,
+        user_id_number: UserIdNumber,
+    ) -> anyhow::Result<Option<ScoreRecord>> {
+        let guard = self.guard_scores()?;
+        Ok(guard.record(user_id_number).cloned())
+    }
+
     pub fn scores_message(&self, user_id_number: UserIdNumber, msg: String) -> anyhow::Result<()> {
         let mut guard = self.guard_scores()?;
         guard.set_message(user_id_number, msg);
@@ -0,0 +1,204 @@
+//! Identifies which of the cohort programs running at the same time something belongs to
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::bail;
+use poise::serenity_prelude::GuildId;
+
+/// The name of a cohort program (e.g. "Monthly Accountability" or "Study Group")
+///
+/// Used as a separator free part of button IDs so it may not contain `:`
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String")]
+pub struct ProgramName(String);
+
+impl ProgramName {
+    pub const MAX_LEN: usize = 32;
+
+    /// The program that existed before multiple programs were supported
+    const DEFAULT: &'static str = "main";
+
+    pub fn is_default(&self) -> bool {
+        self.0 == Self::DEFAULT
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the key to store data under for this program
+    ///
+    /// The default program uses the keys unchanged so data saved before programs existed still loads
+    pub fn data_key(&self, key: &str) -> String {
+        if self.is_default() {
+            key.to_string()
+        } else {
+            format!("program/{}/{key}", self.0)
+        }
+    }
+
+    /// Returns true if `name` refers to this program (ignores case)
+    pub fn matches(&self, name: &str) -> bool {
+        self.0.eq_ignore_ascii_case(name.trim())
+    }
+}
+
+/// Identifies a program across all the servers the bot is in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramKey {
+    pub guild_id: GuildId,
+    pub name: ProgramName,
+    /// The home server keeps using the keys from before multiple servers were supported
+    pub is_home_guild: bool,
+}
+
+impl ProgramKey {
+    /// Returns the key to store data under for this program
+    pub fn data_key(&self, key: &str) -> String {
+        let key = self.name.data_key(key);
+        if self.is_home_guild {
+            key
+        } else {
+            format!("guild/{}/{key}", self.guild_id)
+        }
+    }
+
+    /// The form used in button IDs so presses in DMs can be routed back to the program
+    ///
+    /// See [`ProgramKey::parse_custom_id_part`] for the reverse
+    pub fn to_custom_id_part(&self) -> String {
+        format!("{}/{}", self.guild_id, self.name)
+    }
+
+    /// Splits the part of a button ID created by [`ProgramKey::to_custom_id_part`] into the server and program name
+    ///
+    /// Buttons created before multiple servers were supported only have the name and belong to the home server
+    pub fn parse_custom_id_part(s: &str) -> anyhow::Result<(Option<GuildId>, &str)> {
+        match s.split_once('/') {
+            Some((guild_id, name)) => {
+                let guild_id: u64 = guild_id
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("invalid server id in {s:?}: {e}"))?;
+                if guild_id == 0 {
+                    bail!("invalid server id in {s:?}");
+                }
+                Ok((Some(GuildId::new(guild_id)), name))
+            }
+            None => Ok((None, s)),
+        }
+    }
+}
+
+impl Default for ProgramName {
+    fn default() -> Self {
+        Self(Self::DEFAULT.to_string())
+    }
+}
+
+impl FromStr for ProgramName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            bail!("Program name cannot be empty");
+        }
+        if s.chars().count() > Self::MAX_LEN {
+            bail!(
+                "Program name can be at most {} characters but got {}",
+                Self::MAX_LEN,
+                s.chars().count()
+            );
+        }
+        if s.contains([':', '/']) {
+            bail!("Program name cannot contain `:` or `/`");
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for ProgramName {
+    type Error = anyhow::Error;
+
+    /// Saved names go through the same checks as names given in a command
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for ProgramName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_name_is_checked() {
+        assert_eq!(
+            ProgramName::try_from("Study Group".to_string()).unwrap(),
+            "Study Group".parse().unwrap()
+        );
+        assert!(ProgramName::try_from("a:b".to_string()).is_err());
+        assert!(ProgramName::try_from("a/b".to_string()).is_err());
+        assert!(ProgramName::try_from("x".repeat(ProgramName::MAX_LEN + 1)).is_err());
+    }
+
+    fn key(guild_id: u64, name: &str, is_home_guild: bool) -> ProgramKey {
+        ProgramKey {
+            guild_id: GuildId::new(guild_id),
+            name: name.parse().unwrap(),
+            is_home_guild,
+        }
+    }
+
+    #[test]
+    fn home_guild_default_program_keeps_legacy_keys() {
+        let key = key(1, "main", true);
+        assert_eq!(key.data_key("cohort_record"), "cohort_record");
+    }
+
+    #[test]
+    fn home_guild_named_program_is_prefixed_by_name() {
+        let key = key(1, "Study Group", true);
+        assert_eq!(
+            key.data_key("cohort_record"),
+            "program/Study Group/cohort_record"
+        );
+    }
+
+    #[test]
+    fn other_guild_is_prefixed_by_guild() {
+        assert_eq!(
+            key(2, "main", false).data_key("cohort_record"),
+            "guild/2/cohort_record"
+        );
+        assert_eq!(
+            key(2, "Study Group", false).data_key("cohort_record"),
+            "guild/2/program/Study Group/cohort_record"
+        );
+    }
+
+    #[test]
+    fn custom_id_part_round_trips() {
+        let key = key(2, "Study Group", false);
+        let part = key.to_custom_id_part();
+        assert_eq!(
+            ProgramKey::parse_custom_id_part(&part).unwrap(),
+            (Some(GuildId::new(2)), "Study Group")
+        );
+    }
+
+    #[test]
+    fn legacy_custom_id_part_is_name_only() {
+        assert_eq!(
+            ProgramKey::parse_custom_id_part("Study Group").unwrap(),
+            (None, "Study Group")
+        );
+        assert!(ProgramKey::parse_custom_id_part("0/main").is_err());
+        assert!(ProgramKey::parse_custom_id_part("abc/main").is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! Stores what has happened in the cohort that is currently in progress
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context as _;
+use poise::serenity_prelude::ChannelId;
+
+use crate::{
+    config::SharedConfig,
+    model::{
+        cohort::{
+            check_in::{CheckInId, CheckInRound},
+            constraints::PairingConstraints,
+            feedback::FeedbackResponse,
+            grouping::Group,
+            program::ProgramKey,
+        },
+        schedule::UnixTimestamp,
+        user_serde::{UserIdNumber, UserRecord},
+    },
+};
+
+pub mod protected_ops;
+
+/// The cohort currently in progress (empty until groups are published)
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct CohortRecord {
+    #[serde(default)]
+    groups: Vec<PublishedGroup>,
+    #[serde(default)]
+    check_ins: Vec<CheckInRound>,
+    /// Members that have missed too many check-ins in a row
+    #[serde(default)]
+    inactive: BTreeSet<UserIdNumber>,
+    /// Members whose group mates are all inactive and asked to be re-matched
+    #[serde(default)]
+    rematch_requests: BTreeSet<UserIdNumber>,
+    /// Members that joined after groups were published and are waiting for a group
+    #[serde(default)]
+    late_joiners: Vec<UserRecord>,
+    /// The number of check-ins already started when each late joiner was placed in a group
+    ///
+    /// Only the check-ins after that count towards whether they are inactive
+    #[serde(default)]
+    joined_after_check_ins: BTreeMap<UserIdNumber, usize>,
+    /// Responses to the end of cohort survey
+    #[serde(default)]
+    feedback: BTreeMap<UserIdNumber, FeedbackResponse>,
+}
+
+pub enum OutcomeLeave {
+    NotInGroup,
+
+    /// Includes the group as it is after the member left (may be empty)
+    LeftGroup(PublishedGroup),
+
+    /// The member was waiting for a group as a late joiner
+    LeftWaitingList,
+}
+
+pub enum OutcomeLateJoin {
+    NotInProgress,
+    AlreadyInGroup,
+
+    /// Includes the group as it was before the member joined
+    JoinedGroup(PublishedGroup),
+
+    /// Includes the new group formed with another late joiner
+    PairedWithLateJoiner(Group),
+
+    /// No suitable group so waiting for another late joiner
+    Waiting,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct PublishedGroup {
+    pub group: Group,
+    /// The thread created for the group to talk in if it was created successfully
+    pub thread_id: Option<ChannelId>,
+}
+
+impl CohortRecord {
+    const DATA_KEY: &'static str = "cohort_record";
+
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey) -> Self {
+        shared_config
+            .load_or_default_kv(&key.data_key(Self::DATA_KEY))
+            .await
+    }
+
+    pub fn groups(&self) -> &[PublishedGroup] {
+        &self.groups
+    }
+
+    pub fn check_ins(&self) -> &[CheckInRound] {
+        &self.check_ins
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        !self.groups.is_empty()
+    }
+
+    /// Replaces any groups previously published
+    pub fn set_groups(&mut self, groups: &[Group]) {
+        self.groups = groups
+            .iter()
+            .map(|group| PublishedGroup {
+                group: group.clone(),
+                thread_id: None,
+            })
+            .collect();
+    }
+
+    /// Returns the group the user is in if any
+    pub fn group_of(&self, user_id_number: UserIdNumber) -> Option<&PublishedGroup> {
+        self.groups
+            .iter()
+            .find(|x| x.group.contains(user_id_number))
+    }
+
+    pub fn set_thread(&mut self, group: &Group, thread_id: ChannelId) -> anyhow::Result<()> {
+        match self.groups.iter_mut().find(|x| &x.group == group) {
+            Some(published) => {
+                published.thread_id = Some(thread_id);
+                Ok(())
+            }
+            None => anyhow::bail!("no published group found matching {group:?}"),
+        }
+    }
+
+    pub fn is_inactive(&self, user_id_number: UserIdNumber) -> bool {
+        self.inactive.contains(&user_id_number)
+    }
+
+    pub fn rematch_requests(&self) -> &BTreeSet<UserIdNumber> {
+        &self.rematch_requests
+    }
+
+    /// Flags members that did not respond to any of the last `limit` check-ins and returns the newly flagged members
+    ///
+    /// Check-ins from before a late joiner was placed in a group are not counted
+    pub fn flag_inactive(&mut self, limit: usize) -> Vec<UserIdNumber> {
+        if limit == 0 || self.check_ins.len() < limit {
+            return vec![];
+        }
+        let recent_rounds = &self.check_ins[self.check_ins.len() - limit..];
+        let mut result = vec![];
+        for user_id_number in self.groups.iter().flat_map(|x| x.group.member_ids()) {
+            let rounds_since_joined = self.check_ins.len()
+                - self
+                    .joined_after_check_ins
+                    .get(&user_id_number)
+                    .copied()
+                    .unwrap_or_default();
+            if !self.inactive.contains(&user_id_number)
+                && rounds_since_joined >= limit
+                && recent_rounds
+                    .iter()
+                    .all(|round| round.response(user_id_number).is_none())
+            {
+                result.push(user_id_number);
+            }
+        }
+        self.inactive.extend(result.iter().copied());
+        result
+    }
+
+    /// Returns true iff the user is active but everyone else in their group is inactive
+    pub fn is_stranded(&self, user_id_number: UserIdNumber) -> bool {
+        !self.is_inactive(user_id_number)
+            && self.group_of(user_id_number).is_some_and(|x| {
+                x.group
+                    .member_ids()
+                    .filter(|&other| other != user_id_number)
+                    .all(|other| self.is_inactive(other))
+            })
+    }
+
+    /// Returns the stranded members of the group the user is in
+    pub fn stranded_partners_of(&self, user_id_number: UserIdNumber) -> Vec<UserIdNumber> {
+        self.group_of(user_id_number)
+            .map(|x| {
+                x.group
+                    .member_ids()
+                    .filter(|&other| self.is_stranded(other))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn request_rematch(&mut self, user_id_number: UserIdNumber) -> anyhow::Result<()> {
+        if !self.is_stranded(user_id_number) {
+            anyhow::bail!(
+                "Re-matching is only available when everyone else in your group is inactive"
+            );
+        }
+        self.rematch_requests.insert(user_id_number);
+        Ok(())
+    }
+
+    /// Moves the members of the new groups out of their old groups and publishes the new groups
+    pub fn rematch(&mut self, new_groups: &[Group]) {
+        for new_group in new_groups {
+            for user_id_number in new_group.member_ids() {
+                for published in self.groups.iter_mut() {
+                    published
+                        .group
+                        .members
+                        .retain(|x| x.id_number != user_id_number);
+                }
+                self.rematch_requests.remove(&user_id_number);
+            }
+        }
+        self.groups.retain(|x| !x.group.members.is_empty());
+        self.groups
+            .extend(new_groups.iter().map(|group| PublishedGroup {
+                group: group.clone(),
+                thread_id: None,
+            }));
+    }
+
+    pub fn start_check_in(&mut self, now: UnixTimestamp) -> anyhow::Result<CheckInId> {
+        if !self.is_in_progress() {
+            anyhow::bail!("No cohort in progress to check in on");
+        }
+        self.check_ins.push(CheckInRound::new(now));
+        Ok(CheckInId::from_index(self.check_ins.len() - 1))
+    }
+
+    /// Fails if the user is not in a group or the round does not exist
+    pub fn check_in_mut(
+        &mut self,
+        id: CheckInId,
+        user_id_number: UserIdNumber,
+    ) -> anyhow::Result<&mut CheckInRound> {
+        if self.group_of(user_id_number).is_none() {
+            anyhow::bail!("Only members of the current cohort can check in");
+        }
+        // Responding shows the member is active again
+        self.inactive.remove(&user_id_number);
+        self.check_ins
+            .get_mut(id.as_index())
+            .with_context(|| format!("Check-in #{id} not found. It may be from a previous cohort"))
+    }
+
+    /// Splits the record into the parts kept once the cohort is over (groups, check-ins and feedback)
+    pub fn into_parts(
+        self,
+    ) -> (
+        Vec<Group>,
+        Vec<CheckInRound>,
+        BTreeMap<UserIdNumber, FeedbackResponse>,
+    ) {
+        (
+            self.groups.into_iter().map(|x| x.group).collect(),
+            self.check_ins,
+            self.feedback,
+        )
+    }
+
+    pub fn feedback(&self) -> &BTreeMap<UserIdNumber, FeedbackResponse> {
+        &self.feedback
+    }
+
+    /// Returns the number of members currently in a group
+    pub fn member_count(&self) -> usize {
+        self.groups.iter().map(|x| x.group.members.len()).sum()
+    }
+
+    /// Stores the member's feedback replacing any they gave before
+    pub fn set_feedback(
+        &mut self,
+        user_id_number: UserIdNumber,
+        response: FeedbackResponse,
+    ) -> anyhow::Result<()> {
+        if self.group_of(user_id_number).is_none() {
+            anyhow::bail!("Only members of a group in the current cohort can give feedback");
+        }
+        self.feedback.insert(user_id_number, response);
+        Ok(())
+    }
+
+    pub fn late_joiners(&self) -> &[UserRecord] {
+        &self.late_joiners
+    }
+
+    /// Removes the member from their group (or the late joiners waiting for a group)
+    pub fn leave(&mut self, user_id_number: UserIdNumber) -> OutcomeLeave {
+        let waiting_count = self.late_joiners.len();
+        self.late_joiners.retain(|x| x.id_number != user_id_number);
+        if waiting_count != self.late_joiners.len() {
+            return OutcomeLeave::LeftWaitingList;
+        }
+        let Some(published) = self
+            .groups
+            .iter_mut()
+            .find(|x| x.group.contains(user_id_number))
+        else {
+            return OutcomeLeave::NotInGroup;
+        };
+        published
+            .group
+            .members
+            .retain(|x| x.id_number != user_id_number);
+        let result = published.clone();
+        self.groups.retain(|x| !x.group.members.is_empty());
+        self.inactive.remove(&user_id_number);
+        self.rematch_requests.remove(&user_id_number);
+        self.joined_after_check_ins.remove(&user_id_number);
+        OutcomeLeave::LeftGroup(result)
+    }
+
+    /// Places a member that registered after the groups were published
+    ///
+    /// Prefers (in order) a group that is below the target size, another late joiner and then
+    /// the smallest group which will then be one above the target size.
+    /// Groups with someone the member is excluded from are skipped.
+    pub fn late_join(
+        &mut self,
+        user: UserRecord,
+        group_size: usize,
+        constraints: &PairingConstraints,
+    ) -> OutcomeLateJoin {
+        if !self.is_in_progress() {
+            return OutcomeLateJoin::NotInProgress;
+        }
+        if self.group_of(user.id_number).is_some()
+            || self
+                .late_joiners
+                .iter()
+                .any(|x| x.id_number == user.id_number)
+        {
+            return OutcomeLateJoin::AlreadyInGroup;
+        }
+        let is_allowed = |group: &Group| {
+            !group
+                .member_ids()
+                .any(|other| constraints.is_excluded(user.id_number, other))
+        };
+        let smallest_index = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.group.members.len() <= group_size && is_allowed(&x.group))
+            .filter(|(_, x)| !x.group.member_ids().all(|other| self.is_inactive(other)))
+            .min_by_key(|(_, x)| x.group.members.len())
+            .map(|(i, _)| i);
+        let is_below_target =
+            smallest_index.is_some_and(|i| self.groups[i].group.members.len() < group_size);
+        if !is_below_target
+            && let Some(i) = self
+                .late_joiners
+                .iter()
+                .position(|x| !constraints.is_excluded(user.id_number, x.id_number))
+        {
+            let group = Group {
+                members: vec![self.late_joiners.remove(i), user],
+            };
+            for member_id in group.member_ids() {
+                self.joined_after_check_ins
+                    .insert(member_id, self.check_ins.len());
+            }
+            self.groups.push(PublishedGroup {
+                group: group.clone(),
+                thread_id: None,
+            });
+            return OutcomeLateJoin::PairedWithLateJoiner(group);
+        }
+        if let Some(i) = smallest_index {
+            let result = self.groups[i].clone();
+            self.joined_after_check_ins
+                .insert(user.id_number, self.check_ins.len());
+            self.groups[i].group.members.push(user);
+            return OutcomeLateJoin::JoinedGroup(result);
+        }
+        self.late_joiners.push(user);
+        OutcomeLateJoin::Waiting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+
+    use crate::model::cohort::check_in::CheckInStatus;
+
+    use super::*;
+
+    fn user(i: u64) -> UserRecord {
+        UserRecord {
+            id_number: UserId::new(i).into(),
+            name: format!("member {i}").into(),
+        }
+    }
+
+    /// Starts a check-in that only the members given respond to
+    fn check_in(record: &mut CohortRecord, responders: &[u64]) {
+        let now = UnixTimestamp::new(0);
+        let id = record.start_check_in(now).unwrap();
+        for &i in responders {
+            record
+                .check_in_mut(id, user(i).id_number)
+                .unwrap()
+                .set_status(user(i).id_number, CheckInStatus::OnTrack, now);
+        }
+    }
+
+    fn ids(members: &[u64]) -> Vec<UserIdNumber> {
+        members.iter().map(|&i| user(i).id_number).collect()
+    }
+
+    #[test]
+    fn flag_inactive_after_missing_limit_check_ins() {
+        let mut record = CohortRecord::default();
+        record.set_groups(&[Group {
+            members: vec![user(1), user(2)],
+        }]);
+        check_in(&mut record, &[1]);
+        assert!(record.flag_inactive(2).is_empty());
+        check_in(&mut record, &[1]);
+        assert_eq!(record.flag_inactive(2), ids(&[2]));
+        // Already flagged members are not returned again
+        assert!(record.flag_inactive(2).is_empty());
+        assert!(record.is_stranded(user(1).id_number));
+    }
+
+    #[test]
+    fn flag_inactive_ignores_check_ins_before_a_late_join() {
+        let mut record = CohortRecord::default();
+        record.set_groups(&[Group {
+            members: vec![user(1), user(2)],
+        }]);
+        check_in(&mut record, &[1, 2]);
+        check_in(&mut record, &[1, 2]);
+        let constraints = PairingConstraints::default();
+        assert!(matches!(
+            record.late_join(user(3), 3, &constraints),
+            OutcomeLateJoin::JoinedGroup(_)
+        ));
+        // The late joiner missed every check-in so far but none of them were after they joined
+        assert!(record.flag_inactive(2).is_empty());
+        check_in(&mut record, &[1, 2]);
+        assert!(record.flag_inactive(2).is_empty());
+        check_in(&mut record, &[1, 2]);
+        assert_eq!(record.flag_inactive(2), ids(&[3]));
+    }
+
+    #[test]
+    fn flag_inactive_ignores_check_ins_before_pairing_late_joiners() {
+        let mut record = CohortRecord::default();
+        record.set_groups(&[Group {
+            members: vec![user(1), user(2)],
+        }]);
+        let constraints = PairingConstraints::default();
+        assert!(matches!(
+            record.late_join(user(3), 2, &constraints),
+            OutcomeLateJoin::JoinedGroup(_)
+        ));
+        check_in(&mut record, &[1, 2, 3]);
+        // Every group is above the target size so the next late joiner waits
+        assert!(matches!(
+            record.late_join(user(4), 2, &constraints),
+            OutcomeLateJoin::Waiting
+        ));
+        check_in(&mut record, &[1, 2, 3]);
+        assert!(matches!(
+            record.late_join(user(5), 2, &constraints),
+            OutcomeLateJoin::PairedWithLateJoiner(_)
+        ));
+        assert!(record.flag_inactive(1).is_empty());
+        check_in(&mut record, &[1, 2, 3, 5]);
+        assert_eq!(record.flag_inactive(1), ids(&[4]));
+    }
+}
@@ -0,0 +1,153 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use poise::serenity_prelude::ChannelId;
+
+use crate::model::{
+    cohort::{
+        Cohort,
+        check_in::{CheckInId, CheckInStatus},
+        constraints::PairingConstraints,
+        feedback::FeedbackResponse,
+        grouping::Group,
+    },
+    schedule::UnixTimestamp,
+    user_serde::{UserIdNumber, UserRecord},
+};
+
+use super::{CohortRecord, OutcomeLateJoin, OutcomeLeave};
+
+impl Cohort {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_record(&self) -> anyhow::Result<MutexGuard<'_, CohortRecord>> {
+        match self.record.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_record(&self, data: &CohortRecord) -> anyhow::Result<()> {
+        self.save(CohortRecord::DATA_KEY, data)
+    }
+
+    /// Returns a copy of the record to use without holding the lock
+    pub fn record(&self) -> anyhow::Result<CohortRecord> {
+        let guard = self.guard_record()?;
+        Ok(guard.clone())
+    }
+
+    /// Clears the record for the next cohort and returns what it contained
+    pub fn record_take(&self) -> anyhow::Result<CohortRecord> {
+        let mut guard = self.guard_record()?;
+        let result = std::mem::take(&mut *guard);
+        self.save_record(&guard)?;
+        Ok(result)
+    }
+
+    pub fn record_groups_set(&self, groups: &[Group]) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard.set_groups(groups);
+        self.save_record(&guard)?;
+        Ok(())
+    }
+
+    pub fn record_thread_set(&self, group: &Group, thread_id: ChannelId) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard.set_thread(group, thread_id)?;
+        self.save_record(&guard)?;
+        Ok(())
+    }
+
+    /// Starts a new round of check-ins and returns its ID
+    pub fn record_check_in_start(&self, now: UnixTimestamp) -> anyhow::Result<CheckInId> {
+        let mut guard = self.guard_record()?;
+        let result = guard.start_check_in(now)?;
+        self.save_record(&guard)?;
+        Ok(result)
+    }
+
+    pub fn record_check_in_status_set(
+        &self,
+        id: CheckInId,
+        user_id_number: UserIdNumber,
+        status: CheckInStatus,
+        now: UnixTimestamp,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard
+            .check_in_mut(id, user_id_number)?
+            .set_status(user_id_number, status, now);
+        self.save_record(&guard)?;
+        Ok(())
+    }
+
+    pub fn record_check_in_note_set(
+        &self,
+        id: CheckInId,
+        user_id_number: UserIdNumber,
+        note: String,
+        now: UnixTimestamp,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard
+            .check_in_mut(id, user_id_number)?
+            .set_note(user_id_number, note, now);
+        self.save_record(&guard)?;
+        Ok(())
+    }
+
+    /// Returns the members that were newly flagged as inactive
+    pub fn record_flag_inactive(&self, limit: usize) -> anyhow::Result<Vec<UserIdNumber>> {
+        let mut guard = self.guard_record()?;
+        let result = guard.flag_inactive(limit);
+        self.save_record(&guard)?;
+        Ok(result)
+    }
+
+    pub fn record_rematch_request(&self, user_id_number: UserIdNumber) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard.request_rematch(user_id_number)?;
+        self.save_record(&guard)?;
+        Ok(())
+    }
+
+    pub fn record_rematch(&self, new_groups: &[Group]) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard.rematch(new_groups);
+        self.save_record(&guard)?;
+        Ok(())
+    }
+
+    pub fn record_leave(&self, user_id_number: UserIdNumber) -> anyhow::Result<OutcomeLeave> {
+        let mut guard = self.guard_record()?;
+        let result = guard.leave(user_id_number);
+        self.save_record(&guard)?;
+        Ok(result)
+    }
+
+    /// Takes a copy of the constraints because they are protected by a different lock
+    pub fn record_late_join(
+        &self,
+        user: UserRecord,
+        group_size: usize,
+        constraints: &PairingConstraints,
+    ) -> anyhow::Result<OutcomeLateJoin> {
+        let mut guard = self.guard_record()?;
+        let result = guard.late_join(user, group_size, constraints);
+        self.save_record(&guard)?;
+        Ok(result)
+    }
+
+    pub fn record_feedback_set(
+        &self,
+        user_id_number: UserIdNumber,
+        response: FeedbackResponse,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_record()?;
+        guard.set_feedback(user_id_number, response)?;
+        self.save_record(&guard)?;
+        Ok(())
+    }
+}
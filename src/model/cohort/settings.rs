@@ -0,0 +1,76 @@
+//! Settings that control how a cohort is run
+
+use std::fmt::Display;
+
+use crate::{
+    config::SharedConfig,
+    model::cohort::{eligibility::EligibilityRules, grouping::GroupSize, program::ProgramKey},
+};
+
+pub mod protected_ops;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct CohortSettings {
+    #[serde(default)]
+    pub group_size: GroupSize,
+    /// Number of check-ins in a row a member can miss before they are considered inactive
+    #[serde(default = "CohortSettings::default_missed_check_ins_limit")]
+    pub missed_check_ins_limit: u8,
+    /// Members that register once this many are registered go onto the waitlist
+    #[serde(default)]
+    pub max_participants: Option<u16>,
+    #[serde(default)]
+    pub eligibility: EligibilityRules,
+}
+
+impl CohortSettings {
+    pub const DISPLAY_TITLE: &'static str = "Cohort Settings";
+    const DATA_KEY: &'static str = "cohort_settings";
+
+    /// Uses `defaults` if the program has not saved any settings yet
+    pub async fn new(shared_config: &SharedConfig, key: &ProgramKey, defaults: &Self) -> Self {
+        shared_config
+            .load_or_else_kv(&key.data_key(Self::DATA_KEY), || defaults.clone())
+            .await
+    }
+
+    fn default_missed_check_ins_limit() -> u8 {
+        2
+    }
+
+    pub fn max_participants(&self) -> Option<usize> {
+        self.max_participants.map(usize::from)
+    }
+}
+
+impl Default for CohortSettings {
+    fn default() -> Self {
+        Self {
+            group_size: Default::default(),
+            missed_check_ins_limit: Self::default_missed_check_ins_limit(),
+            max_participants: None,
+            eligibility: Default::default(),
+        }
+    }
+}
+
+impl Display for CohortSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Group size: {} ({})",
+            self.group_size.get(),
+            self.group_size
+        )?;
+        writeln!(
+            f,
+            "Inactive after missing: {} check-ins",
+            self.missed_check_ins_limit
+        )?;
+        match self.max_participants {
+            Some(max) => writeln!(f, "Maximum participants: {max}")?,
+            None => writeln!(f, "Maximum participants: No limit")?,
+        }
+        write!(f, "{}", self.eligibility)
+    }
+}
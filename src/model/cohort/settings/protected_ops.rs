@@ -0,0 +1,93 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use tracing::info;
+
+use crate::model::{
+    cohort::{Cohort, eligibility::EligibilityRules, grouping::GroupSize},
+    user_serde::UserIdNumber,
+};
+
+use super::CohortSettings;
+
+impl Cohort {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_settings(&self) -> anyhow::Result<MutexGuard<'_, CohortSettings>> {
+        match self.settings.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_settings(&self, data: &CohortSettings) -> anyhow::Result<()> {
+        self.save(CohortSettings::DATA_KEY, data)
+    }
+
+    /// Returns a copy of the current settings
+    pub fn settings(&self) -> anyhow::Result<CohortSettings> {
+        let guard = self.guard_settings()?;
+        Ok(guard.clone())
+    }
+
+    pub fn settings_group_size_set(
+        &self,
+        user_id_number: UserIdNumber,
+        group_size: GroupSize,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_settings()?;
+        info!(
+            "User# {user_id_number} is changing group size from {:?} to {group_size:?}",
+            guard.group_size
+        );
+        guard.group_size = group_size;
+        self.save_settings(&guard)?;
+        Ok(())
+    }
+
+    pub fn settings_missed_check_ins_limit_set(
+        &self,
+        user_id_number: UserIdNumber,
+        limit: u8,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_settings()?;
+        info!(
+            "User# {user_id_number} is changing missed check-ins limit from {} to {limit}",
+            guard.missed_check_ins_limit
+        );
+        guard.missed_check_ins_limit = limit;
+        self.save_settings(&guard)?;
+        Ok(())
+    }
+
+    pub fn settings_max_participants_set(
+        &self,
+        user_id_number: UserIdNumber,
+        max_participants: Option<u16>,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_settings()?;
+        info!(
+            "User# {user_id_number} is changing max participants from {:?} to {max_participants:?}",
+            guard.max_participants
+        );
+        guard.max_participants = max_participants;
+        self.save_settings(&guard)?;
+        Ok(())
+    }
+
+    pub fn settings_eligibility_set(
+        &self,
+        user_id_number: UserIdNumber,
+        eligibility: EligibilityRules,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.guard_settings()?;
+        info!(
+            "User# {user_id_number} is changing eligibility rules from {:?} to {eligibility:?}",
+            guard.eligibility
+        );
+        guard.eligibility = eligibility;
+        self.save_settings(&guard)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Calculates participation statistics from the archive of past cohorts
+
+use std::{collections::BTreeSet, fmt::Display};
+
+use crate::model::{
+    cohort::{
+        archive::{CohortArchive, CohortNumber},
+        feedback::GoalOutcome,
+    },
+    user_serde::UserIdNumber,
+};
+
+/// A member's participation over all archived cohorts
+///
+/// Streaks count archived cohorts in a row whatever the time between them
+#[derive(Debug, Default)]
+pub struct UserStats {
+    pub cohorts_joined: usize,
+    /// Consecutive cohorts joined up to and including the most recent one
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    /// Cohorts where the member said they met their goal in the feedback
+    pub goals_met: usize,
+    pub distinct_partners: usize,
+}
+
+/// Participation in a single cohort for showing the trend over time
+#[derive(Debug)]
+pub struct CohortParticipation {
+    pub number: CohortNumber,
+    pub members: usize,
+    /// Members that had not been in any earlier cohort
+    pub new_members: usize,
+    pub groups: usize,
+    pub feedback_responses: usize,
+    pub goals_met: usize,
+}
+
+impl UserStats {
+    pub fn new(archive: &CohortArchive, user_id_number: UserIdNumber) -> Self {
+        let mut result = Self::default();
+        let mut partners = BTreeSet::new();
+        let mut streak = 0;
+        for cohort in archive.cohorts() {
+            let Some(group) = cohort.group_of(user_id_number) else {
+                streak = 0;
+                continue;
+            };
+            result.cohorts_joined += 1;
+            streak += 1;
+            result.longest_streak = result.longest_streak.max(streak);
+            partners.extend(group.member_ids().filter(|&x| x != user_id_number));
+            if cohort
+                .feedback
+                .get(&user_id_number)
+                .is_some_and(|x| x.goal_outcome == GoalOutcome::Met)
+            {
+                result.goals_met += 1;
+            }
+        }
+        result.current_streak = streak;
+        result.distinct_partners = partners.len();
+        result
+    }
+}
+
+impl Display for UserStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Cohorts joined: {}", self.cohorts_joined)?;
+        writeln!(
+            f,
+            "Current streak: {} cohorts in a row",
+            self.current_streak
+        )?;
+        writeln!(
+            f,
+            "Longest streak: {} cohorts in a row",
+            self.longest_streak
+        )?;
+        writeln!(f, "Goals met: {}", self.goals_met)?;
+        write!(f, "Different partners: {}", self.distinct_partners)
+    }
+}
+
+/// Returns the participation in each archived cohort in order
+pub fn participation_trend(archive: &CohortArchive) -> Vec<CohortParticipation> {
+    let mut seen = BTreeSet::new();
+    archive
+        .cohorts()
+        .iter()
+        .enumerate()
+        .map(|(i, cohort)| {
+            let member_ids: Vec<UserIdNumber> =
+                cohort.groups.iter().flat_map(|x| x.member_ids()).collect();
+            let new_members = member_ids.iter().filter(|&&x| seen.insert(x)).count();
+            CohortParticipation {
+                number: CohortNumber::from_index(i),
+                members: member_ids.len(),
+                new_members,
+                groups: cohort.groups.len(),
+                feedback_responses: cohort.feedback.len(),
+                goals_met: cohort
+                    .feedback
+                    .values()
+                    .filter(|x| x.goal_outcome == GoalOutcome::Met)
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+impl Display for CohortParticipation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}. {} members ({} new, {} returning) in {} groups - goals met: {} of {} responses",
+            self.number,
+            self.members,
+            self.new_members,
+            self.members - self.new_members,
+            self.groups,
+            self.goals_met,
+            self.feedback_responses
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+
+    use super::*;
+    use crate::model::{
+        cohort::{archive::ArchivedCohort, grouping::Group},
+        schedule::UnixTimestamp,
+        user_serde::UserRecord,
+    };
+
+    fn user(i: u64) -> UserRecord {
+        UserRecord {
+            id_number: UserId::new(i).into(),
+            name: format!("member {i}").into(),
+        }
+    }
+
+    /// Builds an archive with one cohort for each entry, each a single group of the members given
+    fn archive(cohorts: &[&[u64]]) -> CohortArchive {
+        let mut result = CohortArchive::default();
+        for (i, members) in cohorts.iter().enumerate() {
+            result.add(ArchivedCohort {
+                archived: UnixTimestamp::new(i as i32),
+                members: vec![],
+                groups: vec![Group {
+                    members: members.iter().map(|&x| user(x)).collect(),
+                }],
+                check_ins: vec![],
+                feedback: Default::default(),
+            });
+        }
+        result
+    }
+
+    #[test]
+    fn gap_ends_the_streak() {
+        let archive = archive(&[&[1, 2], &[1, 3], &[1, 2], &[2, 3], &[1, 4]]);
+
+        let stats = UserStats::new(&archive, user(1).id_number);
+
+        assert_eq!(stats.cohorts_joined, 4);
+        assert_eq!(stats.longest_streak, 3);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.distinct_partners, 3);
+    }
+
+    #[test]
+    fn missing_the_last_cohort_has_no_current_streak() {
+        let archive = archive(&[&[1, 2], &[2, 3], &[1, 2], &[1, 3], &[2, 3]]);
+
+        let stats = UserStats::new(&archive, user(1).id_number);
+
+        assert_eq!(stats.cohorts_joined, 3);
+        assert_eq!(stats.longest_streak, 2);
+        assert_eq!(stats.current_streak, 0);
+    }
+
+    #[test]
+    fn never_joined_has_no_stats() {
+        let archive = archive(&[&[1, 2]]);
+
+        let stats = UserStats::new(&archive, user(5).id_number);
+
+        assert_eq!(stats.cohorts_joined, 0);
+        assert_eq!(stats.longest_streak, 0);
+        assert_eq!(stats.distinct_partners, 0);
+    }
+}
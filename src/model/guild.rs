@@ -0,0 +1,317 @@
+//! Configuration for each server (guild) the bot serves
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use poise::serenity_prelude::{ChannelId, GuildId, Mentionable as _, RoleId};
+
+use crate::{
+    config::SharedConfig,
+    errors::bail_user,
+    model::{
+        cohort::{grouping::GroupSize, settings::CohortSettings},
+        guild::permissions::PermissionMap,
+        utc_offset::UtcOffset,
+    },
+};
+
+pub mod permissions;
+pub mod protected_ops;
+
+/// The settings a server needs before cohorts can be run in it
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct GuildConfig {
+    pub guild_id: GuildId,
+    /// The roles that can run privileged commands
+    pub auth_role_ids: Vec<RoleId>,
+    /// The channel used by the default program
+    pub channel_cohort: ChannelId,
+    pub channel_admin: Option<ChannelId>,
+    /// Where privileged actions are posted as they are recorded in the audit log
+    #[serde(default)]
+    pub channel_audit: Option<ChannelId>,
+    /// Where to announce that the bot has started
+    #[serde(default)]
+    pub channel_startup: Option<ChannelId>,
+    pub role_cohort: Option<RoleId>,
+    pub role_veteran: Option<RoleId>,
+    /// Number of completed cohorts needed to be given `role_veteran`
+    #[serde(default = "GuildConfig::default_veteran_cohorts")]
+    pub veteran_cohorts: usize,
+    /// Used to show times in the server's local time
+    #[serde(default)]
+    pub timezone: UtcOffset,
+    /// Settings given to programs when they are first created in the server
+    #[serde(default)]
+    pub cohort_defaults: CohortSettings,
+}
+
+/// Values set at runtime with `/config`, these take precedence over the environment (home server) or `/setup`
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct GuildConfigOverrides {
+    pub channel_cohort: Option<ChannelId>,
+    pub auth_role_ids: Option<Vec<RoleId>>,
+    pub channel_audit: Option<ChannelId>,
+    pub channel_startup: Option<ChannelId>,
+    pub timezone: Option<UtcOffset>,
+    pub group_size: Option<GroupSize>,
+    pub missed_check_ins_limit: Option<u8>,
+    /// 0 means there is no limit
+    pub max_participants: Option<u16>,
+}
+
+impl GuildConfig {
+    /// The home server is configured using the environment
+    pub fn from_shared_config(guild_id: GuildId, shared_config: &SharedConfig) -> Self {
+        Self {
+            guild_id,
+            auth_role_ids: vec![shared_config.auth_role_id],
+            channel_cohort: shared_config.channel_unranked,
+            channel_admin: shared_config.channel_admin,
+            channel_audit: None,
+            channel_startup: shared_config.channel_startup,
+            role_cohort: shared_config.role_cohort,
+            role_veteran: shared_config.role_veteran,
+            veteran_cohorts: shared_config.veteran_cohorts,
+            timezone: Default::default(),
+            cohort_defaults: Default::default(),
+        }
+    }
+
+    fn with_overrides(mut self, overrides: &GuildConfigOverrides) -> Self {
+        if let Some(channel_id) = overrides.channel_cohort {
+            self.channel_cohort = channel_id;
+        }
+        if let Some(role_ids) = overrides.auth_role_ids.as_ref() {
+            self.auth_role_ids = role_ids.clone();
+        }
+        if let Some(channel_id) = overrides.channel_audit {
+            self.channel_audit = Some(channel_id);
+        }
+        if let Some(channel_id) = overrides.channel_startup {
+            self.channel_startup = Some(channel_id);
+        }
+        if let Some(timezone) = overrides.timezone {
+            self.timezone = timezone;
+        }
+        if let Some(group_size) = overrides.group_size {
+            self.cohort_defaults.group_size = group_size;
+        }
+        if let Some(limit) = overrides.missed_check_ins_limit {
+            self.cohort_defaults.missed_check_ins_limit = limit;
+        }
+        if let Some(max) = overrides.max_participants {
+            self.cohort_defaults.max_participants = (max > 0).then_some(max);
+        }
+        self
+    }
+
+    fn default_veteran_cohorts() -> usize {
+        SharedConfig::DEFAULT_VETERAN_COHORTS
+    }
+}
+
+/// The configuration of every server that has been set up
+pub struct GuildConfigs {
+    home_guild_id: GuildId,
+    guilds: BTreeMap<GuildId, GuildConfig>,
+    overrides: BTreeMap<GuildId, GuildConfigOverrides>,
+    permissions: BTreeMap<GuildId, PermissionMap>,
+}
+
+/// What is saved to be able to restore the configuration after a restart
+///
+/// The home server is not included as its configuration comes from the environment
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct GuildConfigList {
+    guilds: Vec<GuildConfig>,
+    #[serde(default)]
+    overrides: Vec<(GuildId, GuildConfigOverrides)>,
+    #[serde(default)]
+    permissions: Vec<(GuildId, PermissionMap)>,
+}
+
+impl GuildConfigs {
+    const DATA_KEY: &'static str = "guild_configs";
+
+    pub async fn new(shared_config: &SharedConfig, home_guild_id: GuildId) -> Self {
+        let list: GuildConfigList = shared_config.load_or_default_kv(Self::DATA_KEY).await;
+        let mut guilds: BTreeMap<GuildId, GuildConfig> =
+            list.guilds.into_iter().map(|x| (x.guild_id, x)).collect();
+        guilds.insert(
+            home_guild_id,
+            GuildConfig::from_shared_config(home_guild_id, shared_config),
+        );
+        Self {
+            home_guild_id,
+            guilds,
+            overrides: list.overrides.into_iter().collect(),
+            permissions: list.permissions.into_iter().collect(),
+        }
+    }
+
+    /// Returns the configuration in effect (with any overrides applied)
+    pub fn get(&self, guild_id: GuildId) -> Option<GuildConfig> {
+        let config = self.guilds.get(&guild_id)?.clone();
+        Some(match self.overrides.get(&guild_id) {
+            Some(overrides) => config.with_overrides(overrides),
+            None => config,
+        })
+    }
+
+    pub fn all(&self) -> Vec<GuildConfig> {
+        self.guilds
+            .keys()
+            .filter_map(|&guild_id| self.get(guild_id))
+            .collect()
+    }
+
+    /// Changes the values set at runtime and returns the configuration that is now in effect
+    pub fn set_overrides(
+        &mut self,
+        guild_id: GuildId,
+        f: impl FnOnce(&mut GuildConfigOverrides),
+    ) -> anyhow::Result<GuildConfig> {
+        if !self.guilds.contains_key(&guild_id) {
+            bail_user!("This server has not been set up yet, see `/setup`");
+        }
+        f(self.overrides.entry(guild_id).or_default());
+        Ok(self
+            .get(guild_id)
+            .expect("server was confirmed to be set up above"))
+    }
+
+    /// Returns the capabilities given in the server (empty if none have been given)
+    pub fn permissions(&self, guild_id: GuildId) -> PermissionMap {
+        self.permissions.get(&guild_id).cloned().unwrap_or_default()
+    }
+
+    /// Changes the capabilities given in the server and returns the result of `f`
+    pub fn update_permissions<T>(
+        &mut self,
+        guild_id: GuildId,
+        f: impl FnOnce(&mut PermissionMap) -> T,
+    ) -> anyhow::Result<T> {
+        if !self.guilds.contains_key(&guild_id) {
+            bail_user!("This server has not been set up yet, see `/setup`");
+        }
+        Ok(f(self.permissions.entry(guild_id).or_default()))
+    }
+
+    /// Lists the configuration in effect and which values have been changed with `/config`
+    pub fn guild_as_string(&self, guild_id: GuildId) -> anyhow::Result<String> {
+        let Some(config) = self.get(guild_id) else {
+            bail_user!("This server has not been set up yet, see `/setup`");
+        };
+        let overrides = self.overrides.get(&guild_id).cloned().unwrap_or_default();
+        let source = |is_set: bool| if is_set { " *" } else { "" };
+        let channel_opt = |x: Option<ChannelId>| match x {
+            Some(channel_id) => channel_id.mention().to_string(),
+            None => "Not set".to_string(),
+        };
+        let role_opt = |x: Option<RoleId>| match x {
+            Some(role_id) => role_id.mention().to_string(),
+            None => "Not set".to_string(),
+        };
+        let auth_roles: Vec<String> = config
+            .auth_role_ids
+            .iter()
+            .map(|x| x.mention().to_string())
+            .collect();
+        let mut result = String::new();
+        writeln!(
+            result,
+            "Cohort channel: {}{}",
+            config.channel_cohort.mention(),
+            source(overrides.channel_cohort.is_some())
+        )?;
+        writeln!(
+            result,
+            "Admin roles: {}{}",
+            auth_roles.join(", "),
+            source(overrides.auth_role_ids.is_some())
+        )?;
+        writeln!(
+            result,
+            "Admin channel: {}",
+            channel_opt(config.channel_admin)
+        )?;
+        writeln!(
+            result,
+            "Audit channel: {}{}",
+            channel_opt(config.channel_audit),
+            source(overrides.channel_audit.is_some())
+        )?;
+        writeln!(
+            result,
+            "Startup channel: {}{}",
+            channel_opt(config.channel_startup),
+            source(overrides.channel_startup.is_some())
+        )?;
+        writeln!(result, "Cohort role: {}", role_opt(config.role_cohort))?;
+        writeln!(
+            result,
+            "Veteran role: {} (after {} cohorts)",
+            role_opt(config.role_veteran),
+            config.veteran_cohorts
+        )?;
+        writeln!(
+            result,
+            "Timezone: {}{}",
+            config.timezone,
+            source(overrides.timezone.is_some())
+        )?;
+        let defaults = &config.cohort_defaults;
+        writeln!(
+            result,
+            "Default group size: {}{}",
+            defaults.group_size.get(),
+            source(overrides.group_size.is_some())
+        )?;
+        writeln!(
+            result,
+            "Default inactive after missing: {} check-ins{}",
+            defaults.missed_check_ins_limit,
+            source(overrides.missed_check_ins_limit.is_some())
+        )?;
+        writeln!(
+            result,
+            "Default maximum participants: {}{}",
+            defaults
+                .max_participants
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "No limit".to_string()),
+            source(overrides.max_participants.is_some())
+        )?;
+        write!(result, "\n\\* Set with `/config`")?;
+        Ok(result)
+    }
+
+    pub fn add(&mut self, config: GuildConfig) -> anyhow::Result<()> {
+        if self.guilds.contains_key(&config.guild_id) {
+            bail_user!("This server has already been set up");
+        }
+        self.guilds.insert(config.guild_id, config);
+        Ok(())
+    }
+
+    fn to_list(&self) -> GuildConfigList {
+        GuildConfigList {
+            guilds: self
+                .guilds
+                .values()
+                .filter(|x| x.guild_id != self.home_guild_id)
+                .cloned()
+                .collect(),
+            overrides: self
+                .overrides
+                .iter()
+                .map(|(&guild_id, overrides)| (guild_id, overrides.clone()))
+                .collect(),
+            permissions: self
+                .permissions
+                .iter()
+                .map(|(&guild_id, permissions)| (guild_id, permissions.clone()))
+                .collect(),
+        }
+    }
+}
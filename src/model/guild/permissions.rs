@@ -0,0 +1,184 @@
+//! Named capabilities that can be given to roles and individual members for the privileged commands
+//!
+//! Members with one of the admin roles can always use every command
+
+use std::fmt::Display;
+
+use poise::serenity_prelude::{Mentionable as _, RoleId, UserId};
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter,
+)]
+pub enum Capability {
+    #[name = "Manage schedule"]
+    ManageSchedule,
+    #[name = "Manage cohort"]
+    ManageCohort,
+    #[name = "Moderate members"]
+    ModerateMembers,
+    #[name = "Configure bot"]
+    ConfigureBot,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::ManageSchedule,
+        Capability::ManageCohort,
+        Capability::ModerateMembers,
+        Capability::ConfigureBot,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Capability::ManageSchedule => "Schedule and cancel events and check-ins",
+            Capability::ManageCohort => "Run the cohorts (groups, programs, settings and resets)",
+            Capability::ModerateMembers => "Add, remove and ban members and view the audit log",
+            Capability::ConfigureBot => "Change the server configuration and permissions",
+        }
+    }
+}
+
+/// Who a capability is given to
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Grantee {
+    Role(RoleId),
+    User(UserId),
+}
+
+/// The capabilities given in one server
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct PermissionMap {
+    grants: Vec<(Capability, Grantee)>,
+}
+
+impl PermissionMap {
+    /// Returns false if the grantee already had the capability
+    pub fn grant(&mut self, capability: Capability, grantee: Grantee) -> bool {
+        if self.grants.contains(&(capability, grantee)) {
+            return false;
+        }
+        self.grants.push((capability, grantee));
+        true
+    }
+
+    /// Returns false if the grantee did not have the capability
+    pub fn revoke(&mut self, capability: Capability, grantee: Grantee) -> bool {
+        let before = self.grants.len();
+        self.grants.retain(|x| x != &(capability, grantee));
+        self.grants.len() != before
+    }
+
+    /// True if the member or any of their roles has been given the capability
+    pub fn allows(&self, capability: Capability, user_id: UserId, roles: &[RoleId]) -> bool {
+        self.grants.iter().any(|(x, grantee)| {
+            *x == capability
+                && match grantee {
+                    Grantee::Role(role_id) => roles.contains(role_id),
+                    Grantee::User(x) => *x == user_id,
+                }
+        })
+    }
+}
+
+impl Display for Grantee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Grantee::Role(role_id) => write!(f, "{}", role_id.mention()),
+            Grantee::User(user_id) => write!(f, "{}", user_id.mention()),
+        }
+    }
+}
+
+impl Display for PermissionMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use poise::ChoiceParameter as _;
+        for capability in Capability::ALL {
+            let grantees: Vec<String> = self
+                .grants
+                .iter()
+                .filter(|(x, _)| *x == capability)
+                .map(|(_, grantee)| grantee.to_string())
+                .collect();
+            writeln!(
+                f,
+                "**{}** - {}\n{}",
+                capability.name(),
+                capability.description(),
+                if grantees.is_empty() {
+                    "Admin roles only".to_string()
+                } else {
+                    grantees.join(", ")
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMBER: UserId = UserId::new(1);
+    const OTHER_MEMBER: UserId = UserId::new(2);
+    const ROLE: RoleId = RoleId::new(10);
+    const OTHER_ROLE: RoleId = RoleId::new(11);
+
+    #[test]
+    fn role_grant_allows_members_with_the_role() {
+        let mut permissions = PermissionMap::default();
+        assert!(permissions.grant(Capability::ManageSchedule, Grantee::Role(ROLE)));
+
+        assert!(permissions.allows(Capability::ManageSchedule, MEMBER, &[OTHER_ROLE, ROLE]));
+        assert!(!permissions.allows(Capability::ManageSchedule, MEMBER, &[OTHER_ROLE]));
+        assert!(!permissions.allows(Capability::ManageSchedule, MEMBER, &[]));
+    }
+
+    #[test]
+    fn user_grant_allows_only_that_member() {
+        let mut permissions = PermissionMap::default();
+        assert!(permissions.grant(Capability::ModerateMembers, Grantee::User(MEMBER)));
+
+        assert!(permissions.allows(Capability::ModerateMembers, MEMBER, &[]));
+        assert!(!permissions.allows(Capability::ModerateMembers, OTHER_MEMBER, &[ROLE]));
+    }
+
+    #[test]
+    fn grant_does_not_allow_other_capabilities() {
+        let mut permissions = PermissionMap::default();
+        permissions.grant(Capability::ManageCohort, Grantee::Role(ROLE));
+        permissions.grant(Capability::ManageCohort, Grantee::User(MEMBER));
+
+        for capability in Capability::ALL {
+            assert_eq!(
+                permissions.allows(capability, MEMBER, &[ROLE]),
+                capability == Capability::ManageCohort
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_grant_is_ignored() {
+        let mut permissions = PermissionMap::default();
+        assert!(permissions.grant(Capability::ConfigureBot, Grantee::User(MEMBER)));
+        assert!(!permissions.grant(Capability::ConfigureBot, Grantee::User(MEMBER)));
+
+        // A single revoke removes it as it was only stored once
+        assert!(permissions.revoke(Capability::ConfigureBot, Grantee::User(MEMBER)));
+        assert!(!permissions.allows(Capability::ConfigureBot, MEMBER, &[]));
+    }
+
+    #[test]
+    fn revoke_removes_only_that_grant() {
+        let mut permissions = PermissionMap::default();
+        permissions.grant(Capability::ManageSchedule, Grantee::Role(ROLE));
+        permissions.grant(Capability::ManageSchedule, Grantee::User(MEMBER));
+
+        assert!(permissions.revoke(Capability::ManageSchedule, Grantee::Role(ROLE)));
+        assert!(!permissions.revoke(Capability::ManageSchedule, Grantee::Role(ROLE)));
+        assert!(!permissions.revoke(Capability::ManageCohort, Grantee::User(MEMBER)));
+
+        assert!(!permissions.allows(Capability::ManageSchedule, OTHER_MEMBER, &[ROLE]));
+        assert!(permissions.allows(Capability::ManageSchedule, MEMBER, &[]));
+    }
+}
@@ -0,0 +1,115 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::MutexGuard;
+
+use poise::serenity_prelude::GuildId;
+use tracing::{info, instrument};
+
+use crate::{Data, errors::bail_user};
+
+use super::{
+    GuildConfig, GuildConfigOverrides, GuildConfigs,
+    permissions::{Capability, Grantee, PermissionMap},
+};
+
+impl Data {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_guild_configs(&self) -> anyhow::Result<MutexGuard<'_, GuildConfigs>> {
+        match self.inner.guild_configs.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_guild_configs(&self, data: &GuildConfigs) -> anyhow::Result<()> {
+        self.save(GuildConfigs::DATA_KEY, &data.to_list())
+    }
+
+    /// Returns a copy of the server's configuration if it has been set up
+    pub fn guild_config(&self, guild_id: GuildId) -> anyhow::Result<Option<GuildConfig>> {
+        let guard = self.guard_guild_configs()?;
+        Ok(guard.get(guild_id))
+    }
+
+    /// Like [`Data::guild_config`] but treats a server that has not been set up as an error
+    pub fn guild_config_required(&self, guild_id: GuildId) -> anyhow::Result<GuildConfig> {
+        let guard = self.guard_guild_configs()?;
+        match guard.get(guild_id) {
+            Some(config) => Ok(config),
+            None => bail_user!("This server has not been set up yet, see `/setup`"),
+        }
+    }
+
+    pub fn guild_configs_all(&self) -> anyhow::Result<Vec<GuildConfig>> {
+        let guard = self.guard_guild_configs()?;
+        Ok(guard.all())
+    }
+
+    #[instrument(skip(self))]
+    pub fn guild_configs_add(&self, config: GuildConfig) -> anyhow::Result<()> {
+        info!("START");
+        let mut guard = self.guard_guild_configs()?;
+        guard.add(config)?;
+        self.save_guild_configs(&guard)?;
+        info!("END");
+        Ok(())
+    }
+
+    /// Changes the values set at runtime with `/config` and returns the configuration now in effect
+    #[instrument(skip(self, f))]
+    pub fn guild_configs_set_overrides(
+        &self,
+        guild_id: GuildId,
+        f: impl FnOnce(&mut GuildConfigOverrides),
+    ) -> anyhow::Result<GuildConfig> {
+        info!("START");
+        let mut guard = self.guard_guild_configs()?;
+        let result = guard.set_overrides(guild_id, f)?;
+        self.save_guild_configs(&guard)?;
+        info!("END");
+        Ok(result)
+    }
+
+    pub fn guild_permissions(&self, guild_id: GuildId) -> anyhow::Result<PermissionMap> {
+        let guard = self.guard_guild_configs()?;
+        Ok(guard.permissions(guild_id))
+    }
+
+    /// Returns false if the grantee already had the capability
+    #[instrument(skip(self))]
+    pub fn guild_permissions_grant(
+        &self,
+        guild_id: GuildId,
+        capability: Capability,
+        grantee: Grantee,
+    ) -> anyhow::Result<bool> {
+        info!("START");
+        let mut guard = self.guard_guild_configs()?;
+        let result = guard.update_permissions(guild_id, |x| x.grant(capability, grantee))?;
+        self.save_guild_configs(&guard)?;
+        info!("END");
+        Ok(result)
+    }
+
+    /// Returns false if the grantee did not have the capability
+    #[instrument(skip(self))]
+    pub fn guild_permissions_revoke(
+        &self,
+        guild_id: GuildId,
+        capability: Capability,
+        grantee: Grantee,
+    ) -> anyhow::Result<bool> {
+        info!("START");
+        let mut guard = self.guard_guild_configs()?;
+        let result = guard.update_permissions(guild_id, |x| x.revoke(capability, grantee))?;
+        self.save_guild_configs(&guard)?;
+        info!("END");
+        Ok(result)
+    }
+
+    pub fn guild_config_as_string(&self, guild_id: GuildId) -> anyhow::Result<String> {
+        let guard = self.guard_guild_configs()?;
+        guard.guild_as_string(guild_id)
+    }
+}
@@ -0,0 +1,192 @@
+//! Keeps track of the cohort programs that are running at the same time in each server
+
+use std::sync::Arc;
+
+use anyhow::bail;
+use poise::serenity_prelude::{ChannelId, GuildId};
+
+use crate::{
+    config::SharedConfig,
+    errors::bail_user,
+    model::{
+        cohort::{
+            Cohort,
+            program::{ProgramKey, ProgramName},
+        },
+        guild::GuildConfig,
+    },
+};
+
+pub mod protected_ops;
+
+/// The programs that are currently running, every server that has been set up has a default program
+pub struct Programs {
+    cohorts: Vec<Arc<Cohort>>,
+}
+
+/// What is saved to be able to recreate the programs after a restart
+///
+/// The default programs are not included as their channels come from the server configuration
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub struct ProgramList {
+    programs: Vec<ProgramConfig>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ProgramConfig {
+    /// Programs saved before multiple servers were supported belong to the home server
+    #[serde(default)]
+    guild_id: Option<GuildId>,
+    name: ProgramName,
+    channel_id: ChannelId,
+}
+
+impl Programs {
+    pub const DISPLAY_TITLE: &'static str = "Cohort Programs";
+    const DATA_KEY: &'static str = "programs";
+
+    pub async fn new(
+        shared_config: &'static SharedConfig,
+        guild_configs: &[GuildConfig],
+        home_guild_id: GuildId,
+    ) -> Self {
+        let list: ProgramList = shared_config.load_or_default_kv(Self::DATA_KEY).await;
+        let mut cohorts = vec![];
+        for config in guild_configs {
+            let key = ProgramKey {
+                guild_id: config.guild_id,
+                name: ProgramName::default(),
+                is_home_guild: config.guild_id == home_guild_id,
+            };
+            cohorts.push(Arc::new(
+                Cohort::new(
+                    shared_config,
+                    key,
+                    config.channel_cohort,
+                    &config.cohort_defaults,
+                )
+                .await,
+            ));
+        }
+        for config in list.programs {
+            if config.name.is_default() {
+                continue;
+            }
+            let guild_id = config.guild_id.unwrap_or(home_guild_id);
+            let key = ProgramKey {
+                guild_id,
+                name: config.name,
+                is_home_guild: guild_id == home_guild_id,
+            };
+            let defaults = guild_configs
+                .iter()
+                .find(|x| x.guild_id == guild_id)
+                .map(|x| x.cohort_defaults.clone())
+                .unwrap_or_default();
+            cohorts.push(Arc::new(
+                Cohort::new(shared_config, key, config.channel_id, &defaults).await,
+            ));
+        }
+        Self { cohorts }
+    }
+
+    pub fn get(&self, guild_id: GuildId, name: &str) -> Option<Arc<Cohort>> {
+        self.cohorts
+            .iter()
+            .find(|x| x.guild_id() == guild_id && x.program().matches(name))
+            .cloned()
+    }
+
+    pub fn find_by_channel(&self, channel_id: ChannelId) -> Option<Arc<Cohort>> {
+        self.cohorts
+            .iter()
+            .find(|x| x.channel_id() == channel_id)
+            .cloned()
+    }
+
+    /// Returns the default program of the server if it has been set up
+    pub fn default_program(&self, guild_id: GuildId) -> Option<Arc<Cohort>> {
+        self.cohorts
+            .iter()
+            .find(|x| x.guild_id() == guild_id && x.program().is_default())
+            .cloned()
+    }
+
+    pub fn names(&self, guild_id: GuildId) -> Vec<ProgramName> {
+        self.cohorts
+            .iter()
+            .filter(|x| x.guild_id() == guild_id)
+            .map(|x| x.program().clone())
+            .collect()
+    }
+
+    pub fn add(&mut self, cohort: Cohort) -> anyhow::Result<Arc<Cohort>> {
+        if self
+            .get(cohort.guild_id(), cohort.program().as_str())
+            .is_some()
+        {
+            bail!(
+                "A program named {:?} already exists",
+                cohort.program().as_str()
+            );
+        }
+        if let Some(existing) = self.find_by_channel(cohort.channel_id()) {
+            bail!(
+                "The channel is already used by the {:?} program",
+                existing.program().as_str()
+            );
+        }
+        let cohort = Arc::new(cohort);
+        self.cohorts.push(Arc::clone(&cohort));
+        Ok(cohort)
+    }
+
+    /// Removes the program, its data is kept so it comes back if it is added again
+    pub fn remove(&mut self, guild_id: GuildId, name: &str) -> anyhow::Result<Arc<Cohort>> {
+        let Some(index) = self
+            .cohorts
+            .iter()
+            .position(|x| x.guild_id() == guild_id && x.program().matches(name))
+        else {
+            bail_user!("No program named {name:?} found");
+        };
+        if self.cohorts[index].program().is_default() {
+            bail_user!("The default program cannot be removed");
+        }
+        Ok(self.cohorts.remove(index))
+    }
+
+    fn to_list(&self) -> ProgramList {
+        ProgramList {
+            programs: self
+                .cohorts
+                .iter()
+                .filter(|x| !x.program().is_default())
+                .map(|x| ProgramConfig {
+                    guild_id: Some(x.guild_id()),
+                    name: x.program().clone(),
+                    channel_id: x.channel_id(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Lists the programs running in the server
+    fn guild_as_string(&self, guild_id: GuildId) -> String {
+        use std::fmt::Write as _;
+        let mut result = String::new();
+        for cohort in self.cohorts.iter().filter(|x| x.guild_id() == guild_id) {
+            let _ = write!(
+                result,
+                "- **{}** in <#{}>",
+                cohort.program(),
+                cohort.channel_id()
+            );
+            if cohort.program().is_default() {
+                result.push_str(" (default)");
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
@@ -0,0 +1,87 @@
+//! This module exists to make it harder to get deadlocks by grouping functions that MUST NOT call each other.
+//! Makes use of the fact that it is a sub-module to access the private function to implement its functionality
+
+use std::sync::{Arc, MutexGuard};
+
+use poise::serenity_prelude::{ChannelId, GuildId};
+use tracing::{info, instrument};
+
+use crate::{
+    Data,
+    errors::bail_user,
+    model::cohort::{Cohort, program::ProgramName},
+};
+
+use super::Programs;
+
+impl Data {
+    /// Serves as the link to the private function that returns the guard
+    fn guard_programs(&self) -> anyhow::Result<MutexGuard<'_, Programs>> {
+        match self.inner.programs.lock() {
+            Ok(guard) => Ok(guard),
+            Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
+        }
+    }
+
+    fn save_programs(&self, data: &Programs) -> anyhow::Result<()> {
+        self.save(Programs::DATA_KEY, &data.to_list())
+    }
+
+    /// Returns the server's program with the given name (ignoring case)
+    pub fn programs_get(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+    ) -> anyhow::Result<Option<Arc<Cohort>>> {
+        let guard = self.guard_programs()?;
+        Ok(guard.get(guild_id, name))
+    }
+
+    /// Returns the program that uses the channel if there is one
+    pub fn programs_find_by_channel(
+        &self,
+        channel_id: ChannelId,
+    ) -> anyhow::Result<Option<Arc<Cohort>>> {
+        let guard = self.guard_programs()?;
+        Ok(guard.find_by_channel(channel_id))
+    }
+
+    /// Returns the server's default program or an error if the server has not been set up
+    pub fn programs_default(&self, guild_id: GuildId) -> anyhow::Result<Arc<Cohort>> {
+        let guard = self.guard_programs()?;
+        match guard.default_program(guild_id) {
+            Some(cohort) => Ok(cohort),
+            None => bail_user!("This server has not been set up yet, see `/setup`"),
+        }
+    }
+
+    pub fn programs_names(&self, guild_id: GuildId) -> anyhow::Result<Vec<ProgramName>> {
+        let guard = self.guard_programs()?;
+        Ok(guard.names(guild_id))
+    }
+
+    #[instrument(skip(self, cohort), fields(program = ?cohort.key()))]
+    pub fn programs_add(&self, cohort: Cohort) -> anyhow::Result<Arc<Cohort>> {
+        info!("START");
+        let mut guard = self.guard_programs()?;
+        let result = guard.add(cohort)?;
+        self.save_programs(&guard)?;
+        info!("END");
+        Ok(result)
+    }
+
+    #[instrument(skip(self))]
+    pub fn programs_remove(&self, guild_id: GuildId, name: &str) -> anyhow::Result<Arc<Cohort>> {
+        info!("START");
+        let mut guard = self.guard_programs()?;
+        let result = guard.remove(guild_id, name)?;
+        self.save_programs(&guard)?;
+        info!("END");
+        Ok(result)
+    }
+
+    pub fn programs_as_string(&self, guild_id: GuildId) -> anyhow::Result<String> {
+        let guard = self.guard_programs()?;
+        Ok(guard.guild_as_string(guild_id))
+    }
+}
@@ -1,7 +1,13 @@
 use super::one_based_id::OneBasedId;
-use crate::{Data, commands::do_start_event};
+use crate::{
+    Data,
+    commands::{Effects, do_objective},
+    errors::bail_user,
+    model::{cohort::program::ProgramName, utc_offset::UtcOffset},
+};
 use anyhow::{Context, bail};
 use human_time::ToHumanTimeString;
+use poise::serenity_prelude::GuildId;
 use std::{
     fmt::Display,
     time::{Duration, UNIX_EPOCH},
@@ -12,12 +18,27 @@ use tracing::{error, info, instrument, warn};
 pub mod protected_ops;
 pub type ScheduledTaskId = OneBasedId;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone, Copy)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone, Copy, PartialEq, Eq)]
 pub struct UnixTimestamp(i32);
 impl UnixTimestamp {
     pub fn new(value: i32) -> Self {
         Self(value)
     }
+
+    pub fn now() -> anyhow::Result<Self> {
+        let seconds_since_epoch = UNIX_EPOCH
+            .elapsed()
+            .context("failed to get timestamp. System date before Unix Epoch?")?
+            .as_secs();
+        let seconds_since_epoch: i32 = seconds_since_epoch
+            .try_into()
+            .context("failed to convert system time as seconds since epoch into i32")?;
+        Ok(Self(seconds_since_epoch))
+    }
+
+    pub fn as_secs(&self) -> i32 {
+        self.0
+    }
 }
 
 impl Display for UnixTimestamp {
@@ -41,9 +62,24 @@ impl ScheduledTasks {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Copy, poise::ChoiceParameter,
+)]
 pub enum Objective {
+    #[name = "Unranked start event"]
     UnrankedStartEvent,
+    #[name = "Weekly check-in"]
+    CohortCheckIn,
+}
+
+impl Objective {
+    /// Returns how long to wait before running again for objectives that repeat
+    pub fn repeat_interval(&self) -> Option<Duration> {
+        match self {
+            Objective::UnrankedStartEvent => None,
+            Objective::CohortCheckIn => Some(Duration::from_secs(7 * 24 * 60 * 60)),
+        }
+    }
 }
 
 pub enum OutcomeCreateScheduledTask {
@@ -57,10 +93,25 @@ pub enum OutcomeCreateScheduledTask {
 pub struct ScheduledTask {
     pub desired_execution_timestamp: UnixTimestamp,
     pub objective: Objective,
+    /// Tasks saved before servers were tracked belong to the home server (set when hydrating)
+    #[serde(default)]
+    pub guild_id: Option<GuildId>,
+    /// Tasks saved before programs existed belong to the default program
+    #[serde(default)]
+    pub program: ProgramName,
     #[serde(skip)]
     task: Option<JoinHandle<()>>,
 }
 
+/// Identifies a task by what it does rather than its position in the list (which changes as tasks
+/// are added and removed)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledTaskKey {
+    pub objective: Objective,
+    pub program: ProgramName,
+    pub desired_execution_timestamp: UnixTimestamp,
+}
+
 enum OutcomeSpawnTask {
     SucceededReplaced,
     SucceededFromEmpty,
@@ -72,7 +123,10 @@ impl ScheduledTask {
     fn spawn_task(&mut self, data: Data) -> anyhow::Result<OutcomeSpawnTask> {
         info!("START");
         let had_handle = if let Some(old_handle) = self.task.take() {
-            info!("Aborting previous handle for {}", self.objective);
+            info!(
+                "Aborting previous handle for {} ({})",
+                self.objective, self.program
+            );
             old_handle.abort();
             true
         } else {
@@ -92,22 +146,16 @@ impl ScheduledTask {
     /// Spawns a new task and saves the join handle
     /// Previous task should already be aborted and cleared
     /// as any currently stored handle will be lost
-    #[instrument(skip(self, data) fields(self.objective = %self.objective, self.desired_execution_timestamp = ?self.desired_execution_timestamp))]
+    #[instrument(skip(self, data) fields(self.objective = %self.objective, self.program = %self.program, self.desired_execution_timestamp = ?self.desired_execution_timestamp))]
     fn do_spawn(&mut self, data: Data) -> anyhow::Result<()> {
         let objective = self.objective;
+        let program = self.program.clone();
+        let guild_id = self.guild_id.unwrap_or(data.inner.home_guild_id);
         debug_assert!(
             self.task.is_none(),
             "task should have been aborted already if it existed"
         );
-        let seconds_since_epoch = UNIX_EPOCH
-            .elapsed()
-            .context("failed to get timestamp. System date before Unix Epoch?")?
-            .as_secs();
-        let seconds_since_epoch: i32 = seconds_since_epoch
-            .try_into()
-            .context("failed to convert system time as seconds since epoch into i32")?;
-        info!(seconds_since_epoch);
-        let timestamp_now = UnixTimestamp::new(seconds_since_epoch);
+        let timestamp_now = UnixTimestamp::now()?;
         info!("timestamp_now={timestamp_now:?}");
         let seconds_to_desired = self.desired_execution_timestamp.0 - timestamp_now.0;
         info!(seconds_to_desired);
@@ -136,15 +184,22 @@ impl ScheduledTask {
             info!("sleeping task has woken up with objective: {objective}");
 
             // Do the objective
-            let cmd_result = match objective {
-                Objective::UnrankedStartEvent => {
-                    do_start_event(
-                        data.inner.ctx.clone(),
-                        data.inner.shared_config.channel_unranked,
-                        &data,
-                    )
-                    .await
-                }
+            let cmd_result = match data.programs_get(guild_id, program.as_str()) {
+                Ok(Some(cohort)) => match data.guild_config(guild_id) {
+                    Ok(config) => {
+                        do_objective(
+                            &Effects::Live(&data.inner.ctx),
+                            objective,
+                            cohort.channel_id(),
+                            config.as_ref(),
+                            &cohort,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                },
+                Ok(None) => Err(anyhow::anyhow!("program {program:?} no longer exists")),
+                Err(e) => Err(e),
             };
 
             // Check result of objective
@@ -153,21 +208,65 @@ impl ScheduledTask {
                 Err(e) => error!("failed to accomplish objective with error: {e:?}"),
             }
 
-            // Remove task from list (We can only do this as we are running from a different task as the mutex is locked rn and we would create a deadlock if this were on the same execution path)
-            if let Err(e) = data.schedule_cancel_task_by_objective(objective) {
+            // Remove task from list or schedule the next run if it repeats (We can only do this as we are running from a different task as the mutex is locked rn and we would create a deadlock if this were on the same execution path)
+            if objective.repeat_interval().is_some() {
+                if let Err(e) =
+                    data.schedule_repeat_task_by_objective(objective, guild_id, &program)
+                {
+                    error!("failed to schedule the next run of the task with error: {e:?}");
+                }
+            } else if let Err(e) =
+                data.schedule_cancel_task_by_objective(objective, guild_id, &program)
+            {
                 error!("failed to remove the task from with error: {e:?}");
             }
         }));
         Ok(())
     }
 
-    fn new(objective: Objective, desired_execution_timestamp: UnixTimestamp) -> Self {
+    /// Moves the execution time forward by the repeat interval and spawns the next run
+    ///
+    /// Expected to be called from the task itself after it finishes so the current handle is
+    /// dropped instead of aborted
+    #[instrument(skip(self, data))]
+    fn respawn_next(&mut self, data: Data) -> anyhow::Result<()> {
+        let interval = self
+            .objective
+            .repeat_interval()
+            .with_context(|| format!("{} does not repeat", self.objective))?;
+        let interval: i32 = interval
+            .as_secs()
+            .try_into()
+            .context("failed to convert repeat interval into i32")?;
+        let timestamp_now = UnixTimestamp::now()?;
+        let mut next = self.desired_execution_timestamp.0 + interval;
+        while next <= timestamp_now.0 {
+            // Skip runs that were missed
+            next += interval;
+        }
+        self.desired_execution_timestamp = UnixTimestamp::new(next);
+        self.task = None;
+        self.do_spawn(data)
+    }
+
+    fn new(
+        objective: Objective,
+        guild_id: GuildId,
+        program: ProgramName,
+        desired_execution_timestamp: UnixTimestamp,
+    ) -> Self {
         Self {
             desired_execution_timestamp,
             objective,
+            guild_id: Some(guild_id),
+            program,
             task: None,
         }
     }
+
+    fn is_for(&self, guild_id: GuildId, program: &ProgramName) -> bool {
+        self.guild_id == Some(guild_id) && &self.program == program
+    }
 }
 
 impl ScheduledTasks {
@@ -177,32 +276,43 @@ impl ScheduledTasks {
     pub fn create_task(
         &mut self,
         objective: Objective,
+        guild_id: GuildId,
+        program: ProgramName,
         desired_execution_timestamp: UnixTimestamp,
         data: Data,
     ) -> anyhow::Result<OutcomeCreateScheduledTask> {
-        if let Some(existing) = self.find_task(objective) {
+        if let Some(existing) = self.find_task(objective, guild_id, &program) {
             let prev_timestamp = existing.desired_execution_timestamp;
             existing.desired_execution_timestamp = desired_execution_timestamp;
             existing.spawn_task(data)?;
             Ok(OutcomeCreateScheduledTask::Replaced(prev_timestamp))
         } else {
-            let mut task = ScheduledTask::new(objective, desired_execution_timestamp);
+            let mut task =
+                ScheduledTask::new(objective, guild_id, program, desired_execution_timestamp);
             task.spawn_task(data)?;
             self.data.push(task);
             Ok(OutcomeCreateScheduledTask::Created)
         }
     }
 
-    pub fn find_task(&mut self, objective: Objective) -> Option<&mut ScheduledTask> {
+    pub fn find_task(
+        &mut self,
+        objective: Objective,
+        guild_id: GuildId,
+        program: &ProgramName,
+    ) -> Option<&mut ScheduledTask> {
         self.data
             .iter_mut()
-            .find(|task| task.objective == objective)
+            .find(|task| task.objective == objective && task.is_for(guild_id, program))
     }
 
     /// Creates the tasks from the saved data after restarting the application
     #[instrument(skip(self, data))]
     pub fn hydrate(&mut self, data: Data) {
         info!("START");
+        for task in self.data.iter_mut() {
+            task.guild_id.get_or_insert(data.inner.home_guild_id);
+        }
         for i in (0..self.data.len()).rev() {
             match self.data[i].spawn_task(data.clone()) {
                 Ok(_) => (),
@@ -218,27 +328,107 @@ impl ScheduledTasks {
         info!("END");
     }
 
+    /// Removes and stops the task if it has not changed since the key was taken
     #[instrument(skip(self))]
-    pub fn cancel_task_by_id(&mut self, id: ScheduledTaskId) -> anyhow::Result<ScheduledTask> {
+    pub fn cancel_task_by_key(
+        &mut self,
+        guild_id: GuildId,
+        key: &ScheduledTaskKey,
+    ) -> anyhow::Result<ScheduledTask> {
         info!("START");
-        let index = id.as_index();
-        if index < self.data.len() {
-            info!("ENDING with removal");
-            Ok(self.data.remove(index))
-        } else {
-            warn!("ENDING with out of bounds");
-            bail!("Invalid ID received for cancel of {id}");
+        let Some(index) = self.data.iter().position(|task| {
+            task.is_for(guild_id, &key.program)
+                && task.objective == key.objective
+                && task.desired_execution_timestamp == key.desired_execution_timestamp
+        }) else {
+            warn!("ENDING with task not found");
+            bail_user!(
+                "{} for {} is no longer scheduled at that time so nothing was cancelled, see `/schedule display`",
+                key.objective,
+                key.program
+            );
+        };
+        let result = self.data.remove(index);
+        if let Some(handle) = result.task.as_ref() {
+            handle.abort();
+        }
+        info!("ENDING with removal");
+        Ok(result)
+    }
+
+    /// Describes the task and returns its key without changing it (used to confirm before cancelling)
+    pub fn task_by_id(
+        &self,
+        guild_id: GuildId,
+        id: ScheduledTaskId,
+    ) -> anyhow::Result<(ScheduledTaskKey, String)> {
+        let task = &self.data[self.index_by_id(guild_id, id)?];
+        let key = ScheduledTaskKey {
+            objective: task.objective,
+            program: task.program.clone(),
+            desired_execution_timestamp: task.desired_execution_timestamp,
+        };
+        Ok((key, task.to_string()))
+    }
+
+    /// Describes the tasks for the program without changing them (used to confirm before removing it)
+    pub fn tasks_by_program(&self, guild_id: GuildId, program: &ProgramName) -> Vec<String> {
+        self.data
+            .iter()
+            .filter(|task| task.is_for(guild_id, program))
+            .map(|task| task.to_string())
+            .collect()
+    }
+
+    /// IDs are the position of the task among the server's tasks as shown by [`Self::guild_as_string`]
+    fn index_by_id(&self, guild_id: GuildId, id: ScheduledTaskId) -> anyhow::Result<usize> {
+        let index = self
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| task.guild_id == Some(guild_id))
+            .nth(id.as_index())
+            .map(|(i, _)| i);
+        match index {
+            Some(index) => Ok(index),
+            None => {
+                warn!("out of bounds id: {id}");
+                bail_user!("Invalid ID received for cancel of {id}");
+            }
         }
     }
 
+    #[instrument(skip(self, data))]
+    pub fn repeat_task_by_objective(
+        &mut self,
+        objective: Objective,
+        guild_id: GuildId,
+        program: &ProgramName,
+        data: Data,
+    ) -> anyhow::Result<UnixTimestamp> {
+        info!("START");
+        let task = self
+            .find_task(objective, guild_id, program)
+            .with_context(|| {
+                format!(
+                    "Unable to find any scheduled task with objective: {objective} for {program}"
+                )
+            })?;
+        task.respawn_next(data)?;
+        info!("END");
+        Ok(task.desired_execution_timestamp)
+    }
+
     #[instrument(skip(self))]
     pub fn cancel_task_by_objective(
         &mut self,
         objective: Objective,
+        guild_id: GuildId,
+        program: &ProgramName,
     ) -> anyhow::Result<ScheduledTask> {
         info!("START");
         let index = self.data.iter().enumerate().find_map(|(i, task)| {
-            if task.objective == objective {
+            if task.objective == objective && task.is_for(guild_id, program) {
                 Some(i)
             } else {
                 None
@@ -249,8 +439,51 @@ impl ScheduledTasks {
             Ok(self.data.remove(index))
         } else {
             warn!("ENDING with objective not found");
-            bail!("Unable to find any scheduled task with objective: {objective}");
+            bail!("Unable to find any scheduled task with objective: {objective} for {program}");
+        }
+    }
+}
+
+impl ScheduledTasks {
+    /// Removes and stops all the tasks for the program
+    #[instrument(skip(self))]
+    pub fn cancel_tasks_by_program(
+        &mut self,
+        guild_id: GuildId,
+        program: &ProgramName,
+    ) -> Vec<ScheduledTask> {
+        info!("START");
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.data)
+            .into_iter()
+            .partition(|task| task.is_for(guild_id, program));
+        self.data = kept;
+        for task in removed.iter() {
+            if let Some(handle) = task.task.as_ref() {
+                handle.abort();
+            }
+        }
+        info!("END removed {} tasks", removed.len());
+        removed
+    }
+
+    /// Lists the tasks for one server numbered by the IDs used to cancel them
+    ///
+    /// Times are also shown in the server's timezone for members reading the list outside of Discord
+    pub fn guild_as_string(&self, guild_id: GuildId, timezone: UtcOffset) -> String {
+        let mut result = String::new();
+        for (i, task) in self
+            .data
+            .iter()
+            .filter(|task| task.guild_id == Some(guild_id))
+            .enumerate()
+        {
+            result.push_str(&format!(
+                "{}. {task} ({})\n",
+                i + 1,
+                timezone.format(task.desired_execution_timestamp)
+            ));
         }
+        result
     }
 }
 
@@ -261,6 +494,7 @@ impl Display for Objective {
             "{}",
             match self {
                 Objective::UnrankedStartEvent => "UnrankedStartEvent",
+                Objective::CohortCheckIn => "CohortCheckIn",
             }
         )
     }
@@ -270,8 +504,8 @@ impl Display for ScheduledTask {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Objective: {}, Scheduled for {}",
-            self.objective, self.desired_execution_timestamp
+            "Objective: {}, Program: {}, Scheduled for {}",
+            self.objective, self.program, self.desired_execution_timestamp
         )
     }
 }
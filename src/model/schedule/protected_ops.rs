@@ -1,18 +1,22 @@
 use std::sync::MutexGuard;
 
 use anyhow::Context;
+use poise::serenity_prelude::GuildId;
 use tracing::{error, info, instrument};
 
-use crate::Data;
+use crate::{
+    Data,
+    model::{cohort::program::ProgramName, utc_offset::UtcOffset},
+};
 
 use super::{
-    Objective, OutcomeCreateScheduledTask, ScheduledTask, ScheduledTaskId, ScheduledTasks,
-    UnixTimestamp,
+    Objective, OutcomeCreateScheduledTask, ScheduledTask, ScheduledTaskId, ScheduledTaskKey,
+    ScheduledTasks, UnixTimestamp,
 };
 
 impl Data {
     /// Serves as the link to the private function that returns the guard
-    fn guard_schedule(&self) -> anyhow::Result<MutexGuard<ScheduledTasks>> {
+    fn guard_schedule(&self) -> anyhow::Result<MutexGuard<'_, ScheduledTasks>> {
         match self.inner.schedule_tasks.lock() {
             Ok(guard) => Ok(guard),
             Err(e) => anyhow::bail!("failed to lock mutex because '{e}"),
@@ -24,35 +28,98 @@ impl Data {
     }
 
     #[instrument(skip(self))]
-    /// Add a new task to the scheduled tasks and replaces if a task with the same objective exists
+    /// Add a new task to the scheduled tasks and replaces if a task with the same objective and program exists
     pub fn schedule_create_task(
         &self,
         objective: Objective,
+        guild_id: GuildId,
+        program: ProgramName,
         desired_execution_timestamp: UnixTimestamp,
     ) -> anyhow::Result<OutcomeCreateScheduledTask> {
         let mut guard = self.guard_schedule()?;
-        let result = guard.create_task(objective, desired_execution_timestamp, self.clone())?;
+        let result = guard.create_task(
+            objective,
+            guild_id,
+            program,
+            desired_execution_timestamp,
+            self.clone(),
+        )?;
+        self.save_scheduled_tasks(&guard)?;
+        Ok(result)
+    }
+
+    #[instrument(skip(self))]
+    pub fn schedule_cancel_task_by_key(
+        &self,
+        guild_id: GuildId,
+        key: &ScheduledTaskKey,
+    ) -> anyhow::Result<ScheduledTask> {
+        info!("START");
+        let mut guard = self.guard_schedule()?;
+        let result = guard.cancel_task_by_key(guild_id, key)?;
         self.save_scheduled_tasks(&guard)?;
+        info!("END");
         Ok(result)
     }
 
+    pub fn schedule_task_by_id(
+        &self,
+        guild_id: GuildId,
+        id: ScheduledTaskId,
+    ) -> anyhow::Result<(ScheduledTaskKey, String)> {
+        let guard = self.guard_schedule()?;
+        guard.task_by_id(guild_id, id)
+    }
+
+    pub fn schedule_tasks_by_program(
+        &self,
+        guild_id: GuildId,
+        program: &ProgramName,
+    ) -> anyhow::Result<Vec<String>> {
+        let guard = self.guard_schedule()?;
+        Ok(guard.tasks_by_program(guild_id, program))
+    }
+
     #[instrument(skip(self))]
-    pub fn schedule_cancel_task_by_id(&self, id: ScheduledTaskId) -> anyhow::Result<ScheduledTask> {
+    /// Schedules the next run of a repeating task
+    pub fn schedule_repeat_task_by_objective(
+        &self,
+        objective: Objective,
+        guild_id: GuildId,
+        program: &ProgramName,
+    ) -> anyhow::Result<UnixTimestamp> {
         info!("START");
         let mut guard = self.guard_schedule()?;
-        let result = guard.cancel_task_by_id(id)?;
+        let result = guard.repeat_task_by_objective(objective, guild_id, program, self.clone())?;
         self.save_scheduled_tasks(&guard)?;
         info!("END");
         Ok(result)
     }
+
     #[instrument(skip(self))]
     pub fn schedule_cancel_task_by_objective(
         &self,
         objective: Objective,
+        guild_id: GuildId,
+        program: &ProgramName,
     ) -> anyhow::Result<ScheduledTask> {
         info!("START");
         let mut guard = self.guard_schedule()?;
-        let result = guard.cancel_task_by_objective(objective)?;
+        let result = guard.cancel_task_by_objective(objective, guild_id, program)?;
+        self.save_scheduled_tasks(&guard)?;
+        info!("END");
+        Ok(result)
+    }
+
+    #[instrument(skip(self))]
+    pub fn schedule_cancel_tasks_by_program(
+        &self,
+        guild_id: GuildId,
+        program: &ProgramName,
+    ) -> anyhow::Result<Vec<ScheduledTask>> {
+        info!("START");
+        let mut guard = self.guard_schedule()?;
+        let result = guard.cancel_tasks_by_program(guild_id, program);
         self.save_scheduled_tasks(&guard)?;
         info!("END");
         Ok(result)
@@ -75,8 +142,12 @@ impl Data {
     }
 
     #[instrument(skip(self))]
-    pub fn schedule_as_string(&self) -> anyhow::Result<String> {
+    pub fn schedule_as_string(
+        &self,
+        guild_id: GuildId,
+        timezone: UtcOffset,
+    ) -> anyhow::Result<String> {
         let guard = self.guard_schedule()?;
-        Ok(guard.to_string())
+        Ok(guard.guild_as_string(guild_id, timezone))
     }
 }
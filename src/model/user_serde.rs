@@ -8,7 +8,19 @@ use crate::{AuthorPreferredDisplay as _, Context};
 
 /// Created to use in place of User or UserId from Framework because they
 /// are not able to be deserialized from Bincode which shuttle-persist uses
-#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
 pub struct UserIdNumber(u64);
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone)]
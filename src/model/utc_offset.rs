@@ -0,0 +1,177 @@
+//! Parses and shows the fixed UTC offset a server uses to display scheduled times
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Context as _, bail};
+
+use super::schedule::UnixTimestamp;
+
+/// A fixed offset from UTC used to show times in a server's timezone
+///
+/// Stored in minutes so offsets that are not whole hours (e.g. `+05:30`) are supported
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UtcOffset(i16);
+
+impl UtcOffset {
+    const MAX_MINUTES: i16 = 14 * 60;
+
+    /// Formats the timestamp as the date and time in this timezone (e.g. `2025-03-01 18:30 UTC+02:00`)
+    pub fn format(self, timestamp: UnixTimestamp) -> String {
+        let local = i64::from(timestamp.as_secs()) + i64::from(self.0) * 60;
+        let days = local.div_euclid(24 * 60 * 60);
+        let seconds_of_day = local.rem_euclid(24 * 60 * 60);
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{year:04}-{month:02}-{day:02} {:02}:{:02} {self}",
+            seconds_of_day / 3600,
+            seconds_of_day % 3600 / 60
+        )
+    }
+}
+
+/// Converts days since the Unix epoch into a (year, month, day) date
+///
+/// Based on <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl FromStr for UtcOffset {
+    type Err = anyhow::Error;
+
+    /// Accepts `UTC`, `UTC+2`, `-4`, `+05:30` and `UTC-03:30` (the `UTC` prefix is optional)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let offset = trimmed
+            .strip_prefix("UTC")
+            .or_else(|| trimmed.strip_prefix("utc"))
+            .unwrap_or(trimmed);
+        if offset.is_empty() {
+            return Ok(Self(0));
+        }
+        let (sign, rest) = if let Some(rest) = offset.strip_prefix('+') {
+            (1, rest)
+        } else if let Some(rest) = offset.strip_prefix('-') {
+            (-1, rest)
+        } else {
+            bail!("Timezone must look like UTC+2 or UTC-03:30 but got {s:?}");
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: u8 = hours
+            .parse()
+            .with_context(|| format!("invalid hours in timezone {s:?}"))?;
+        let minutes: u8 = minutes
+            .parse()
+            .with_context(|| format!("invalid minutes in timezone {s:?}"))?;
+        if minutes >= 60 {
+            bail!("Minutes in timezone must be less than 60 but got {minutes}");
+        }
+        let total = sign * (i16::from(hours) * 60 + i16::from(minutes));
+        if total.abs() > Self::MAX_MINUTES {
+            bail!("Timezone must be between UTC-14:00 and UTC+14:00 but got {s:?}");
+        }
+        Ok(Self(total))
+    }
+}
+
+impl Display for UtcOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "UTC");
+        }
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        let minutes = self.0.abs();
+        write!(f, "UTC{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> anyhow::Result<i16> {
+        Ok(s.parse::<UtcOffset>()?.0)
+    }
+
+    #[test]
+    fn civil_from_days_known_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(10_957), (2000, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(20_147), (2025, 2, 28));
+        assert_eq!(civil_from_days(20_148), (2025, 3, 1));
+        assert_eq!(civil_from_days(-719_468), (0, 3, 1));
+    }
+
+    #[test]
+    fn parse_valid_offsets() {
+        assert_eq!(parse("UTC").unwrap(), 0);
+        assert_eq!(parse("utc").unwrap(), 0);
+        assert_eq!(parse(" UTC ").unwrap(), 0);
+        assert_eq!(parse("UTC+2").unwrap(), 120);
+        assert_eq!(parse("+2").unwrap(), 120);
+        assert_eq!(parse("-4").unwrap(), -240);
+        assert_eq!(parse("UTC-4").unwrap(), -240);
+        assert_eq!(parse("+05:30").unwrap(), 330);
+        assert_eq!(parse("UTC+05:30").unwrap(), 330);
+        assert_eq!(parse("UTC-03:30").unwrap(), -210);
+        assert_eq!(parse("+14").unwrap(), 14 * 60);
+        assert_eq!(parse("-14:00").unwrap(), -14 * 60);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_offsets() {
+        for s in [
+            "2", "UTC2", "GMT+1", "+", "UTC+", "+x", "+1:x", "+05:60", "+14:01", "-15", "+300",
+            "+1:2:3",
+        ] {
+            assert!(parse(s).is_err(), "{s:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for (s, expected) in [
+            ("UTC", "UTC"),
+            ("+0", "UTC"),
+            ("UTC+2", "UTC+02:00"),
+            ("-4", "UTC-04:00"),
+            ("+05:30", "UTC+05:30"),
+            ("UTC-03:30", "UTC-03:30"),
+        ] {
+            let offset: UtcOffset = s.parse().unwrap();
+            assert_eq!(offset.to_string(), expected);
+            assert_eq!(expected.parse::<UtcOffset>().unwrap(), offset);
+        }
+    }
+
+    #[test]
+    fn format_shows_local_date_and_time() {
+        // 2025-03-01 16:30 UTC
+        let timestamp = UnixTimestamp::new(1_740_846_600);
+        let format = |s: &str| s.parse::<UtcOffset>().unwrap().format(timestamp);
+        assert_eq!(format("UTC"), "2025-03-01 16:30 UTC");
+        assert_eq!(format("+2"), "2025-03-01 18:30 UTC+02:00");
+        assert_eq!(format("-03:30"), "2025-03-01 13:00 UTC-03:30");
+        assert_eq!(format("+08"), "2025-03-02 00:30 UTC+08:00");
+        assert_eq!(format("-14"), "2025-03-01 02:30 UTC-14:00");
+        let midnight = UnixTimestamp::new(1_740_787_200);
+        assert_eq!(
+            "-1".parse::<UtcOffset>().unwrap().format(midnight),
+            "2025-02-28 23:00 UTC-01:00"
+        );
+    }
+}
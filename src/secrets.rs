@@ -1,10 +1,40 @@
 //! This modules handles access to all the secret information
+//!
+//! Values are taken from the environment first, then from a file named by the `<NAME>_FILE`
+//! environment variable (for secrets mounted by Docker or Kubernetes) and then from the config file
+//! (see [`config_file`])
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use secrecy::SecretString;
-use std::str::FromStr;
-use tracing::warn;
+use std::{fmt::Write as _, path::PathBuf, str::FromStr, sync::OnceLock};
 
+use self::config_file::ConfigFile;
+
+pub mod config_file;
+
+static CONFIG_FILE: OnceLock<ConfigFile> = OnceLock::new();
+
+/// Loads the config file so its values can be used as a fallback for the environment
+///
+/// Expected to be called once at startup after the environment has been loaded
+pub fn load_config_file() -> anyhow::Result<()> {
+    let config_file = ConfigFile::load()?;
+    if CONFIG_FILE.set(config_file).is_err() {
+        bail!("config file was already loaded");
+    }
+    Ok(())
+}
+
+/// Where a configuration value was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueSource {
+    Environment,
+    /// The file named by the `<NAME>_FILE` environment variable
+    SecretFile(PathBuf),
+    ConfigFile,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub enum KeyName {
     DiscordToken,
 
@@ -19,6 +49,18 @@ pub enum KeyName {
 
     /// The chanel to use for the startup message
     StartupMsgChannel,
+
+    /// The channel to use for messages meant only for admins
+    AdminChannel,
+
+    /// The role given to members taking part in the current cohort
+    CohortRoleId,
+
+    /// The role given to members that have completed enough cohorts
+    VeteranRoleId,
+
+    /// The number of completed cohorts needed for the veteran role
+    VeteranCohorts,
 }
 
 impl AsRef<str> for KeyName {
@@ -29,21 +71,93 @@ impl AsRef<str> for KeyName {
             KeyName::AuthRoleId => "AUTH_ROLE_ID",
             KeyName::CohortChannel => "COHORT_CHANNEL",
             KeyName::StartupMsgChannel => "STARTUP_MSG_CHANNEL",
+            KeyName::AdminChannel => "ADMIN_CHANNEL",
+            KeyName::CohortRoleId => "COHORT_ROLE_ID",
+            KeyName::VeteranRoleId => "VETERAN_ROLE_ID",
+            KeyName::VeteranCohorts => "VETERAN_COHORTS",
         }
     }
 }
 
 impl KeyName {
-    pub fn get_secret_string(&self) -> anyhow::Result<SecretString> {
-        Ok(SecretString::new(
-            get_env_var(self.as_ref())
-                .with_context(|| format!("failed to get environment variable: {}", self.as_ref()))?
-                .into(),
-        ))
+    pub const ALL: [KeyName; 9] = [
+        KeyName::DiscordToken,
+        KeyName::TestGuildId,
+        KeyName::AuthRoleId,
+        KeyName::CohortChannel,
+        KeyName::StartupMsgChannel,
+        KeyName::AdminChannel,
+        KeyName::CohortRoleId,
+        KeyName::VeteranRoleId,
+        KeyName::VeteranCohorts,
+    ];
+
+    /// The name used for the value in the config file (e.g. `cohort_channel`)
+    pub fn file_key(&self) -> String {
+        self.as_ref().to_lowercase()
+    }
+
+    /// Secret values are never shown when printing the configuration
+    pub fn is_secret(&self) -> bool {
+        matches!(self, KeyName::DiscordToken)
+    }
+
+    /// The environment variable that can hold the path of a file containing the value (e.g. `TOKEN_FILE`)
+    pub fn file_env_var(&self) -> String {
+        format!("{}_FILE", self.as_ref())
     }
 
-    pub fn get_non_secret_string(&self) -> anyhow::Result<String> {
-        get_env_var(self.as_ref())
+    /// Looks for the value in the environment first, then the file named by [`Self::file_env_var`]
+    /// and then the config file
+    fn lookup(&self) -> anyhow::Result<Option<(String, ValueSource)>> {
+        let value = get_env_var(self.as_ref())?;
+        let file_env_var = self.file_env_var();
+        let secret_path = get_env_var(&file_env_var)?.map(PathBuf::from);
+        match (value, secret_path) {
+            (Some(_), Some(_)) => bail!(
+                "{} and {file_env_var} are both set but only one of them can be used",
+                self.as_ref()
+            ),
+            (Some(value), None) => return Ok(Some((value, ValueSource::Environment))),
+            (None, Some(path)) => {
+                let contents = std::fs::read_to_string(&path).with_context(|| {
+                    format!(
+                        "{} could not be read from {} (set by {file_env_var})",
+                        self.as_ref(),
+                        path.display()
+                    )
+                })?;
+                // Files written by editors and `echo` usually end in a newline which is not part of the value
+                let value = contents.trim_end_matches(['\n', '\r']).to_string();
+                return Ok(Some((value, ValueSource::SecretFile(path))));
+            }
+            (None, None) => (),
+        }
+        Ok(CONFIG_FILE
+            .get()
+            .and_then(|x| x.get(&self.file_key()))
+            .map(|value| (value.to_string(), ValueSource::ConfigFile)))
+    }
+
+    fn missing_error(&self) -> anyhow::Error {
+        let file = match CONFIG_FILE.get().and_then(|x| x.path()) {
+            Some(path) => format!("in {}", path.display()),
+            None => "in the config file".to_string(),
+        };
+        anyhow::anyhow!(
+            "{} is missing: set the {} or {} environment variable or `{}` {file}",
+            self.as_ref(),
+            self.as_ref(),
+            self.file_env_var(),
+            self.file_key()
+        )
+    }
+
+    pub fn get_secret_string(&self) -> anyhow::Result<SecretString> {
+        match self.lookup()? {
+            Some((value, _)) => Ok(SecretString::new(value.into())),
+            None => Err(self.missing_error()),
+        }
     }
 
     pub fn get_non_secret_parse<F, E>(&self) -> anyhow::Result<F>
@@ -51,29 +165,73 @@ impl KeyName {
         E: 'static + Send + Sync + std::error::Error,
         F: FromStr<Err = E>,
     {
-        self.get_non_secret_string()?
-            .parse()
-            .with_context(|| format!("failed to parse environment variable: {}", self.as_ref()))
+        match self.get_non_secret_parse_opt()? {
+            Some(x) => Ok(x),
+            None => Err(self.missing_error()),
+        }
     }
 
-    pub fn get_non_secret_parse_opt<F, E>(&self) -> Option<F>
+    /// Returns None only if the value is not set, a value that is set but can't be parsed is an error
+    pub fn get_non_secret_parse_opt<F, E>(&self) -> anyhow::Result<Option<F>>
     where
         E: 'static + Send + Sync + std::error::Error,
         F: FromStr<Err = E>,
     {
-        match self.get_non_secret_parse() {
-            Ok(x) => Some(x),
-            Err(_) => {
-                warn!(
-                    "failed to optionally load {}. Defaulting to use None instead",
-                    self.as_ref(),
-                );
-                None
+        let Some((value, source)) = self.lookup()? else {
+            return Ok(None);
+        };
+        let parsed = value.trim().parse().with_context(|| {
+            format!(
+                "{} could not be parsed from the {} value {value:?}",
+                self.as_ref(),
+                source
+            )
+        })?;
+        Ok(Some(parsed))
+    }
+
+    /// Lists every setting with where its value came from, secret values are redacted
+    pub fn effective_config_redacted() -> anyhow::Result<String> {
+        let mut result = String::new();
+        for key in KeyName::ALL {
+            match key.lookup() {
+                Ok(Some((value, source))) => {
+                    let value = if key.is_secret() {
+                        // The length helps spot empty values or extra whitespace without showing the secret
+                        format!("[REDACTED {} characters]", value.chars().count())
+                    } else {
+                        format!("{value:?}")
+                    };
+                    writeln!(result, "{} = {value} (from {source})", key.as_ref())?;
+                }
+                Ok(None) => writeln!(result, "{} is not set", key.as_ref())?,
+                Err(e) => writeln!(result, "{} is invalid: {e:#}", key.as_ref())?,
             }
         }
+        Ok(result)
     }
 }
 
-fn get_env_var(key: &str) -> anyhow::Result<String> {
-    std::env::var(key).with_context(|| format!("failed to get environment variable: {key}"))
+impl std::fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueSource::Environment => write!(f, "environment variable"),
+            ValueSource::SecretFile(path) => write!(f, "file {}", path.display()),
+            ValueSource::ConfigFile => match CONFIG_FILE.get().and_then(|x| x.path()) {
+                Some(path) => write!(f, "config file {}", path.display()),
+                None => write!(f, "config file"),
+            },
+        }
+    }
+}
+
+/// Returns None if the variable is not set
+fn get_env_var(key: &str) -> anyhow::Result<Option<String>> {
+    match std::env::var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => {
+            bail!("{key} could not be parsed: the environment variable is not valid unicode")
+        }
+    }
 }
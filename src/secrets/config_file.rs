@@ -0,0 +1,161 @@
+//! Reads settings from a TOML file so they don't all need to be set as environment variables
+//!
+//! The file uses the lowercase form of each [`KeyName`] (e.g. `cohort_channel = 123`) and the
+//! environment takes precedence over it
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, bail};
+use tracing::info;
+
+use super::KeyName;
+
+/// Environment variable used to choose the file, if not set [`ConfigFile::DEFAULT_PATH`] is used when it exists
+const PATH_ENV_VAR: &str = "CONFIG_FILE";
+
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    /// None if no file was loaded
+    path: Option<PathBuf>,
+    values: BTreeMap<String, String>,
+}
+
+impl ConfigFile {
+    const DEFAULT_PATH: &'static str = "config.toml";
+
+    /// Loads the file set in the environment or the default file if there is one
+    ///
+    /// Fails if a file that was asked for is missing, is not valid TOML or contains keys that are not known
+    pub fn load() -> anyhow::Result<Self> {
+        let path = match std::env::var(PATH_ENV_VAR) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => {
+                let path = PathBuf::from(Self::DEFAULT_PATH);
+                if !path.exists() {
+                    info!("No config file found at {}", path.display());
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let values = Self::parse(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        info!("Loaded config file {}", path.display());
+        Ok(Self {
+            path: Some(path),
+            values,
+        })
+    }
+
+    fn parse(contents: &str) -> anyhow::Result<BTreeMap<String, String>> {
+        let table: toml::Table = toml::from_str(contents).context("not valid TOML")?;
+        let known: Vec<String> = KeyName::ALL.iter().map(|x| x.file_key()).collect();
+        let mut values = BTreeMap::new();
+        let mut problems = vec![];
+        for (key, value) in table {
+            if !known.contains(&key) {
+                problems.push(format!(
+                    "unknown key `{key}` (valid keys are: {})",
+                    known.join(", ")
+                ));
+                continue;
+            }
+            let value = match value {
+                toml::Value::String(x) => x,
+                toml::Value::Integer(x) => x.to_string(),
+                toml::Value::Boolean(x) => x.to_string(),
+                other => {
+                    problems.push(format!(
+                        "`{key}` must be a string or a number but is a {}",
+                        other.type_str()
+                    ));
+                    continue;
+                }
+            };
+            values.insert(key, value);
+        }
+        if !problems.is_empty() {
+            bail!(problems.join("\n"));
+        }
+        Ok(values)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|x| x.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(contents: &str) -> String {
+        format!("{:#}", ConfigFile::parse(contents).unwrap_err())
+    }
+
+    #[test]
+    fn parse_empty_file() {
+        assert!(ConfigFile::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_integers_strings_and_booleans_as_text() {
+        let values = ConfigFile::parse(
+            "cohort_channel = 123\nadmin_channel = \"456\"\ntoken = \"secret\"\nveteran_cohorts = true",
+        )
+        .unwrap();
+        assert_eq!(values["cohort_channel"], "123");
+        assert_eq!(values["admin_channel"], "456");
+        assert_eq!(values["token"], "secret");
+        assert_eq!(values["veteran_cohorts"], "true");
+        assert_eq!(values.len(), 4);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        let error = parse_error("cohort_channel = 1\nCOHORT_CHANNEL = 2\nchannel = 3");
+        assert!(error.contains("unknown key `COHORT_CHANNEL`"), "{error}");
+        assert!(error.contains("unknown key `channel`"), "{error}");
+        assert!(error.contains("valid keys are: token, "), "{error}");
+        assert!(!error.contains("`cohort_channel` must"), "{error}");
+    }
+
+    #[test]
+    fn parse_rejects_non_scalar_values() {
+        let error =
+            parse_error("cohort_channel = [1, 2]\nadmin_channel = 1.5\n[auth_role_id]\nid = 3");
+        assert!(
+            error.contains("`cohort_channel` must be a string or a number but is a array"),
+            "{error}"
+        );
+        assert!(
+            error.contains("`admin_channel` must be a string or a number but is a float"),
+            "{error}"
+        );
+        assert!(
+            error.contains("`auth_role_id` must be a string or a number but is a table"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_toml() {
+        for contents in [
+            "cohort_channel = ",
+            "cohort_channel 123",
+            "cohort_channel = \"123",
+        ] {
+            let error = parse_error(contents);
+            assert!(error.starts_with("not valid TOML"), "{error}");
+        }
+    }
+}